@@ -0,0 +1,541 @@
+//! Command-line tool for keys, transactions and blocks.
+//!
+//! Transactions are read and written as hex encoded bincode, since signatures can't
+//! be read back from JSON. Requests, UTXO sets and blocks are read as JSON.
+
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tw_chain::crypto::sign_ed25519::{self as sign, PublicKey, SecretKey};
+use tw_chain::primitives::asset::Asset;
+use tw_chain::primitives::block::{build_hex_merkle_root_sync, build_hex_txs_hash, Block};
+use tw_chain::primitives::druid::DdeValues;
+use tw_chain::primitives::network::Network;
+use tw_chain::primitives::transaction::{
+    GenesisTxHashSpec, OutPoint, Transaction, TxConstructor, TxOut,
+};
+use tw_chain::script::lang::Script;
+use tw_chain::utils::fee_utils::FeeRate;
+use tw_chain::utils::script_utils::tx_is_valid_for_network;
+use tw_chain::utils::transaction_builder::TransactionBuilder;
+use tw_chain::utils::transaction_utils::{
+    construct_address_for, construct_dde_tx, construct_item_create_tx, construct_payment_tx_ins,
+    construct_tx_hash, update_input_signatures_for_network, ReceiverInfo,
+};
+
+const USAGE: &str = "Usage: tw_chain <command> [arguments]
+
+Commands:
+    keygen                                    Generate a keypair and its address
+    address <public_key> [address_version]    Get the address of a hex public key
+    payment <request.json>                    Build and sign a payment transaction
+    create <request.json>                     Build and sign an item create transaction
+    dde <request.json>                        Build and sign a dual double entry transaction
+    validate <tx_file> <utxos.json> [height] [network]
+                                              Validate a transaction against a UTXO set
+    decode-tx <tx_file>                       Pretty-print a transaction
+    decode-script <script_hex>                Pretty-print a bincode encoded script
+    merkle-root <block.json>                  Compute the merkle root and hash of a block";
+
+/// Keys signing for an output
+#[derive(Deserialize)]
+struct SigningKeys {
+    out_point: OutPoint,
+    public_key: String,
+    secret_key: String,
+}
+
+/// An output that may be spent, with the keys to sign for it
+#[derive(Deserialize)]
+struct SpendableOutput {
+    out_point: OutPoint,
+    tx_out: TxOut,
+    public_key: String,
+    secret_key: String,
+}
+
+/// An output to pay in a payment request
+#[derive(Deserialize)]
+struct Payment {
+    address: String,
+    asset: Asset,
+    #[serde(default)]
+    locktime: u64,
+}
+
+/// Request for a payment transaction
+#[derive(Deserialize)]
+struct PaymentRequest {
+    utxos: Vec<SpendableOutput>,
+    payments: Vec<Payment>,
+    change_address: String,
+    #[serde(default)]
+    fee_rate: u64,
+    #[serde(default)]
+    fee_address: String,
+    #[serde(default)]
+    block_number: Option<u64>,
+    #[serde(default)]
+    network: Network,
+}
+
+/// Request for an item create transaction
+#[derive(Deserialize)]
+struct CreateRequest {
+    block_number: u64,
+    public_key: String,
+    secret_key: String,
+    amount: u64,
+    #[serde(default)]
+    default_genesis_hash: bool,
+    #[serde(default)]
+    metadata: Option<String>,
+}
+
+/// Request for a dual double entry transaction
+#[derive(Deserialize)]
+struct DdeRequest {
+    druid_info: DdeValues,
+    inputs: Vec<SigningKeys>,
+    outputs: Vec<TxOut>,
+    #[serde(default)]
+    network: Network,
+}
+
+/// An unspent output in a UTXO set file, with the block number it was included in
+/// if known
+#[derive(Deserialize)]
+struct Utxo {
+    out_point: OutPoint,
+    tx_out: TxOut,
+    #[serde(default)]
+    height: Option<u64>,
+}
+
+/// A keypair and its address, as printed by `keygen`
+#[derive(Serialize)]
+struct KeyPairOutput {
+    public_key: String,
+    secret_key: String,
+    address: String,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match run(&args) {
+        Ok(output) => println!("{output}"),
+        Err(e) => {
+            eprintln!("{} {}", "error:".red().bold(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs a command, returning its output
+///
+/// ### Arguments
+///
+/// * `args`    - Command and its arguments
+fn run(args: &[String]) -> Result<String, String> {
+    let command = args.first().map(String::as_str).unwrap_or_default();
+
+    match command {
+        "keygen" => keygen(),
+        "address" => address(get_arg(args, 1)?, args.get(2)),
+        "payment" => build_payment(&read_json(get_arg(args, 1)?)?),
+        "create" => build_create(&read_json(get_arg(args, 1)?)?),
+        "dde" => build_dde(&read_json(get_arg(args, 1)?)?),
+        "validate" => validate(
+            get_arg(args, 1)?,
+            get_arg(args, 2)?,
+            args.get(3),
+            args.get(4),
+        ),
+        "decode-tx" => decode_tx(get_arg(args, 1)?),
+        "decode-script" => decode_script(get_arg(args, 1)?),
+        "merkle-root" => merkle_root(get_arg(args, 1)?),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+/// Generates a keypair and its address
+fn keygen() -> Result<String, String> {
+    let (public_key, secret_key) = sign::gen_keypair();
+
+    to_json(&KeyPairOutput {
+        public_key: hex::encode(public_key.as_ref()),
+        secret_key: hex::encode(secret_key.as_ref()),
+        address: construct_address_for(&public_key, None),
+    })
+}
+
+/// Gets the address of a public key
+///
+/// ### Arguments
+///
+/// * `public_key`      - Hex encoded public key
+/// * `address_version` - Address version, if not the current one
+fn address(public_key: &str, address_version: Option<&String>) -> Result<String, String> {
+    let address_version = address_version.map(|v| parse_number(v)).transpose()?;
+    Ok(construct_address_for(
+        &parse_public_key(public_key)?,
+        address_version,
+    ))
+}
+
+/// Builds and signs a payment transaction, choosing inputs from the given outputs
+///
+/// ### Arguments
+///
+/// * `request` - Payment request
+fn build_payment(request: &PaymentRequest) -> Result<String, String> {
+    let mut key_material = BTreeMap::new();
+    for utxo in &request.utxos {
+        let keys = parse_keypair(&utxo.public_key, &utxo.secret_key)?;
+        key_material.insert(utxo.out_point.clone(), keys);
+    }
+
+    let mut builder = TransactionBuilder::new()
+        .with_utxos(
+            request
+                .utxos
+                .iter()
+                .map(|u| (u.out_point.clone(), u.tx_out.clone())),
+        )
+        .with_change_address(request.change_address.clone())
+        .with_fee(FeeRate(request.fee_rate), request.fee_address.clone())
+        .with_network(request.network)
+        .with_key_material(key_material);
+
+    if let Some(block_number) = request.block_number {
+        builder = builder.with_current_block_number(block_number);
+    }
+    for payment in &request.payments {
+        let receiver = ReceiverInfo {
+            address: payment.address.clone(),
+            asset: payment.asset.clone(),
+        };
+        builder = builder.pay_to(receiver, payment.locktime);
+    }
+
+    format_tx(&builder.build()?)
+}
+
+/// Builds and signs an item create transaction
+///
+/// ### Arguments
+///
+/// * `request` - Create request
+fn build_create(request: &CreateRequest) -> Result<String, String> {
+    let (public_key, secret_key) = parse_keypair(&request.public_key, &request.secret_key)?;
+    let genesis_hash_spec = if request.default_genesis_hash {
+        GenesisTxHashSpec::Default
+    } else {
+        GenesisTxHashSpec::Create
+    };
+
+    format_tx(&construct_item_create_tx(
+        request.block_number,
+        public_key,
+        &secret_key,
+        request.amount,
+        genesis_hash_spec,
+        None,
+        request.metadata.clone(),
+    ))
+}
+
+/// Builds and signs a dual double entry transaction
+///
+/// ### Arguments
+///
+/// * `request` - DDE request
+fn build_dde(request: &DdeRequest) -> Result<String, String> {
+    let mut key_material = BTreeMap::new();
+    let mut tx_values = Vec::new();
+
+    for input in &request.inputs {
+        let keys = parse_keypair(&input.public_key, &input.secret_key)?;
+        key_material.insert(input.out_point.clone(), keys);
+        tx_values.push(TxConstructor {
+            previous_out: input.out_point.clone(),
+            signatures: Vec::new(),
+            pub_keys: Vec::new(),
+            address_version: None,
+        });
+    }
+
+    let mut tx = construct_dde_tx(
+        request.druid_info.clone(),
+        construct_payment_tx_ins(tx_values),
+        request.outputs.clone(),
+        None,
+        &key_material,
+    );
+    tx.inputs = update_input_signatures_for_network(&tx, request.network, &key_material);
+
+    format_tx(&tx)
+}
+
+/// Validates a transaction's scripts, amounts and locks against a UTXO set. Relative
+/// locks can only be checked for outputs whose height is given.
+///
+/// ### Arguments
+///
+/// * `tx_file`     - File holding the hex encoded transaction
+/// * `utxo_file`   - JSON file holding the UTXO set
+/// * `height`      - Current block number, zero if not given
+/// * `network`     - Network the transaction is signed for, mainnet if not given
+fn validate(
+    tx_file: &str,
+    utxo_file: &str,
+    height: Option<&String>,
+    network: Option<&String>,
+) -> Result<String, String> {
+    let tx = read_tx(tx_file)?;
+    let utxos: Vec<Utxo> = read_json(utxo_file)?;
+    let utxo_heights: BTreeMap<OutPoint, u64> = utxos
+        .iter()
+        .filter_map(|utxo| Some((utxo.out_point.clone(), utxo.height?)))
+        .collect();
+    let utxo_set: BTreeMap<OutPoint, TxOut> = utxos
+        .into_iter()
+        .map(|utxo| (utxo.out_point, utxo.tx_out))
+        .collect();
+    let height = height.map(|h| parse_number(h)).transpose()?.unwrap_or(0);
+    let network = network
+        .map(|n| parse_network(n))
+        .transpose()?
+        .unwrap_or_default();
+
+    let (is_valid, message) = tx_is_valid_for_network(
+        &tx,
+        network,
+        height,
+        |out_point| utxo_set.get(out_point),
+        |out_point| utxo_heights.get(out_point).copied(),
+    );
+    if !is_valid {
+        return Err(format!("Invalid transaction: {message}"));
+    }
+    Ok(format!(
+        "{} transaction {} can be spent at block {} on {:?}",
+        "valid:".green().bold(),
+        construct_tx_hash(&tx),
+        height,
+        network
+    ))
+}
+
+/// Pretty-prints a transaction
+///
+/// ### Arguments
+///
+/// * `tx_file` - File holding the hex encoded transaction
+fn decode_tx(tx_file: &str) -> Result<String, String> {
+    let tx = read_tx(tx_file)?;
+    Ok(format!(
+        "{} {}\n{}",
+        "hash:".bold(),
+        construct_tx_hash(&tx),
+        to_json(&tx)?
+    ))
+}
+
+/// Pretty-prints a script
+///
+/// ### Arguments
+///
+/// * `script_hex`  - Hex encoded bincode of the script
+fn decode_script(script_hex: &str) -> Result<String, String> {
+    let bytes = hex::decode(script_hex.trim()).map_err(|e| format!("Invalid hex: {e}"))?;
+    let script: Script =
+        bincode::deserialize(&bytes).map_err(|e| format!("Invalid script: {e}"))?;
+    Ok(format!("{script:#?}"))
+}
+
+/// Computes the merkle root and transactions hash of a block
+///
+/// ### Arguments
+///
+/// * `block_file`  - JSON file holding the block
+fn merkle_root(block_file: &str) -> Result<String, String> {
+    let block: Block = read_json(block_file)?;
+    let merkle_root = build_hex_merkle_root_sync(&block.transactions);
+    let txs_hash = build_hex_txs_hash(&block.transactions);
+    let (header_root, header_hash) = &block.header.txs_merkle_root_and_hash;
+
+    let matches = if merkle_root == *header_root && txs_hash == *header_hash {
+        "matches header".green()
+    } else {
+        "does not match header".red()
+    };
+    Ok(format!(
+        "{} {}\n{} {}\n{}",
+        "merkle root:".bold(),
+        merkle_root,
+        "txs hash:".bold(),
+        txs_hash,
+        matches
+    ))
+}
+
+/*---- HELPERS ----*/
+
+/// Gets a required argument
+fn get_arg(args: &[String], index: usize) -> Result<&str, String> {
+    args.get(index)
+        .map(String::as_str)
+        .ok_or_else(|| USAGE.to_string())
+}
+
+/// Parses a decimal number argument
+fn parse_number(value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|e| format!("Invalid number {value}: {e}"))
+}
+
+/// Parses a network name, such as `testnet`
+fn parse_network(value: &str) -> Result<Network, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_owned()))
+        .map_err(|_| format!("Unknown network {value}"))
+}
+
+/// Parses a hex encoded public key
+fn parse_public_key(public_key: &str) -> Result<PublicKey, String> {
+    hex::decode(public_key)
+        .ok()
+        .and_then(|bytes| PublicKey::from_slice(&bytes))
+        .ok_or_else(|| format!("Invalid public key {public_key}"))
+}
+
+/// Parses a hex encoded public key and secret key
+fn parse_keypair(public_key: &str, secret_key: &str) -> Result<(PublicKey, SecretKey), String> {
+    let secret_key = hex::decode(secret_key)
+        .ok()
+        .and_then(|bytes| SecretKey::from_slice(&bytes))
+        .ok_or_else(|| "Invalid secret key".to_string())?;
+    Ok((parse_public_key(public_key)?, secret_key))
+}
+
+/// Reads and parses a JSON file
+fn read_json<T: serde::de::DeserializeOwned>(path: &str) -> Result<T, String> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+    serde_json::from_str(&contents).map_err(|e| format!("Invalid JSON in {path}: {e}"))
+}
+
+/// Reads a file holding a hex encoded transaction
+fn read_tx(path: &str) -> Result<Transaction, String> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+    let bytes = hex::decode(contents.trim()).map_err(|e| format!("Invalid hex in {path}: {e}"))?;
+    bincode::deserialize(&bytes).map_err(|e| format!("Invalid transaction in {path}: {e}"))
+}
+
+/// Formats a built transaction as its hash, hex encoding and JSON view
+fn format_tx(tx: &Transaction) -> Result<String, String> {
+    let bytes = bincode::serialize(tx).map_err(|e| format!("Invalid transaction: {e}"))?;
+    Ok(format!(
+        "{} {}\n{} {}\n{}",
+        "hash:".bold(),
+        construct_tx_hash(tx),
+        "hex:".bold(),
+        hex::encode(bytes),
+        to_json(tx)?
+    ))
+}
+
+/// Formats a value as pretty-printed JSON
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|e| format!("Failed to format JSON: {e}"))
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tw_chain::primitives::asset::TokenAmount;
+    use tw_chain::utils::transaction_utils::construct_address;
+
+    /// Writes a file for a test to the temporary directory, returning its path
+    fn write_temp_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("tw_chain_{}_{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Runs a command with the given arguments
+    fn run_command(args: &[&str]) -> Result<String, String> {
+        run(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    /// Writes a transaction spending a single output, relative locked for `sequence`
+    /// blocks, and the UTXO set it spends from, returning their paths
+    fn write_test_tx(name: &str, network: Network, sequence: u64, height: u64) -> (String, String) {
+        let (pk, sk) = sign::gen_keypair();
+        let out_point = OutPoint::new("tx_hash".to_owned(), 0);
+        let tx_out = TxOut::new_token_amount(construct_address(&pk), TokenAmount(10), None);
+
+        let tx = TransactionBuilder::new()
+            .with_utxos(vec![(out_point.clone(), tx_out.clone())])
+            .with_key_material(std::iter::once((out_point.clone(), (pk, sk))).collect())
+            .with_change_address(construct_address(&pk))
+            .with_network(network)
+            .with_relative_lock(sequence)
+            .pay_to(
+                ReceiverInfo {
+                    address: construct_address(&pk),
+                    asset: Asset::token_u64(10),
+                },
+                0,
+            )
+            .build()
+            .unwrap();
+        let utxos = serde_json::json!([{
+            "out_point": out_point,
+            "tx_out": tx_out,
+            "height": height,
+        }]);
+
+        (
+            write_temp_file(
+                &format!("{name}.tx"),
+                &hex::encode(bincode::serialize(&tx).unwrap()),
+            ),
+            write_temp_file(&format!("{name}.json"), &utxos.to_string()),
+        )
+    }
+
+    #[test]
+    /// Checks that relative locks are validated against the heights in the UTXO set
+    fn test_validate_relative_locked_tx() {
+        let (tx_file, utxo_file) = write_test_tx("relative", Network::Mainnet, 3, 5);
+
+        let too_early = run_command(&["validate", &tx_file, &utxo_file, "7"]);
+        let valid = run_command(&["validate", &tx_file, &utxo_file, "8"]).unwrap();
+
+        assert_eq!(
+            too_early,
+            Err("Invalid transaction: Relative locktime not expired".to_string())
+        );
+        assert!(valid.contains("can be spent at block 8 on Mainnet"));
+    }
+
+    #[test]
+    /// Checks that transactions are validated for the given network
+    fn test_validate_tx_network() {
+        let (tx_file, utxo_file) = write_test_tx("network", Network::Testnet, 0, 0);
+
+        let mainnet = run_command(&["validate", &tx_file, &utxo_file]);
+        let testnet = run_command(&["validate", &tx_file, &utxo_file, "0", "testnet"]).unwrap();
+        let unknown = run_command(&["validate", &tx_file, &utxo_file, "0", "othernet"]);
+
+        assert!(mainnet.is_err());
+        assert!(testnet.contains("can be spent at block 0 on Testnet"));
+        assert_eq!(unknown, Err("Unknown network othernet".to_string()));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::primitives::asset::Asset;
+use crate::primitives::block::Block;
+use crate::primitives::network::Network;
+use crate::primitives::transaction::{Transaction, TxIn, TxOut};
+use crate::script::lang::Script;
+use crate::utils::consensus_utils::ConsensusRules;
+use crate::utils::difficulty_utils::DifficultyParams;
+use crate::utils::pow_utils::{difficulty_from_compact, target_from_compact};
+use crate::utils::transaction_utils::{construct_tx_core, construct_tx_hash};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+/// An initial allocation of assets in the genesis block
+///
+/// `address`   - Address receiving the assets
+/// `value`     - Assets allocated
+/// `locktime`  - Block number before which the allocation can't be spent
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisAllocation {
+    pub address: String,
+    pub value: Asset,
+    #[serde(default)]
+    pub locktime: u64,
+}
+
+/// Specification of a chain, from which its genesis block is built. Specifications are
+/// read from JSON rather than TOML, as TOML has no null for unset optional values and
+/// only allows string keys, while consensus rules are keyed by transaction version.
+///
+/// `name`                      - Name of the chain
+/// `network`                   - Network transactions on the chain are signed for,
+///                               committed to by the genesis block
+/// `timestamp`                 - Timestamp of the genesis block
+/// `initial_compact_target`    - Compact target of the genesis block
+/// `difficulty`                - Difficulty retargeting parameters
+/// `consensus_rules`           - Rule versions, their activation heights and the legacy
+///                               address policy of the chain
+/// `allocations`               - Initial allocations, paid in order, to hex addresses
+///                               or addresses checksummed for `network`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainSpec {
+    pub name: String,
+    #[serde(default)]
+    pub network: Network,
+    pub timestamp: i64,
+    pub initial_compact_target: u32,
+    #[serde(default)]
+    pub difficulty: DifficultyParams,
+    #[serde(default)]
+    pub consensus_rules: ConsensusRules,
+    pub allocations: Vec<GenesisAllocation>,
+}
+
+impl ChainSpec {
+    /// Reads and validates a chain specification from JSON
+    ///
+    /// ### Arguments
+    ///
+    /// * `json`    - JSON encoded chain specification
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let spec: Self =
+            serde_json::from_str(json).map_err(|e| format!("Invalid chain spec: {e}"))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Encodes the chain specification as JSON
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Invalid chain spec: {e}"))
+    }
+
+    /// Checks that the initial target is valid and allocations have valid addresses
+    pub fn validate(&self) -> Result<(), String> {
+        let initial_target = target_from_compact(self.initial_compact_target);
+        let pow_limit = target_from_compact(self.difficulty.pow_limit_compact);
+
+        match (initial_target, pow_limit) {
+            (Some(initial_target), Some(pow_limit)) if initial_target <= pow_limit => {}
+            _ => {
+                error!("CHAIN SPEC HAS INVALID INITIAL TARGET");
+                return Err("Chain spec initial target is invalid".to_string());
+            }
+        }
+
+        for allocation in &self.allocations {
+            if let Err(e) = self.network.parse_address(&allocation.address) {
+                error!("CHAIN SPEC ALLOCATION HAS INVALID ADDRESS");
+                return Err(format!(
+                    "Chain spec allocation address {} is invalid: {e}",
+                    allocation.address
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds the genesis block of a chain and the transaction paying its allocations.
+/// The same specification always gives the same genesis hash, and chains for different
+/// networks never share a genesis hash.
+///
+/// ### Arguments
+///
+/// * `spec`    - Chain specification
+pub fn build_genesis(spec: &ChainSpec) -> (Block, Vec<Transaction>) {
+    let transactions = if spec.allocations.is_empty() {
+        Vec::new()
+    } else {
+        let tx_in = TxIn::new_from_script(Script::new_for_coinbase(0));
+        let tx_outs = spec
+            .allocations
+            .iter()
+            .map(|allocation| TxOut {
+                value: allocation.value.clone(),
+                locktime: allocation.locktime,
+                script_public_key: Some(get_hex_address(spec.network, &allocation.address)),
+            })
+            .collect();
+        vec![construct_tx_core(vec![tx_in], tx_outs, None)]
+    };
+
+    let mut block = Block::new();
+    block.header.timestamp = spec.timestamp;
+    block.header.seed_value = spec.network.id().to_be_bytes().to_vec();
+    block.header.difficulty = difficulty_from_compact(spec.initial_compact_target);
+    block.transactions = transactions.iter().map(construct_tx_hash).collect();
+    block.set_txs_merkle_root_and_hash_sync();
+    block.set_bits();
+
+    debug!(
+        "Built genesis block {} for chain {}",
+        block.header.hash(),
+        spec.name
+    );
+    (block, transactions)
+}
+
+/// Gets the hex form of an allocation address, which may be checksummed for the network
+///
+/// ### Arguments
+///
+/// * `network` - Network of the chain
+/// * `address` - Allocation address
+fn get_hex_address(network: Network, address: &str) -> String {
+    match network.parse_address(address) {
+        Ok(address) => address.to_string(),
+        Err(_) => address.to_owned(),
+    }
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::address::Address;
+    use crate::primitives::block::FullBlock;
+    use crate::utils::consensus_utils::ValidationRules;
+    use crate::utils::migration_utils::LegacyAddressPolicy;
+    use std::collections::BTreeSet;
+
+    const TEST_CHAIN_SPEC: &str = r#"{
+        "name": "testnet",
+        "timestamp": 1700000000,
+        "initial_compact_target": 545259519,
+        "allocations": [
+            {
+                "address": "a0b08e623c6800bb27bddb5d7d6c5d7f4d1f0f0e5c6b4a3b2c1d0e0f1a2b3c4d",
+                "value": { "Token": 1000000 }
+            },
+            {
+                "address": "b0b08e623c6800bb27bddb5d7d6c5d7f4d1f0f0e5c6b4a3b2c1d0e0f1a2b3c4d",
+                "value": { "Token": 500 },
+                "locktime": 100
+            }
+        ]
+    }"#;
+
+    #[test]
+    /// Checks that a chain spec always builds the same genesis block
+    fn test_build_genesis_is_deterministic() {
+        let spec = ChainSpec::from_json(TEST_CHAIN_SPEC).unwrap();
+        let (block, transactions) = build_genesis(&spec);
+        let (rebuilt_block, _) =
+            build_genesis(&ChainSpec::from_json(&spec.to_json().unwrap()).unwrap());
+
+        assert_eq!(block.header.hash(), rebuilt_block.header.hash());
+        assert_eq!(
+            block.header.hash(),
+            "d7aba35a1d3ab9474eb5a606de0f7a09442019e328d08b992ff5f5af7c8028a8"
+        );
+        assert_eq!(spec.network, Network::Mainnet);
+        assert_eq!(block.header.seed_value, vec![0, 0, 0, 0]);
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].outputs[1].locktime, 100);
+        assert!(transactions[0].inputs[0].previous_out.is_none());
+        assert!(FullBlock::from_block(block, transactions)
+            .unwrap()
+            .has_valid_txs_merkle_root_and_hash());
+    }
+
+    #[test]
+    /// Checks that the same allocations on different networks give different genesis blocks
+    fn test_genesis_commits_to_network() {
+        let spec = ChainSpec::from_json(TEST_CHAIN_SPEC).unwrap();
+        let hashes: BTreeSet<String> = Network::ALL
+            .iter()
+            .map(|network| {
+                let mut spec = spec.clone();
+                spec.network = *network;
+                build_genesis(&spec).0.header.hash()
+            })
+            .collect();
+
+        assert_eq!(hashes.len(), Network::ALL.len());
+    }
+
+    #[test]
+    /// Checks that chain specs with invalid targets or allocations are rejected
+    fn test_invalid_chain_spec() {
+        let mut spec = ChainSpec::from_json(TEST_CHAIN_SPEC).unwrap();
+        spec.initial_compact_target = 0x21010000;
+        let mut no_address = ChainSpec::from_json(TEST_CHAIN_SPEC).unwrap();
+        no_address.allocations[0].address = String::new();
+        let mut short_address = ChainSpec::from_json(TEST_CHAIN_SPEC).unwrap();
+        short_address.allocations[0].address = "a".to_owned();
+        let mut non_hex_address = ChainSpec::from_json(TEST_CHAIN_SPEC).unwrap();
+        non_hex_address.allocations[1].address = "z".repeat(64);
+
+        assert!(spec.validate().is_err());
+        assert!(no_address.validate().is_err());
+        assert!(short_address.validate().is_err());
+        assert!(non_hex_address.validate().is_err());
+        assert!(ChainSpec::from_json("{}").is_err());
+    }
+
+    #[test]
+    /// Checks that consensus rules are read from the chain spec, defaulting when absent
+    fn test_chain_spec_consensus_rules() {
+        let json = r#"{
+            "name": "private",
+            "timestamp": 0,
+            "initial_compact_target": 545259519,
+            "consensus_rules": {
+                "rule_sets": {
+                    "2": {
+                        "activation_height": 500,
+                        "rules": {
+                            "max_outputs": 10,
+                            "allow_lock_height": false,
+                            "allow_relative_locks": true,
+                            "allow_p2sh": true,
+                            "disabled_opcodes": []
+                        }
+                    }
+                },
+                "address_policy": { "v0_retirement_height": 1000 }
+            },
+            "allocations": []
+        }"#;
+        let expected = ConsensusRules::new()
+            .with_rule_set(
+                2,
+                500,
+                ValidationRules {
+                    max_outputs: 10,
+                    allow_lock_height: false,
+                    ..Default::default()
+                },
+            )
+            .with_address_policy(LegacyAddressPolicy::new().with_v0_retirement_height(1000));
+
+        let spec = ChainSpec::from_json(json).unwrap();
+        let rebuilt = ChainSpec::from_json(&spec.to_json().unwrap()).unwrap();
+
+        assert_eq!(spec.consensus_rules, expected);
+        assert_eq!(rebuilt, spec);
+        assert_eq!(
+            ChainSpec::from_json(TEST_CHAIN_SPEC)
+                .unwrap()
+                .consensus_rules,
+            ConsensusRules::default()
+        );
+    }
+
+    #[test]
+    /// Checks that checksummed allocation addresses must be for the spec's network and
+    /// are paid in hex
+    fn test_chain_spec_allocation_network() {
+        let address = Address::Standard([7; 32]);
+        let mut testnet = ChainSpec::from_json(TEST_CHAIN_SPEC).unwrap();
+        testnet.network = Network::Testnet;
+        testnet.allocations[0].address = Network::Testnet.encode_address(&address);
+        let mut mismatched = testnet.clone();
+        mismatched.allocations[0].address = Network::Mainnet.encode_address(&address);
+
+        let (_, transactions) = build_genesis(&testnet);
+
+        assert!(testnet.validate().is_ok());
+        assert_eq!(
+            mismatched.validate(),
+            Err(format!(
+                "Chain spec allocation address {} is invalid: Address is for Mainnet, not Testnet",
+                mismatched.allocations[0].address
+            ))
+        );
+        assert_eq!(
+            transactions[0].outputs[0].script_public_key,
+            Some(address.to_string())
+        );
+    }
+}
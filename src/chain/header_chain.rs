@@ -0,0 +1,394 @@
+use crate::constants::MEDIAN_TIME_PAST_WINDOW;
+use crate::primitives::block::BlockHeader;
+use crate::utils::difficulty_utils::{
+    header_has_valid_difficulty, header_has_valid_timestamp, DifficultyParams,
+};
+use crate::utils::pow_utils::get_target_work;
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::{debug, error};
+
+/// A header stored in the chain, with the total work of the chain ending at it
+#[derive(Clone, Debug)]
+pub struct HeaderEntry {
+    pub header: BlockHeader,
+    pub cumulative_work: u128,
+}
+
+/// A change of best chain. Blocks in `disconnect` are listed from the old tip back
+/// to the fork point and should be rolled back first, then blocks in `connect` are
+/// listed from the fork point up to the new tip and should be applied in order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reorg {
+    pub disconnect: Vec<String>,
+    pub connect: Vec<String>,
+}
+
+/// Headers stored by hash from a single genesis, with every fork kept. Headers must
+/// meet their proof-of-work target, carry the retargeted difficulty and be timestamped
+/// after the median time past, so work can only be claimed by mining it. The best
+/// chain is the one with the most cumulative work, keeping the first seen tip on a tie.
+#[derive(Clone, Debug)]
+pub struct HeaderChain {
+    headers: BTreeMap<String, HeaderEntry>,
+    tips: BTreeSet<String>,
+    genesis_hash: String,
+    best_tip: String,
+    params: DifficultyParams,
+}
+
+impl HeaderChain {
+    /// Creates a new chain from its genesis header
+    ///
+    /// ### Arguments
+    ///
+    /// * `genesis` - Header of the genesis block
+    /// * `params`  - Difficulty parameters of the network
+    pub fn new(genesis: BlockHeader, params: DifficultyParams) -> Self {
+        let genesis_hash = genesis.hash();
+        let cumulative_work = genesis.get_target().map_or(0, |t| get_target_work(&t));
+
+        let mut headers = BTreeMap::new();
+        headers.insert(
+            genesis_hash.clone(),
+            HeaderEntry {
+                header: genesis,
+                cumulative_work,
+            },
+        );
+
+        Self {
+            headers,
+            tips: std::iter::once(genesis_hash.clone()).collect(),
+            best_tip: genesis_hash.clone(),
+            genesis_hash,
+            params,
+        }
+    }
+
+    /// Gets the hash of the genesis header
+    pub fn genesis_hash(&self) -> &str {
+        &self.genesis_hash
+    }
+
+    /// Gets the hash of the tip of the best chain
+    pub fn best_tip(&self) -> &str {
+        &self.best_tip
+    }
+
+    /// Gets the header at the tip of the best chain
+    pub fn best_header(&self) -> &BlockHeader {
+        &self.headers[&self.best_tip].header
+    }
+
+    /// Gets the hashes of the tips of every fork
+    pub fn tips(&self) -> &BTreeSet<String> {
+        &self.tips
+    }
+
+    /// Checks whether a header is stored
+    ///
+    /// ### Arguments
+    ///
+    /// * `hash`    - Hash of the header
+    pub fn contains(&self, hash: &str) -> bool {
+        self.headers.contains_key(hash)
+    }
+
+    /// Gets a stored header and its cumulative work
+    ///
+    /// ### Arguments
+    ///
+    /// * `hash`    - Hash of the header
+    pub fn get(&self, hash: &str) -> Option<&HeaderEntry> {
+        self.headers.get(hash)
+    }
+
+    /// Gets up to `count` headers ending at `hash`, in ascending block order
+    ///
+    /// ### Arguments
+    ///
+    /// * `hash`    - Hash of the last header
+    /// * `count`   - Maximum number of headers
+    pub fn get_ancestors(&self, hash: &str, count: usize) -> Vec<BlockHeader> {
+        let mut ancestors = Vec::new();
+        let mut current = self.headers.get(hash);
+
+        while let Some(entry) = current {
+            if ancestors.len() == count {
+                break;
+            }
+            ancestors.push(entry.header.clone());
+            current = entry
+                .header
+                .previous_hash
+                .as_ref()
+                .and_then(|previous| self.headers.get(previous));
+        }
+
+        ancestors.reverse();
+        ancestors
+    }
+
+    /// Checks whether a header is on the best chain
+    ///
+    /// ### Arguments
+    ///
+    /// * `hash`    - Hash of the header
+    pub fn is_in_best_chain(&self, hash: &str) -> bool {
+        match self.headers.get(hash) {
+            Some(entry) => self.get_best_chain_hash(entry.header.b_num).as_deref() == Some(hash),
+            None => false,
+        }
+    }
+
+    /// Gets the hash of the best chain's header at a block number
+    ///
+    /// ### Arguments
+    ///
+    /// * `b_num`   - Block number
+    pub fn get_best_chain_hash(&self, b_num: u64) -> Option<String> {
+        let mut hash = self.best_tip.clone();
+
+        loop {
+            let header = &self.headers.get(&hash)?.header;
+            if header.b_num == b_num {
+                return Some(hash);
+            }
+            if header.b_num < b_num {
+                return None;
+            }
+            hash = header.previous_hash.clone()?;
+        }
+    }
+
+    /// Verifies and adds a header extending a stored header. Returns the change of
+    /// best chain if the new header becomes the best tip.
+    ///
+    /// ### Arguments
+    ///
+    /// * `header`          - Header to add
+    /// * `current_time`    - Current time, in seconds
+    pub fn add_header(
+        &mut self,
+        header: BlockHeader,
+        current_time: i64,
+    ) -> Result<Option<Reorg>, String> {
+        let hash = header.hash();
+        if self.contains(&hash) {
+            return Err("Header is already in the chain".to_string());
+        }
+
+        let previous_hash = header
+            .previous_hash
+            .clone()
+            .ok_or_else(|| "Header has no previous hash".to_string())?;
+        let previous = self
+            .headers
+            .get(&previous_hash)
+            .ok_or_else(|| "Header does not extend a known header".to_string())?;
+
+        if header.b_num != previous.header.b_num + 1 {
+            error!("HEADER BLOCK NUMBER DOES NOT FOLLOW PREVIOUS");
+            return Err("Header block number does not follow its previous header".to_string());
+        }
+
+        if !header.meets_target() {
+            error!("BLOCK HEADER DOES NOT MEET ITS TARGET");
+            return Err("Header does not meet its proof-of-work target".to_string());
+        }
+
+        let previous_headers =
+            self.get_ancestors(&previous_hash, self.params.get_retarget_window());
+        if !header_has_valid_difficulty(&header, &previous_headers, &self.params) {
+            return Err("Header has an incorrect difficulty".to_string());
+        }
+
+        let previous_headers = self.get_ancestors(&previous_hash, MEDIAN_TIME_PAST_WINDOW);
+        let (valid_timestamp, reason) =
+            header_has_valid_timestamp(&header, &previous_headers, current_time);
+        if !valid_timestamp {
+            return Err(reason);
+        }
+
+        let target = header
+            .get_target()
+            .ok_or_else(|| "Header has an invalid difficulty".to_string())?;
+        let cumulative_work = self.headers[&previous_hash]
+            .cumulative_work
+            .saturating_add(get_target_work(&target));
+
+        self.tips.remove(&previous_hash);
+        self.tips.insert(hash.clone());
+        self.headers.insert(
+            hash.clone(),
+            HeaderEntry {
+                header,
+                cumulative_work,
+            },
+        );
+
+        if cumulative_work <= self.headers[&self.best_tip].cumulative_work {
+            debug!("Stored header {} on a side chain", hash);
+            return Ok(None);
+        }
+
+        let reorg = self.get_reorg(&self.best_tip, &hash);
+        debug!(
+            "New best tip {}, disconnecting {} and connecting {} blocks",
+            hash,
+            reorg.disconnect.len(),
+            reorg.connect.len()
+        );
+        self.best_tip = hash;
+        Ok(Some(reorg))
+    }
+
+    /// Gets the blocks to disconnect and connect to move from one tip to another
+    ///
+    /// ### Arguments
+    ///
+    /// * `old_tip` - Hash of the current tip
+    /// * `new_tip` - Hash of the new tip
+    fn get_reorg(&self, old_tip: &str, new_tip: &str) -> Reorg {
+        let mut reorg = Reorg::default();
+        let mut old = old_tip.to_string();
+        let mut new = new_tip.to_string();
+
+        while old != new {
+            let old_entry = &self.headers[&old];
+            let new_entry = &self.headers[&new];
+
+            if new_entry.header.b_num >= old_entry.header.b_num {
+                reorg.connect.push(new.clone());
+                new = new_entry.header.previous_hash.clone().unwrap_or_default();
+            } else {
+                reorg.disconnect.push(old.clone());
+                old = old_entry.header.previous_hash.clone().unwrap_or_default();
+            }
+        }
+
+        reorg.connect.reverse();
+        reorg
+    }
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MAX_POW_TARGET_COMPACT;
+    use crate::utils::difficulty_utils::get_next_compact_target;
+    use crate::utils::pow_utils::{difficulty_from_compact, mine_header};
+
+    const CURRENT_TIME: i64 = 1_000;
+
+    /// Creates a mined header following `previous` at the retargeted difficulty
+    fn next_header(chain: &HeaderChain, previous: &BlockHeader, timestamp: i64) -> BlockHeader {
+        let params = DifficultyParams::default();
+        let previous_headers = chain.get_ancestors(&previous.hash(), params.get_retarget_window());
+
+        let mut header = BlockHeader::new();
+        header.b_num = previous.b_num + 1;
+        header.timestamp = timestamp;
+        header.previous_hash = Some(previous.hash());
+        header.difficulty =
+            difficulty_from_compact(get_next_compact_target(&previous_headers, &params));
+        assert!(mine_header(&mut header, 10_000));
+        header
+    }
+
+    fn genesis() -> BlockHeader {
+        let mut genesis = BlockHeader::new();
+        genesis.difficulty = difficulty_from_compact(MAX_POW_TARGET_COMPACT);
+        genesis
+    }
+
+    #[test]
+    /// Checks that extending the best chain connects each new header
+    fn test_extend_best_chain() {
+        let genesis = genesis();
+        let mut chain = HeaderChain::new(genesis.clone(), DifficultyParams::default());
+        let first = next_header(&chain, &genesis, 60);
+        let first_update = chain.add_header(first.clone(), CURRENT_TIME).unwrap();
+        let second = next_header(&chain, &first, 120);
+        chain.add_header(second.clone(), CURRENT_TIME).unwrap();
+
+        let mut unlinked = next_header(&chain, &second, 180);
+        unlinked.previous_hash = Some(BlockHeader::new().hash());
+
+        assert_eq!(
+            first_update,
+            Some(Reorg {
+                disconnect: Vec::new(),
+                connect: vec![first.hash()],
+            })
+        );
+        assert_eq!(chain.best_tip(), second.hash());
+        assert_eq!(chain.tips().len(), 1);
+        assert_eq!(chain.get_ancestors(&second.hash(), 10).len(), 3);
+        assert_eq!(chain.get_best_chain_hash(1), Some(first.hash()));
+        assert!(chain.add_header(first, CURRENT_TIME).is_err());
+        assert!(chain.add_header(unlinked, CURRENT_TIME).is_err());
+    }
+
+    #[test]
+    /// Checks that a fork with more work becomes the best chain and reports a reorg
+    fn test_reorg_to_chain_with_most_work() {
+        let genesis = genesis();
+        let mut chain = HeaderChain::new(genesis.clone(), DifficultyParams::default());
+
+        let a1 = next_header(&chain, &genesis, 60);
+        chain.add_header(a1.clone(), CURRENT_TIME).unwrap();
+        let a2 = next_header(&chain, &a1, 120);
+        chain.add_header(a2.clone(), CURRENT_TIME).unwrap();
+
+        // An equal-work fork does not replace the first seen chain
+        let b1 = next_header(&chain, &genesis, 61);
+        assert_eq!(chain.add_header(b1.clone(), CURRENT_TIME).unwrap(), None);
+        let b2 = next_header(&chain, &b1, 62);
+        assert_eq!(chain.add_header(b2.clone(), CURRENT_TIME).unwrap(), None);
+
+        // The fast block before it retargets the next header to a harder difficulty
+        let b3 = next_header(&chain, &b2, 63);
+        let reorg = chain.add_header(b3.clone(), CURRENT_TIME).unwrap();
+
+        assert_eq!(
+            reorg,
+            Some(Reorg {
+                disconnect: vec![a2.hash(), a1.hash()],
+                connect: vec![b1.hash(), b2.hash(), b3.hash()],
+            })
+        );
+        assert_eq!(chain.best_tip(), b3.hash());
+        assert_eq!(chain.tips().len(), 2);
+        assert!(chain.is_in_best_chain(&b1.hash()));
+        assert!(!chain.is_in_best_chain(&a1.hash()));
+    }
+
+    #[test]
+    /// Checks that headers claiming work they did not mine are rejected, so a single
+    /// unmined header with an all-zero target can't force a reorg
+    fn test_reject_unmined_and_wrong_difficulty_headers() {
+        let genesis = genesis();
+        let mut chain = HeaderChain::new(genesis.clone(), DifficultyParams::default());
+        let tip = next_header(&chain, &genesis, 60);
+        chain.add_header(tip.clone(), CURRENT_TIME).unwrap();
+
+        let mut zero_target = next_header(&chain, &genesis, 61);
+        zero_target.difficulty = vec![0; 4];
+        let mut unmined = next_header(&chain, &genesis, 62);
+        while unmined.meets_target() {
+            unmined.nonce_and_mining_tx_hash.0.push(0);
+        }
+        let mut wrong_difficulty = next_header(&chain, &genesis, 63);
+        wrong_difficulty.difficulty = difficulty_from_compact(0x2000ffff);
+        assert!(mine_header(&mut wrong_difficulty, 10_000));
+
+        assert!(chain.add_header(zero_target.clone(), CURRENT_TIME).is_err());
+        assert!(chain.add_header(unmined, CURRENT_TIME).is_err());
+        assert!(chain.add_header(wrong_difficulty, CURRENT_TIME).is_err());
+        assert!(!chain.contains(&zero_target.hash()));
+        assert_eq!(chain.best_tip(), tip.hash());
+    }
+}
@@ -0,0 +1,290 @@
+use crate::chain::store::{
+    decode, encode, get_address_index_prefix, ChainStorage, ChainStore, Column, StoreBatch,
+};
+use crate::primitives::asset::AssetValues;
+use crate::primitives::transaction::{OutPoint, TxOut};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::error;
+
+/// An output paying an indexed address
+///
+/// `tx_out`    - The output, with the genesis hash of created items filled in
+/// `spent_by`  - Hash of the transaction spending the output, if it is spent
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedOutput {
+    pub tx_out: TxOut,
+    pub spent_by: Option<String>,
+}
+
+/// Index of the outputs paying each address and the transactions spending them,
+/// read from `ChainStorage` that keeps address history. Unspent outputs come from
+/// the storage's address index, and spent outputs are written in the same batch as
+/// the block spending them, so the index always matches the connected chain.
+#[derive(Debug, Clone)]
+pub struct AddressIndexer<'a, S: ChainStore> {
+    storage: &'a ChainStorage<S>,
+}
+
+impl<'a, S: ChainStore> AddressIndexer<'a, S> {
+    /// Creates an address indexer over chain storage
+    ///
+    /// ### Arguments
+    ///
+    /// * `storage` - Chain storage keeping address history
+    pub fn new(storage: &'a ChainStorage<S>) -> Result<Self, String> {
+        if !storage.has_address_history() {
+            error!("CHAIN STORAGE DOES NOT KEEP ADDRESS HISTORY");
+            return Err("Chain storage does not keep address history".to_string());
+        }
+        Ok(Self { storage })
+    }
+
+    /// Gets every indexed output paying an address, spent or unspent, in outpoint order
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address to get the outputs of
+    pub fn get_outputs(&self, address: &str) -> Result<Vec<(OutPoint, IndexedOutput)>, String> {
+        let prefix = get_address_index_prefix(address);
+        let mut outputs = self
+            .storage
+            .store()
+            .get_prefix(Column::AddressHistory, &prefix)?
+            .into_iter()
+            .map(|(key, value)| Ok((decode(&key[prefix.len()..])?, decode(&value)?)))
+            .collect::<Result<BTreeMap<OutPoint, IndexedOutput>, String>>()?;
+
+        for (out_point, tx_out) in self.get_unspent_outputs(address)? {
+            let output = IndexedOutput {
+                tx_out,
+                spent_by: None,
+            };
+            outputs.insert(out_point, output);
+        }
+
+        Ok(outputs.into_iter().collect())
+    }
+
+    /// Gets the unspent outputs paying an address
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address to get the outputs of
+    pub fn get_unspent_outputs(&self, address: &str) -> Result<Vec<(OutPoint, TxOut)>, String> {
+        Ok(self
+            .storage
+            .get_address_utxos(address)?
+            .into_iter()
+            .map(|(out_point, tx_out)| {
+                let tx_out = with_fixed_hash(tx_out, &out_point);
+                (out_point, tx_out)
+            })
+            .collect())
+    }
+
+    /// Gets the balance of an address, with tokens and items per genesis hash
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address to get the balance of
+    pub fn get_balance(&self, address: &str) -> Result<AssetValues, String> {
+        let mut balance = AssetValues::default();
+        for (_, tx_out) in self.get_unspent_outputs(address)? {
+            balance.update_add(&tx_out.value);
+        }
+        Ok(balance)
+    }
+}
+
+/// Adds a write of a spent output to its address's history to a batch
+///
+/// ### Arguments
+///
+/// * `batch`       - Batch connecting the block spending the output
+/// * `out_point`   - Outpoint of the output
+/// * `tx_out`      - The spent output
+/// * `spent_by`    - Hash of the transaction spending the output
+pub(crate) fn put_spent_output(
+    batch: &mut StoreBatch,
+    out_point: &OutPoint,
+    tx_out: &TxOut,
+    spent_by: &str,
+) -> Result<(), String> {
+    if let Some(key) = get_address_history_key(out_point, tx_out)? {
+        let output = IndexedOutput {
+            tx_out: with_fixed_hash(tx_out.clone(), out_point),
+            spent_by: Some(spent_by.to_owned()),
+        };
+        batch.put(Column::AddressHistory, key, encode(&output)?);
+    }
+    Ok(())
+}
+
+/// Adds a deletion of a spent output from its address's history to a batch
+///
+/// ### Arguments
+///
+/// * `batch`       - Batch disconnecting the block spending the output
+/// * `out_point`   - Outpoint of the output
+/// * `tx_out`      - The spent output
+pub(crate) fn delete_spent_output(
+    batch: &mut StoreBatch,
+    out_point: &OutPoint,
+    tx_out: &TxOut,
+) -> Result<(), String> {
+    if let Some(key) = get_address_history_key(out_point, tx_out)? {
+        batch.delete(Column::AddressHistory, key);
+    }
+    Ok(())
+}
+
+/// Gets the address history key of an output, if it pays an address
+///
+/// ### Arguments
+///
+/// * `out_point`   - Outpoint of the output
+/// * `tx_out`      - The output
+fn get_address_history_key(
+    out_point: &OutPoint,
+    tx_out: &TxOut,
+) -> Result<Option<Vec<u8>>, String> {
+    match &tx_out.script_public_key {
+        Some(address) => {
+            let mut key = get_address_index_prefix(address);
+            key.extend_from_slice(&encode(out_point)?);
+            Ok(Some(key))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Fills in the genesis hash of an item created by an output
+///
+/// ### Arguments
+///
+/// * `tx_out`      - The output
+/// * `out_point`   - Outpoint of the output
+fn with_fixed_hash(mut tx_out: TxOut, out_point: &OutPoint) -> TxOut {
+    tx_out.value = tx_out.value.with_fixed_hash(out_point);
+    tx_out
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::store::MemoryChainStore;
+    use crate::primitives::asset::{Asset, TokenAmount};
+    use crate::primitives::block::{Block, FullBlock};
+    use crate::primitives::transaction::{Transaction, TxIn};
+    use crate::script::lang::Script;
+    use crate::utils::transaction_utils::{construct_tx_core, construct_tx_hash};
+
+    /// Creates a block after `previous` containing the given transactions
+    fn test_block(previous: Option<&FullBlock>, transactions: Vec<Transaction>) -> FullBlock {
+        let mut block = Block::new();
+        block.header.previous_hash = previous.map(|p| p.header.hash());
+        block.transactions = transactions.iter().map(construct_tx_hash).collect();
+        block.set_txs_merkle_root_and_hash_sync();
+        FullBlock::from_block(block, transactions).unwrap()
+    }
+
+    /// Creates a transaction spending an output and paying the given assets
+    fn test_tx(out_point: Option<OutPoint>, outputs: &[(&str, Asset)]) -> Transaction {
+        let tx_in = match out_point {
+            Some(out_point) => TxIn::new_from_input(out_point, Script::new()),
+            None => TxIn::new_from_script(Script::new_for_coinbase(0)),
+        };
+        let tx_outs = outputs
+            .iter()
+            .map(|(address, asset)| TxOut::new_asset(address.to_string(), asset.clone(), None))
+            .collect();
+        construct_tx_core(vec![tx_in], tx_outs, None)
+    }
+
+    #[test]
+    /// Checks that spends, created items and balances are indexed and unindexed
+    fn test_index_connected_and_disconnected_blocks() {
+        let mut storage = ChainStorage::new(MemoryChainStore::default()).with_address_history();
+        let allocation = test_tx(None, &[("a", Asset::token_u64(10))]);
+        let allocation_out = OutPoint::new(construct_tx_hash(&allocation), 0);
+        let genesis = test_block(None, vec![allocation]);
+
+        let payment = test_tx(
+            Some(allocation_out.clone()),
+            &[("b", Asset::token_u64(4)), ("a", Asset::token_u64(6))],
+        );
+        let payment_hash = construct_tx_hash(&payment);
+        let create = test_tx(None, &[("b", Asset::item(3, None, None))]);
+        let create_hash = construct_tx_hash(&create);
+        let block = test_block(Some(&genesis), vec![payment, create]);
+
+        storage.connect_block(&genesis).unwrap();
+        storage.connect_block(&block).unwrap();
+
+        let indexer = AddressIndexer::new(&storage).unwrap();
+        let a_outputs = indexer.get_outputs("a").unwrap();
+        let b_balance = indexer.get_balance("b").unwrap();
+
+        let spent = a_outputs.iter().find(|(op, _)| *op == allocation_out);
+
+        assert_eq!(a_outputs.len(), 2);
+        assert_eq!(spent.unwrap().1.spent_by, Some(payment_hash));
+        assert_eq!(indexer.get_balance("a").unwrap(), AssetValues::token_u64(6));
+        assert_eq!(b_balance.tokens, TokenAmount(4));
+        assert_eq!(b_balance.items.get(&create_hash), Some(&3));
+
+        storage.disconnect_tip().unwrap();
+        let indexer = AddressIndexer::new(&storage).unwrap();
+
+        assert_eq!(
+            indexer.get_outputs("a").unwrap(),
+            vec![(
+                allocation_out,
+                IndexedOutput {
+                    tx_out: TxOut::new_asset("a".to_owned(), Asset::token_u64(10), None),
+                    spent_by: None,
+                }
+            )]
+        );
+        assert!(indexer.get_outputs("b").unwrap().is_empty());
+        assert!(indexer.get_balance("b").unwrap().is_empty());
+    }
+
+    #[test]
+    /// Checks that an output created and spent in the same block is indexed as spent
+    fn test_index_spend_within_block() {
+        let mut storage = ChainStorage::new(MemoryChainStore::default()).with_address_history();
+        let parent = test_tx(None, &[("a", Asset::token_u64(5))]);
+        let parent_out = OutPoint::new(construct_tx_hash(&parent), 0);
+        let child = test_tx(Some(parent_out), &[("b", Asset::token_u64(5))]);
+        let block = test_block(None, vec![parent, child.clone()]);
+
+        storage.connect_block(&block).unwrap();
+        let indexer = AddressIndexer::new(&storage).unwrap();
+
+        assert_eq!(
+            indexer.get_outputs("a").unwrap()[0].1.spent_by,
+            Some(construct_tx_hash(&child))
+        );
+        assert!(indexer.get_balance("a").unwrap().is_empty());
+        assert_eq!(indexer.get_balance("b").unwrap(), AssetValues::token_u64(5));
+
+        storage.disconnect_tip().unwrap();
+        assert!(storage
+            .store()
+            .get_prefix(Column::AddressHistory, &[])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    /// Checks that history can't be read from storage which does not keep it
+    fn test_indexer_requires_address_history() {
+        let storage = ChainStorage::new(MemoryChainStore::default());
+
+        assert!(AddressIndexer::new(&storage).is_err());
+    }
+}
@@ -0,0 +1,9 @@
+// ------- MODS ------- //
+
+pub mod genesis;
+pub mod header_chain;
+pub mod indexer;
+#[cfg(feature = "build_bin")]
+pub mod rocks_store;
+pub mod spv;
+pub mod store;
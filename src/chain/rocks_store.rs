@@ -0,0 +1,261 @@
+use crate::chain::store::{ChainStore, Column, StoreBatch, StoreEntries, StoreOp};
+use rocksdb::{ColumnFamily, Options, WriteBatch, DB};
+use std::path::Path;
+use tracing::debug;
+
+/// `ChainStore` persisted in RocksDB, with a column family per `Column`
+pub struct RocksChainStore {
+    db: DB,
+}
+
+impl RocksChainStore {
+    /// Opens the store at a path, creating it and its column families if missing
+    ///
+    /// ### Arguments
+    ///
+    /// * `path`    - Directory of the database
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+
+        let names = Column::ALL.iter().map(|column| column.name());
+        let db = DB::open_cf(&options, path.as_ref(), names)
+            .map_err(|e| format!("Failed to open chain store: {e}"))?;
+
+        debug!("Opened chain store at {}", path.as_ref().display());
+        Ok(Self { db })
+    }
+
+    /// Gets the handle of a column family
+    ///
+    /// ### Arguments
+    ///
+    /// * `column`  - Column to get the handle of
+    fn cf(&self, column: Column) -> Result<&ColumnFamily, String> {
+        self.db
+            .cf_handle(column.name())
+            .ok_or_else(|| format!("Missing column family {}", column.name()))
+    }
+}
+
+impl ChainStore for RocksChainStore {
+    fn get(&self, column: Column, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        self.db
+            .get_cf(self.cf(column)?, key)
+            .map_err(|e| format!("Failed to read chain store: {e}"))
+    }
+
+    fn get_prefix(&self, column: Column, prefix: &[u8]) -> Result<StoreEntries, String> {
+        let mut values = Vec::new();
+
+        for item in self.db.prefix_iterator_cf(self.cf(column)?, prefix) {
+            let (key, value) = item.map_err(|e| format!("Failed to read chain store: {e}"))?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            values.push((key.to_vec(), value.to_vec()));
+        }
+
+        Ok(values)
+    }
+
+    fn write(&mut self, batch: StoreBatch) -> Result<(), String> {
+        let mut write_batch = WriteBatch::default();
+
+        for op in batch.ops {
+            match op {
+                StoreOp::Put { column, key, value } => {
+                    write_batch.put_cf(self.cf(column)?, key, value)
+                }
+                StoreOp::Delete { column, key } => write_batch.delete_cf(self.cf(column)?, key),
+            }
+        }
+
+        self.db
+            .write(write_batch)
+            .map_err(|e| format!("Failed to write chain store: {e}"))
+    }
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::genesis::{build_genesis, ChainSpec};
+    use crate::chain::header_chain::Reorg;
+    use crate::chain::store::ChainStorage;
+    use crate::primitives::asset::TokenAmount;
+    use crate::primitives::block::{Block, FullBlock};
+    use crate::primitives::transaction::{OutPoint, Transaction, TxIn, TxOut};
+    use crate::script::lang::Script;
+    use crate::utils::transaction_utils::{construct_tx_core, construct_tx_hash};
+    use std::path::PathBuf;
+
+    const ADDRESS_A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const ADDRESS_B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+    const ADDRESS_C: &str = "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc";
+
+    const TEST_CHAIN_SPEC: &str = r#"{
+        "name": "test",
+        "timestamp": 0,
+        "initial_compact_target": 545259519,
+        "allocations": [
+            {
+                "address": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+                "value": { "Token": 10 }
+            }
+        ]
+    }"#;
+
+    /// Gets an empty directory for a test's database
+    fn temp_db_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("tw_chain_rocks_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    /// Creates a genesis block paying 10 tokens to `ADDRESS_A`
+    fn test_genesis() -> FullBlock {
+        let (block, transactions) = build_genesis(&ChainSpec::from_json(TEST_CHAIN_SPEC).unwrap());
+        FullBlock::from_block(block, transactions).unwrap()
+    }
+
+    /// Creates a block after `previous` containing the given transactions
+    fn next_block(previous: &FullBlock, transactions: Vec<Transaction>, seed: &[u8]) -> FullBlock {
+        let mut block = Block::new();
+        block.header.b_num = previous.header.b_num + 1;
+        block.header.seed_value = seed.to_vec();
+        block.header.previous_hash = Some(previous.header.hash());
+        block.transactions = transactions.iter().map(construct_tx_hash).collect();
+        block.set_txs_merkle_root_and_hash_sync();
+        FullBlock::from_block(block, transactions).unwrap()
+    }
+
+    /// Creates a transaction spending an output to the given address
+    fn test_tx(out_point: OutPoint, address: &str, amount: u64) -> Transaction {
+        let tx_in = TxIn::new_from_input(out_point, Script::new());
+        let tx_out = TxOut::new_token_amount(address.to_owned(), TokenAmount(amount), None);
+        construct_tx_core(vec![tx_in], vec![tx_out], None)
+    }
+
+    /// Reads every entry of every column of a store
+    fn read_all(store: &RocksChainStore) -> Vec<StoreEntries> {
+        Column::ALL
+            .iter()
+            .map(|column| store.get_prefix(*column, &[]).unwrap())
+            .collect()
+    }
+
+    #[test]
+    /// Checks that connected blocks persist across reopening and disconnect cleanly
+    fn test_rocks_connect_and_disconnect_blocks() {
+        let path = temp_db_path("connect");
+        let genesis = test_genesis();
+        let genesis_out = OutPoint::new(construct_tx_hash(&genesis.transactions[0]), 0);
+        let spend = test_tx(genesis_out.clone(), ADDRESS_B, 10);
+        let spend_out = OutPoint::new(construct_tx_hash(&spend), 0);
+        let block = next_block(&genesis, vec![spend.clone()], b"1");
+
+        {
+            let mut storage =
+                ChainStorage::new(RocksChainStore::open(&path).unwrap()).with_address_history();
+            storage.connect_block(&genesis).unwrap();
+            storage.connect_block(&block).unwrap();
+        }
+
+        let mut storage =
+            ChainStorage::new(RocksChainStore::open(&path).unwrap()).with_address_history();
+        assert_eq!(storage.get_tip().unwrap(), Some(block.header.hash()));
+        assert_eq!(storage.get_utxo(&genesis_out).unwrap(), None);
+        assert_eq!(
+            storage.get_address_utxos(ADDRESS_B).unwrap(),
+            vec![(spend_out.clone(), spend.outputs[0].clone())]
+        );
+        assert_eq!(
+            storage.get_transaction(&construct_tx_hash(&spend)).unwrap(),
+            Some(spend.clone())
+        );
+        assert!(!storage
+            .store()
+            .get_prefix(Column::AddressHistory, &[])
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(storage.disconnect_tip().unwrap(), block);
+        assert_eq!(storage.get_tip().unwrap(), Some(genesis.header.hash()));
+        assert_eq!(storage.get_utxo(&spend_out).unwrap(), None);
+        assert_eq!(storage.get_address_utxos(ADDRESS_A).unwrap().len(), 1);
+        assert!(storage
+            .store()
+            .get_prefix(Column::AddressHistory, &[])
+            .unwrap()
+            .is_empty());
+
+        drop(storage);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    /// Checks that a reorg is applied in one write, and a reorg failing part way leaves
+    /// the database unchanged
+    fn test_rocks_apply_reorg() {
+        let path = temp_db_path("reorg");
+        let mut storage =
+            ChainStorage::new(RocksChainStore::open(&path).unwrap()).with_address_history();
+        let genesis = test_genesis();
+        let genesis_out = OutPoint::new(construct_tx_hash(&genesis.transactions[0]), 0);
+        storage.connect_block(&genesis).unwrap();
+
+        let a1 = next_block(
+            &genesis,
+            vec![test_tx(genesis_out.clone(), ADDRESS_B, 10)],
+            b"a",
+        );
+        let b1 = next_block(
+            &genesis,
+            vec![test_tx(genesis_out.clone(), ADDRESS_C, 10)],
+            b"b",
+        );
+        let invalid_b2 = next_block(
+            &b1,
+            vec![test_tx(
+                OutPoint::new("unknown".to_owned(), 0),
+                ADDRESS_C,
+                1,
+            )],
+            b"c",
+        );
+        storage.connect_block(&a1).unwrap();
+        storage.store_block(&b1).unwrap();
+        storage.store_block(&invalid_b2).unwrap();
+        let before = read_all(storage.store());
+
+        let failed = storage.apply_reorg(&Reorg {
+            disconnect: vec![a1.header.hash()],
+            connect: vec![b1.header.hash(), invalid_b2.header.hash()],
+        });
+
+        assert!(failed.is_err());
+        assert_eq!(read_all(storage.store()), before);
+        assert_eq!(storage.get_tip().unwrap(), Some(a1.header.hash()));
+
+        storage
+            .apply_reorg(&Reorg {
+                disconnect: vec![a1.header.hash()],
+                connect: vec![b1.header.hash()],
+            })
+            .unwrap();
+        drop(storage);
+
+        let storage = ChainStorage::new(RocksChainStore::open(&path).unwrap());
+        assert_eq!(storage.get_tip().unwrap(), Some(b1.header.hash()));
+        assert!(storage.get_address_utxos(ADDRESS_B).unwrap().is_empty());
+        assert_eq!(storage.get_address_utxos(ADDRESS_C).unwrap().len(), 1);
+
+        drop(storage);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
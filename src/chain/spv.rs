@@ -0,0 +1,294 @@
+use crate::chain::header_chain::{HeaderChain, Reorg};
+use crate::primitives::block::{verify_inclusion, BlockHeader, TxInclusionProof};
+use crate::primitives::transaction::{OutPoint, Transaction, TxOut};
+use crate::utils::difficulty_utils::DifficultyParams;
+use crate::utils::transaction_utils::construct_tx_hash;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use tracing::{debug, error};
+
+/// Proof that a transaction is in a block, as served to a light client
+///
+/// `block_hash`    - Hash of the header of the block containing the transaction
+/// `transaction`   - The transaction, so that its outputs can be inspected
+/// `inclusion`     - Proof of the transaction against the header's merkle root
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpvTxProof {
+    pub block_hash: String,
+    pub transaction: Transaction,
+    pub inclusion: TxInclusionProof,
+}
+
+/// Light client that keeps only block headers. Headers must link to a known header,
+/// meet their proof-of-work target and carry the retargeted difficulty. Transactions
+/// are accepted on a merkle proof against a header on the best chain.
+#[derive(Clone, Debug)]
+pub struct SpvClient {
+    chain: HeaderChain,
+}
+
+impl SpvClient {
+    /// Creates a new light client from a trusted genesis header
+    ///
+    /// ### Arguments
+    ///
+    /// * `genesis` - Header of the genesis block
+    /// * `params`  - Difficulty parameters of the network
+    pub fn new(genesis: BlockHeader, params: DifficultyParams) -> Self {
+        Self {
+            chain: HeaderChain::new(genesis, params),
+        }
+    }
+
+    /// Gets the stored headers
+    pub fn chain(&self) -> &HeaderChain {
+        &self.chain
+    }
+
+    /// Gets the header at the tip of the best chain
+    pub fn best_header(&self) -> &BlockHeader {
+        self.chain.best_header()
+    }
+
+    /// Verifies and stores a header. Returns the change of best chain if the header
+    /// becomes the best tip.
+    ///
+    /// ### Arguments
+    ///
+    /// * `header`          - Header to add
+    /// * `current_time`    - Current time, in seconds
+    pub fn add_header(
+        &mut self,
+        header: BlockHeader,
+        current_time: i64,
+    ) -> Result<Option<Reorg>, String> {
+        self.chain.add_header(header, current_time)
+    }
+
+    /// Gets the number of confirmations of a block on the best chain, counting the
+    /// block itself. Returns `None` if the block is not on the best chain.
+    ///
+    /// ### Arguments
+    ///
+    /// * `block_hash`  - Hash of the block's header
+    pub fn get_confirmations(&self, block_hash: &str) -> Option<u64> {
+        if !self.chain.is_in_best_chain(block_hash) {
+            return None;
+        }
+
+        let b_num = self.chain.get(block_hash)?.header.b_num;
+        Some(self.best_header().b_num - b_num + 1)
+    }
+
+    /// Verifies that a transaction is in a block on the best chain, returning the
+    /// block's number of confirmations
+    ///
+    /// ### Arguments
+    ///
+    /// * `proof`   - Proof of the transaction's inclusion
+    pub fn verify_transaction(&self, proof: &SpvTxProof) -> Result<u64, String> {
+        let header = &self
+            .chain
+            .get(&proof.block_hash)
+            .ok_or_else(|| "Block header is not known".to_string())?
+            .header;
+        let confirmations = self
+            .get_confirmations(&proof.block_hash)
+            .ok_or_else(|| "Block is not on the best chain".to_string())?;
+
+        let tx_hash = construct_tx_hash(&proof.transaction);
+        if !verify_inclusion(
+            &proof.inclusion,
+            &tx_hash,
+            &header.txs_merkle_root_and_hash.0,
+        ) {
+            error!("TRANSACTION INCLUSION PROOF IS INVALID");
+            return Err("Transaction is not included in the block".to_string());
+        }
+
+        debug!(
+            "Verified transaction {} in block {} with {} confirmations",
+            tx_hash, header.b_num, confirmations
+        );
+        Ok(confirmations)
+    }
+
+    /// Verifies a transaction and gets its outputs paying any of the given addresses
+    ///
+    /// ### Arguments
+    ///
+    /// * `proof`       - Proof of the transaction's inclusion
+    /// * `addresses`   - Addresses the client is watching
+    pub fn get_verified_outputs(
+        &self,
+        proof: &SpvTxProof,
+        addresses: &BTreeSet<String>,
+    ) -> Result<Vec<(OutPoint, TxOut)>, String> {
+        self.verify_transaction(proof)?;
+        let tx_hash = construct_tx_hash(&proof.transaction);
+
+        Ok(proof
+            .transaction
+            .outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, tx_out)| match &tx_out.script_public_key {
+                Some(address) => addresses.contains(address),
+                None => false,
+            })
+            .map(|(n, tx_out)| (OutPoint::new(tx_hash.clone(), n as i32), tx_out.clone()))
+            .collect())
+    }
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{MAX_FUTURE_BLOCK_TIME, MAX_POW_TARGET_COMPACT};
+    use crate::primitives::asset::TokenAmount;
+    use crate::primitives::block::{gen_random_hash, Block};
+    use crate::primitives::transaction::TxIn;
+    use crate::script::lang::Script;
+    use crate::utils::difficulty_utils::get_next_compact_target;
+    use crate::utils::pow_utils::{difficulty_from_compact, mine_header};
+    use crate::utils::transaction_utils::construct_tx_core;
+
+    const CURRENT_TIME: i64 = 10_000;
+
+    fn genesis() -> BlockHeader {
+        let mut genesis = BlockHeader::new();
+        genesis.difficulty = difficulty_from_compact(MAX_POW_TARGET_COMPACT);
+        genesis
+    }
+
+    /// Creates a mined block following the client's best header
+    fn next_block(client: &SpvClient, transactions: &[Transaction]) -> Block {
+        let timestamp = client.best_header().timestamp + 600;
+        next_block_at(client, transactions, timestamp)
+    }
+
+    /// Creates a mined block following the client's best header with the given timestamp
+    fn next_block_at(client: &SpvClient, transactions: &[Transaction], timestamp: i64) -> Block {
+        let previous = client.best_header();
+        let previous_headers = client.chain().get_ancestors(
+            &previous.hash(),
+            DifficultyParams::default().get_retarget_window(),
+        );
+
+        let mut block = Block::new();
+        block.transactions = transactions.iter().map(construct_tx_hash).collect();
+        block.set_txs_merkle_root_and_hash_sync();
+        block.header.b_num = previous.b_num + 1;
+        block.header.timestamp = timestamp;
+        block.header.previous_hash = Some(previous.hash());
+        block.header.difficulty = difficulty_from_compact(get_next_compact_target(
+            &previous_headers,
+            &DifficultyParams::default(),
+        ));
+        assert!(mine_header(&mut block.header, 10_000));
+        block
+    }
+
+    /// Creates a transaction paying each of the given addresses
+    fn test_tx(addresses: &[&str]) -> Transaction {
+        let tx_in = TxIn::new_from_input(OutPoint::new("tx_hash".to_owned(), 0), Script::new());
+        let tx_outs = addresses
+            .iter()
+            .map(|a| TxOut::new_token_amount(a.to_string(), TokenAmount(1), None))
+            .collect();
+        construct_tx_core(vec![tx_in], tx_outs, None)
+    }
+
+    #[test]
+    /// Checks that headers must link to a known header and carry proof-of-work
+    fn test_add_verified_headers() {
+        let mut client = SpvClient::new(genesis(), DifficultyParams::default());
+        let block = next_block(&client, &[test_tx(&["a"])]);
+
+        let mut unmined = block.header.clone();
+        unmined.seed_value = b"changed".to_vec();
+        while unmined.meets_target() {
+            unmined.nonce_and_mining_tx_hash.0.push(0);
+        }
+        let mut wrong_difficulty = block.header.clone();
+        wrong_difficulty.difficulty = difficulty_from_compact(0x2000ffff);
+        assert!(mine_header(&mut wrong_difficulty, 10_000));
+        let mut unlinked = block.header.clone();
+        unlinked.previous_hash = Some(block.header.hash());
+
+        assert!(client.add_header(unmined, CURRENT_TIME).is_err());
+        assert!(client.add_header(wrong_difficulty, CURRENT_TIME).is_err());
+        assert!(client.add_header(unlinked, CURRENT_TIME).is_err());
+        assert!(client
+            .add_header(block.header.clone(), CURRENT_TIME)
+            .unwrap()
+            .is_some());
+        assert_eq!(client.best_header().hash(), block.header.hash());
+    }
+
+    #[test]
+    /// Checks that headers must be timestamped after the median time past and not too
+    /// far in the future
+    fn test_reject_invalid_header_timestamps() {
+        let mut client = SpvClient::new(genesis(), DifficultyParams::default());
+        for _ in 0..2 {
+            let block = next_block(&client, &[]);
+            client.add_header(block.header, CURRENT_TIME).unwrap();
+        }
+
+        // The median of the genesis and two following timestamps is 600
+        let at_median = next_block_at(&client, &[], 600);
+        let in_future = next_block_at(&client, &[], CURRENT_TIME + MAX_FUTURE_BLOCK_TIME + 1);
+        let after_median = next_block_at(&client, &[], 601);
+
+        assert_eq!(
+            client.add_header(at_median.header, CURRENT_TIME),
+            Err("Block timestamp is not after the median time past".to_string())
+        );
+        assert_eq!(
+            client.add_header(in_future.header, CURRENT_TIME),
+            Err("Block timestamp is too far in the future".to_string())
+        );
+        assert!(client
+            .add_header(after_median.header, CURRENT_TIME)
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    /// Checks that transactions are accepted on a proof against a best chain header
+    fn test_verify_transaction_outputs() {
+        let mut client = SpvClient::new(genesis(), DifficultyParams::default());
+        let tx = test_tx(&["a", "b", "a"]);
+        let transactions = vec![test_tx(&["c"]), tx.clone(), test_tx(&["d"])];
+        let block = next_block(&client, &transactions);
+        client
+            .add_header(block.header.clone(), CURRENT_TIME)
+            .unwrap();
+        let child = next_block(&client, &[]);
+        client.add_header(child.header, CURRENT_TIME).unwrap();
+
+        let proof = SpvTxProof {
+            block_hash: block.header.hash(),
+            transaction: tx.clone(),
+            inclusion: block.merkle_proof(&construct_tx_hash(&tx)).unwrap(),
+        };
+        let addresses: BTreeSet<String> = std::iter::once("a".to_owned()).collect();
+        let outputs = client.get_verified_outputs(&proof, &addresses).unwrap();
+
+        let mut forged = proof.clone();
+        forged.transaction = test_tx(&["a", "a", "a"]);
+        let mut unknown_block = proof.clone();
+        unknown_block.block_hash = gen_random_hash();
+
+        assert_eq!(client.verify_transaction(&proof), Ok(2));
+        assert_eq!(
+            outputs.iter().map(|(o, _)| o.n).collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+        assert!(client.verify_transaction(&forged).is_err());
+        assert!(client.verify_transaction(&unknown_block).is_err());
+    }
+}
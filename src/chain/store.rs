@@ -0,0 +1,841 @@
+use crate::chain::header_chain::Reorg;
+use crate::chain::indexer::{delete_spent_output, put_spent_output};
+use crate::primitives::block::FullBlock;
+use crate::primitives::transaction::{OutPoint, Transaction, TxOut};
+use crate::utils::transaction_utils::construct_tx_hash;
+use bincode::{deserialize, serialize};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::{debug, error};
+
+/// Keys and values read from a column, in key order
+pub type StoreEntries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Key in the `Meta` column holding the hash of the connected tip
+pub const TIP_KEY: &[u8] = b"tip";
+
+/// Column families of the chain store
+///
+/// * `Blocks`          - Block hash to encoded `FullBlock`, for every stored block
+/// * `Transactions`    - Transaction hash to transaction, for connected blocks
+/// * `Utxos`           - Encoded `OutPoint` to unspent `TxOut`
+/// * `AddressIndex`    - Address, a zero byte and an encoded `OutPoint`, to unspent `TxOut`
+/// * `Undo`            - Block hash to the outputs the block spent
+/// * `Meta`            - Chain metadata such as the connected tip
+/// * `AddressHistory`  - Address, a zero byte and an encoded `OutPoint`, to the spent
+///   output and the transaction spending it, if address history is kept
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Column {
+    Blocks,
+    Transactions,
+    Utxos,
+    AddressIndex,
+    Undo,
+    Meta,
+    AddressHistory,
+}
+
+impl Column {
+    /// Every column of the store
+    pub const ALL: [Column; 7] = [
+        Column::Blocks,
+        Column::Transactions,
+        Column::Utxos,
+        Column::AddressIndex,
+        Column::Undo,
+        Column::Meta,
+        Column::AddressHistory,
+    ];
+
+    /// Gets the name of the column family backing the column
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Blocks => "blocks",
+            Column::Transactions => "transactions",
+            Column::Utxos => "utxos",
+            Column::AddressIndex => "address_index",
+            Column::Undo => "undo",
+            Column::Meta => "meta",
+            Column::AddressHistory => "address_history",
+        }
+    }
+}
+
+/// A single write to the store
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StoreOp {
+    Put {
+        column: Column,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Delete {
+        column: Column,
+        key: Vec<u8>,
+    },
+}
+
+/// Writes applied to the store together, so that either all or none of them are
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct StoreBatch {
+    pub ops: Vec<StoreOp>,
+}
+
+impl StoreBatch {
+    /// Adds a write of a value to the batch
+    ///
+    /// ### Arguments
+    ///
+    /// * `column`  - Column to write to
+    /// * `key`     - Key to write
+    /// * `value`   - Value to write
+    pub fn put(&mut self, column: Column, key: Vec<u8>, value: Vec<u8>) {
+        self.ops.push(StoreOp::Put { column, key, value });
+    }
+
+    /// Adds a deletion of a key to the batch
+    ///
+    /// ### Arguments
+    ///
+    /// * `column`  - Column to delete from
+    /// * `key`     - Key to delete
+    pub fn delete(&mut self, column: Column, key: Vec<u8>) {
+        self.ops.push(StoreOp::Delete { column, key });
+    }
+}
+
+/// Key-value storage split into columns, with atomic batch writes
+pub trait ChainStore {
+    /// Gets the value stored at a key
+    ///
+    /// ### Arguments
+    ///
+    /// * `column`  - Column to read from
+    /// * `key`     - Key to read
+    fn get(&self, column: Column, key: &[u8]) -> Result<Option<Vec<u8>>, String>;
+
+    /// Gets every key and value whose key starts with a prefix, in key order
+    ///
+    /// ### Arguments
+    ///
+    /// * `column`  - Column to read from
+    /// * `prefix`  - Prefix of the keys to read
+    fn get_prefix(&self, column: Column, prefix: &[u8]) -> Result<StoreEntries, String>;
+
+    /// Applies every write in a batch atomically
+    ///
+    /// ### Arguments
+    ///
+    /// * `batch`   - Writes to apply
+    fn write(&mut self, batch: StoreBatch) -> Result<(), String>;
+}
+
+/// In-memory `ChainStore`, for tests and short-lived nodes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryChainStore {
+    columns: BTreeMap<Column, BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl ChainStore for MemoryChainStore {
+    fn get(&self, column: Column, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        Ok(self
+            .columns
+            .get(&column)
+            .and_then(|values| values.get(key))
+            .cloned())
+    }
+
+    fn get_prefix(&self, column: Column, prefix: &[u8]) -> Result<StoreEntries, String> {
+        let values = match self.columns.get(&column) {
+            Some(values) => values,
+            None => return Ok(Vec::new()),
+        };
+
+        Ok(values
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+
+    fn write(&mut self, batch: StoreBatch) -> Result<(), String> {
+        for op in batch.ops {
+            match op {
+                StoreOp::Put { column, key, value } => {
+                    self.columns.entry(column).or_default().insert(key, value);
+                }
+                StoreOp::Delete { column, key } => {
+                    if let Some(values) = self.columns.get_mut(&column) {
+                        values.remove(&key);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes made over a `ChainStore` without applying them to it. Reads see the
+/// pending writes, so several batches can be built and then applied as one.
+struct StoreOverlay<'a, S: ChainStore> {
+    store: &'a S,
+    changes: BTreeMap<(Column, Vec<u8>), Option<Vec<u8>>>,
+    batch: StoreBatch,
+}
+
+impl<'a, S: ChainStore> StoreOverlay<'a, S> {
+    /// Creates an overlay with no pending writes
+    ///
+    /// ### Arguments
+    ///
+    /// * `store`   - Store the writes will be applied to
+    fn new(store: &'a S) -> Self {
+        Self {
+            store,
+            changes: BTreeMap::new(),
+            batch: StoreBatch::default(),
+        }
+    }
+}
+
+impl<'a, S: ChainStore> ChainStore for StoreOverlay<'a, S> {
+    fn get(&self, column: Column, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        match self.changes.get(&(column, key.to_vec())) {
+            Some(value) => Ok(value.clone()),
+            None => self.store.get(column, key),
+        }
+    }
+
+    fn get_prefix(&self, column: Column, prefix: &[u8]) -> Result<StoreEntries, String> {
+        let mut values: BTreeMap<Vec<u8>, Vec<u8>> =
+            self.store.get_prefix(column, prefix)?.into_iter().collect();
+
+        let changes = self
+            .changes
+            .range((column, prefix.to_vec())..)
+            .take_while(|((c, key), _)| *c == column && key.starts_with(prefix));
+        for ((_, key), value) in changes {
+            match value {
+                Some(value) => values.insert(key.clone(), value.clone()),
+                None => values.remove(key),
+            };
+        }
+
+        Ok(values.into_iter().collect())
+    }
+
+    fn write(&mut self, batch: StoreBatch) -> Result<(), String> {
+        for op in &batch.ops {
+            match op {
+                StoreOp::Put { column, key, value } => {
+                    self.changes
+                        .insert((*column, key.clone()), Some(value.clone()));
+                }
+                StoreOp::Delete { column, key } => {
+                    self.changes.insert((*column, key.clone()), None);
+                }
+            }
+        }
+        self.batch.ops.extend(batch.ops);
+        Ok(())
+    }
+}
+
+/// Blocks, transactions and the UTXO set of the connected chain, kept in a
+/// `ChainStore`. Blocks are assumed to be validated before they are connected.
+/// Each block is connected or disconnected in a single batch write, with the
+/// outputs it spent kept as undo data so that it can be rolled back on a reorg.
+/// If address history is kept, the spent outputs of each address are written in
+/// the same batch.
+#[derive(Debug, Clone, Default)]
+pub struct ChainStorage<S: ChainStore> {
+    store: S,
+    address_history: bool,
+}
+
+impl<S: ChainStore> ChainStorage<S> {
+    /// Creates chain storage over a store
+    ///
+    /// ### Arguments
+    ///
+    /// * `store`   - Underlying key-value store
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            address_history: false,
+        }
+    }
+
+    /// Keeps the spent outputs of each address as well as its unspent outputs, so
+    /// that the history of an address can be read with an `AddressIndexer`. Must be
+    /// set before any block is connected.
+    pub fn with_address_history(mut self) -> Self {
+        self.address_history = true;
+        self
+    }
+
+    /// Checks whether the spent outputs of each address are kept
+    pub fn has_address_history(&self) -> bool {
+        self.address_history
+    }
+
+    /// Gets the underlying store
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Gets the hash of the connected tip, if any block is connected
+    pub fn get_tip(&self) -> Result<Option<String>, String> {
+        self.store
+            .get(Column::Meta, TIP_KEY)?
+            .map(|tip| String::from_utf8(tip).map_err(|e| format!("Invalid stored tip: {e}")))
+            .transpose()
+    }
+
+    /// Gets a stored block
+    ///
+    /// ### Arguments
+    ///
+    /// * `block_hash`  - Hash of the block's header
+    pub fn get_block(&self, block_hash: &str) -> Result<Option<FullBlock>, String> {
+        self.store
+            .get(Column::Blocks, block_hash.as_bytes())?
+            .map(|bytes| FullBlock::decode(&bytes))
+            .transpose()
+    }
+
+    /// Gets a transaction in a connected block
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx_hash` - Hash of the transaction
+    pub fn get_transaction(&self, tx_hash: &str) -> Result<Option<Transaction>, String> {
+        self.get_value(Column::Transactions, tx_hash.as_bytes())
+    }
+
+    /// Gets an unspent output
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_point`   - Outpoint of the output
+    pub fn get_utxo(&self, out_point: &OutPoint) -> Result<Option<TxOut>, String> {
+        self.get_value(Column::Utxos, &encode(out_point)?)
+    }
+
+    /// Gets the unspent outputs paying an address
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address to get the outputs of
+    pub fn get_address_utxos(&self, address: &str) -> Result<Vec<(OutPoint, TxOut)>, String> {
+        let prefix = get_address_index_prefix(address);
+
+        self.store
+            .get_prefix(Column::AddressIndex, &prefix)?
+            .into_iter()
+            .map(|(key, value)| Ok((decode(&key[prefix.len()..])?, decode(&value)?)))
+            .collect()
+    }
+
+    /// Stores a block without connecting it, such as a block on a side chain
+    ///
+    /// ### Arguments
+    ///
+    /// * `block`   - Block to store
+    pub fn store_block(&mut self, block: &FullBlock) -> Result<(), String> {
+        let mut batch = StoreBatch::default();
+        batch.put(
+            Column::Blocks,
+            block.header.hash().into_bytes(),
+            block.encode()?,
+        );
+        self.store.write(batch)
+    }
+
+    /// Connects a block extending the connected tip, spending its inputs and adding
+    /// its outputs to the UTXO set
+    ///
+    /// ### Arguments
+    ///
+    /// * `block`   - Block to connect
+    pub fn connect_block(&mut self, block: &FullBlock) -> Result<(), String> {
+        let block_hash = block.header.hash();
+        if self.get_tip()? != block.header.previous_hash {
+            error!("BLOCK DOES NOT EXTEND CONNECTED TIP");
+            return Err("Block does not extend the connected tip".to_string());
+        }
+        if !block.has_valid_txs_merkle_root_and_hash() {
+            return Err("Block transactions do not match its header".to_string());
+        }
+
+        let mut created: BTreeMap<OutPoint, TxOut> = BTreeMap::new();
+        let mut spent: BTreeSet<OutPoint> = BTreeSet::new();
+        let mut tx_hashes: BTreeSet<String> = BTreeSet::new();
+        let mut undo: Vec<(OutPoint, TxOut)> = Vec::new();
+        let mut batch = StoreBatch::default();
+
+        for tx in &block.transactions {
+            let tx_hash = construct_tx_hash(tx);
+
+            // A repeated transaction would overwrite the outputs of the first
+            if !tx_hashes.insert(tx_hash.clone()) || self.get_transaction(&tx_hash)?.is_some() {
+                error!("BLOCK CONTAINS DUPLICATE TRANSACTION");
+                return Err(format!("Block contains duplicate transaction {tx_hash}"));
+            }
+
+            for out_point in tx.inputs.iter().filter_map(|i| i.previous_out.as_ref()) {
+                if !spent.insert(out_point.clone()) {
+                    return Err(format!("Block spends output {out_point} twice"));
+                }
+
+                let tx_out = match created.remove(out_point) {
+                    Some(tx_out) => tx_out,
+                    None => {
+                        let tx_out = self
+                            .get_utxo(out_point)?
+                            .ok_or_else(|| format!("Block spends unknown output {out_point}"))?;
+                        undo.push((out_point.clone(), tx_out.clone()));
+                        tx_out
+                    }
+                };
+                if self.address_history {
+                    put_spent_output(&mut batch, out_point, &tx_out, &tx_hash)?;
+                }
+            }
+
+            for (n, tx_out) in tx.outputs.iter().enumerate() {
+                created.insert(OutPoint::new(tx_hash.clone(), n as i32), tx_out.clone());
+            }
+            batch.put(Column::Transactions, tx_hash.into_bytes(), encode(tx)?);
+        }
+
+        for (out_point, tx_out) in &undo {
+            self.delete_utxo(&mut batch, out_point, tx_out)?;
+        }
+        for (out_point, tx_out) in &created {
+            self.put_utxo(&mut batch, out_point, tx_out)?;
+        }
+
+        batch.put(
+            Column::Undo,
+            block_hash.clone().into_bytes(),
+            encode(&undo)?,
+        );
+        batch.put(
+            Column::Blocks,
+            block_hash.clone().into_bytes(),
+            block.encode()?,
+        );
+        batch.put(
+            Column::Meta,
+            TIP_KEY.to_vec(),
+            block_hash.clone().into_bytes(),
+        );
+        self.store.write(batch)?;
+
+        debug!(
+            "Connected block {} spending {} and creating {} outputs",
+            block_hash,
+            undo.len(),
+            created.len()
+        );
+        Ok(())
+    }
+
+    /// Disconnects the connected tip, restoring the outputs it spent. The block
+    /// stays stored so that it can be connected again.
+    pub fn disconnect_tip(&mut self) -> Result<FullBlock, String> {
+        let block_hash = self
+            .get_tip()?
+            .ok_or_else(|| "No block is connected".to_string())?;
+        let block = self
+            .get_block(&block_hash)?
+            .ok_or_else(|| format!("Connected block {block_hash} is not stored"))?;
+        let undo: Vec<(OutPoint, TxOut)> = self
+            .get_value(Column::Undo, block_hash.as_bytes())?
+            .ok_or_else(|| format!("No undo data for block {block_hash}"))?;
+
+        let mut batch = StoreBatch::default();
+        let mut block_tx_outs: BTreeMap<OutPoint, &TxOut> = BTreeMap::new();
+
+        for tx in block.transactions.iter().rev() {
+            let tx_hash = construct_tx_hash(tx);
+
+            for (n, tx_out) in tx.outputs.iter().enumerate() {
+                let out_point = OutPoint::new(tx_hash.clone(), n as i32);
+                self.delete_utxo(&mut batch, &out_point, tx_out)?;
+                block_tx_outs.insert(out_point, tx_out);
+            }
+            batch.delete(Column::Transactions, tx_hash.into_bytes());
+        }
+
+        if self.address_history {
+            block_tx_outs.extend(
+                undo.iter()
+                    .map(|(out_point, tx_out)| (out_point.clone(), tx_out)),
+            );
+            for tx in &block.transactions {
+                for out_point in tx.inputs.iter().filter_map(|i| i.previous_out.as_ref()) {
+                    if let Some(tx_out) = block_tx_outs.get(out_point) {
+                        delete_spent_output(&mut batch, out_point, tx_out)?;
+                    }
+                }
+            }
+        }
+
+        for (out_point, tx_out) in &undo {
+            self.put_utxo(&mut batch, out_point, tx_out)?;
+        }
+
+        batch.delete(Column::Undo, block_hash.clone().into_bytes());
+        match &block.header.previous_hash {
+            Some(previous_hash) => batch.put(
+                Column::Meta,
+                TIP_KEY.to_vec(),
+                previous_hash.clone().into_bytes(),
+            ),
+            None => batch.delete(Column::Meta, TIP_KEY.to_vec()),
+        }
+        self.store.write(batch)?;
+
+        debug!("Disconnected block {}", block_hash);
+        Ok(block)
+    }
+
+    /// Applies a change of best chain, disconnecting blocks back to the fork point
+    /// then connecting the stored blocks of the new chain. The whole change is
+    /// written in a single batch, so the store is left unchanged if any block fails.
+    ///
+    /// ### Arguments
+    ///
+    /// * `reorg`   - Change of best chain to apply
+    pub fn apply_reorg(&mut self, reorg: &Reorg) -> Result<(), String> {
+        let mut pending = ChainStorage {
+            store: StoreOverlay::new(&self.store),
+            address_history: self.address_history,
+        };
+
+        for block_hash in &reorg.disconnect {
+            if pending.get_tip()?.as_ref() != Some(block_hash) {
+                return Err(format!("Block {block_hash} is not the connected tip"));
+            }
+            pending.disconnect_tip()?;
+        }
+
+        for block_hash in &reorg.connect {
+            let block = pending
+                .get_block(block_hash)?
+                .ok_or_else(|| format!("Block {block_hash} is not stored"))?;
+            pending.connect_block(&block)?;
+        }
+
+        let batch = pending.store.batch;
+        self.store.write(batch)
+    }
+
+    /// Adds writes of an unspent output and its address index entry to a batch
+    fn put_utxo(
+        &self,
+        batch: &mut StoreBatch,
+        out_point: &OutPoint,
+        tx_out: &TxOut,
+    ) -> Result<(), String> {
+        let key = encode(out_point)?;
+        let value = encode(tx_out)?;
+
+        if let Some(address) = &tx_out.script_public_key {
+            let mut index_key = get_address_index_prefix(address);
+            index_key.extend_from_slice(&key);
+            batch.put(Column::AddressIndex, index_key, value.clone());
+        }
+        batch.put(Column::Utxos, key, value);
+        Ok(())
+    }
+
+    /// Adds deletions of an unspent output and its address index entry to a batch
+    fn delete_utxo(
+        &self,
+        batch: &mut StoreBatch,
+        out_point: &OutPoint,
+        tx_out: &TxOut,
+    ) -> Result<(), String> {
+        let key = encode(out_point)?;
+
+        if let Some(address) = &tx_out.script_public_key {
+            let mut index_key = get_address_index_prefix(address);
+            index_key.extend_from_slice(&key);
+            batch.delete(Column::AddressIndex, index_key);
+        }
+        batch.delete(Column::Utxos, key);
+        Ok(())
+    }
+
+    /// Gets and decodes a stored value
+    fn get_value<T: DeserializeOwned>(
+        &self,
+        column: Column,
+        key: &[u8],
+    ) -> Result<Option<T>, String> {
+        self.store
+            .get(column, key)?
+            .map(|bytes| decode(&bytes))
+            .transpose()
+    }
+}
+
+/// Gets the prefix of the address index keys for an address
+///
+/// ### Arguments
+///
+/// * `address` - Address to get the prefix for
+pub fn get_address_index_prefix(address: &str) -> Vec<u8> {
+    let mut prefix = address.as_bytes().to_vec();
+    prefix.push(0);
+    prefix
+}
+
+/// Encodes a value for the store
+pub(crate) fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    serialize(value).map_err(|e| format!("Failed to serialize stored value: {e:?}"))
+}
+
+/// Decodes a value from the store
+pub(crate) fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+    deserialize(bytes).map_err(|e| format!("Failed to deserialize stored value: {e:?}"))
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::genesis::{build_genesis, ChainSpec, GenesisAllocation};
+    use crate::constants::MAX_POW_TARGET_COMPACT;
+    use crate::primitives::asset::{Asset, TokenAmount};
+    use crate::primitives::block::Block;
+    use crate::primitives::network::Network;
+    use crate::primitives::transaction::TxIn;
+    use crate::script::lang::Script;
+    use crate::utils::consensus_utils::ConsensusRules;
+    use crate::utils::difficulty_utils::DifficultyParams;
+    use crate::utils::transaction_utils::construct_tx_core;
+
+    const ADDRESS_A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const ADDRESS_B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+    const ADDRESS_C: &str = "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc";
+
+    /// Creates a genesis block paying 10 tokens to `ADDRESS_A`
+    fn test_genesis() -> FullBlock {
+        let spec = ChainSpec {
+            name: "test".to_owned(),
+            network: Network::Mainnet,
+            timestamp: 0,
+            initial_compact_target: MAX_POW_TARGET_COMPACT,
+            difficulty: DifficultyParams::default(),
+            consensus_rules: ConsensusRules::default(),
+            allocations: vec![GenesisAllocation {
+                address: ADDRESS_A.to_owned(),
+                value: Asset::token_u64(10),
+                locktime: 0,
+            }],
+        };
+        spec.validate().unwrap();
+        let (block, transactions) = build_genesis(&spec);
+        FullBlock::from_block(block, transactions).unwrap()
+    }
+
+    /// Creates a block after `previous` containing the given transactions
+    fn next_block(previous: &FullBlock, transactions: Vec<Transaction>, seed: &[u8]) -> FullBlock {
+        let mut block = Block::new();
+        block.header.b_num = previous.header.b_num + 1;
+        block.header.seed_value = seed.to_vec();
+        block.header.previous_hash = Some(previous.header.hash());
+        block.transactions = transactions.iter().map(construct_tx_hash).collect();
+        block.set_txs_merkle_root_and_hash_sync();
+        FullBlock::from_block(block, transactions).unwrap()
+    }
+
+    /// Creates a transaction spending an output to the given address
+    fn test_tx(out_point: OutPoint, address: &str, amount: u64) -> Transaction {
+        let tx_in = TxIn::new_from_input(out_point, Script::new());
+        let tx_out = TxOut::new_token_amount(address.to_owned(), TokenAmount(amount), None);
+        construct_tx_core(vec![tx_in], vec![tx_out], None)
+    }
+
+    #[test]
+    /// Checks that connecting and disconnecting blocks updates and restores the UTXO set
+    fn test_connect_and_disconnect_blocks() {
+        let mut storage = ChainStorage::new(MemoryChainStore::default());
+        let genesis = test_genesis();
+        let genesis_out = OutPoint::new(construct_tx_hash(&genesis.transactions[0]), 0);
+
+        // The child spends its parent's output within the same block
+        let parent = test_tx(genesis_out.clone(), ADDRESS_B, 10);
+        let child = test_tx(OutPoint::new(construct_tx_hash(&parent), 0), ADDRESS_C, 10);
+        let block = next_block(&genesis, vec![parent.clone(), child.clone()], b"1");
+        let child_out = OutPoint::new(construct_tx_hash(&child), 0);
+
+        storage.connect_block(&genesis).unwrap();
+        assert_eq!(storage.get_address_utxos(ADDRESS_A).unwrap().len(), 1);
+        storage.connect_block(&block).unwrap();
+
+        assert_eq!(storage.get_tip().unwrap(), Some(block.header.hash()));
+        assert_eq!(storage.get_utxo(&genesis_out).unwrap(), None);
+        assert!(storage.get_address_utxos(ADDRESS_A).unwrap().is_empty());
+        assert!(storage.get_address_utxos(ADDRESS_B).unwrap().is_empty());
+        assert_eq!(
+            storage.get_address_utxos(ADDRESS_C).unwrap(),
+            vec![(child_out.clone(), child.outputs[0].clone())]
+        );
+        assert_eq!(
+            storage
+                .get_transaction(&construct_tx_hash(&parent))
+                .unwrap(),
+            Some(parent.clone())
+        );
+
+        assert_eq!(storage.disconnect_tip().unwrap(), block);
+        assert_eq!(storage.get_tip().unwrap(), Some(genesis.header.hash()));
+        assert_eq!(storage.get_utxo(&child_out).unwrap(), None);
+        assert_eq!(storage.get_address_utxos(ADDRESS_A).unwrap().len(), 1);
+        assert_eq!(
+            storage
+                .get_transaction(&construct_tx_hash(&parent))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            storage.get_block(&block.header.hash()).unwrap(),
+            Some(block)
+        );
+    }
+
+    #[test]
+    /// Checks that blocks must extend the tip and spend known outputs, and reorgs apply
+    fn test_reject_invalid_blocks_and_apply_reorg() {
+        let mut storage = ChainStorage::new(MemoryChainStore::default());
+        let genesis = test_genesis();
+        let genesis_out = OutPoint::new(construct_tx_hash(&genesis.transactions[0]), 0);
+        storage.connect_block(&genesis).unwrap();
+
+        let a1 = next_block(
+            &genesis,
+            vec![test_tx(genesis_out.clone(), ADDRESS_B, 10)],
+            b"a",
+        );
+        let b1 = next_block(
+            &genesis,
+            vec![test_tx(genesis_out.clone(), ADDRESS_C, 10)],
+            b"b",
+        );
+        let unknown = next_block(
+            &genesis,
+            vec![test_tx(
+                OutPoint::new("unknown".to_owned(), 0),
+                ADDRESS_B,
+                1,
+            )],
+            b"c",
+        );
+        let double_spend = next_block(
+            &genesis,
+            vec![
+                test_tx(genesis_out.clone(), ADDRESS_B, 10),
+                test_tx(genesis_out.clone(), ADDRESS_C, 10),
+            ],
+            b"d",
+        );
+
+        assert!(storage.connect_block(&unknown).is_err());
+        assert!(storage.connect_block(&double_spend).is_err());
+        storage.connect_block(&a1).unwrap();
+        assert!(storage.connect_block(&b1).is_err());
+
+        storage.store_block(&b1).unwrap();
+        storage
+            .apply_reorg(&Reorg {
+                disconnect: vec![a1.header.hash()],
+                connect: vec![b1.header.hash()],
+            })
+            .unwrap();
+
+        assert_eq!(storage.get_tip().unwrap(), Some(b1.header.hash()));
+        assert!(storage.get_address_utxos(ADDRESS_B).unwrap().is_empty());
+        assert_eq!(storage.get_address_utxos(ADDRESS_C).unwrap().len(), 1);
+    }
+
+    #[test]
+    /// Checks that blocks repeating a connected transaction or one of their own are
+    /// rejected rather than overwriting its outputs
+    fn test_reject_duplicate_transactions() {
+        let mut storage = ChainStorage::new(MemoryChainStore::default());
+        let genesis = test_genesis();
+        let genesis_out = OutPoint::new(construct_tx_hash(&genesis.transactions[0]), 0);
+        storage.connect_block(&genesis).unwrap();
+
+        let spend = test_tx(genesis_out.clone(), ADDRESS_B, 10);
+        let spend_out = OutPoint::new(construct_tx_hash(&spend), 0);
+        let repeated_genesis = next_block(&genesis, vec![genesis.transactions[0].clone()], b"1");
+        let repeated_in_block = next_block(&genesis, vec![spend.clone(), spend.clone()], b"2");
+        let block = next_block(&genesis, vec![spend], b"3");
+
+        assert_eq!(
+            storage.connect_block(&repeated_genesis),
+            Err(format!(
+                "Block contains duplicate transaction {}",
+                genesis_out.t_hash
+            ))
+        );
+        assert!(storage.connect_block(&repeated_in_block).is_err());
+        assert_eq!(storage.get_tip().unwrap(), Some(genesis.header.hash()));
+        assert!(storage.get_utxo(&genesis_out).unwrap().is_some());
+
+        storage.connect_block(&block).unwrap();
+        assert!(storage.get_utxo(&spend_out).unwrap().is_some());
+    }
+
+    #[test]
+    /// Checks that a reorg failing part way leaves the connected chain unchanged
+    fn test_failed_reorg_is_not_applied() {
+        let mut storage = ChainStorage::new(MemoryChainStore::default());
+        let genesis = test_genesis();
+        let genesis_out = OutPoint::new(construct_tx_hash(&genesis.transactions[0]), 0);
+        storage.connect_block(&genesis).unwrap();
+
+        let a1 = next_block(
+            &genesis,
+            vec![test_tx(genesis_out.clone(), ADDRESS_B, 10)],
+            b"a",
+        );
+        let b1 = next_block(
+            &genesis,
+            vec![test_tx(genesis_out.clone(), ADDRESS_C, 10)],
+            b"b",
+        );
+        let b2 = next_block(
+            &b1,
+            vec![test_tx(
+                OutPoint::new("unknown".to_owned(), 0),
+                ADDRESS_C,
+                1,
+            )],
+            b"c",
+        );
+        storage.connect_block(&a1).unwrap();
+        storage.store_block(&b1).unwrap();
+        storage.store_block(&b2).unwrap();
+        let before = storage.store().clone();
+
+        let result = storage.apply_reorg(&Reorg {
+            disconnect: vec![a1.header.hash()],
+            connect: vec![b1.header.hash(), b2.header.hash()],
+        });
+
+        assert!(result.is_err());
+        assert_eq!(storage.get_tip().unwrap(), Some(a1.header.hash()));
+        assert_eq!(storage.get_address_utxos(ADDRESS_B).unwrap().len(), 1);
+        assert!(storage.get_address_utxos(ADDRESS_C).unwrap().is_empty());
+        assert_eq!(storage.store(), &before);
+    }
+}
@@ -3,6 +3,11 @@ pub const TX_PREPEND: u8 = b'g';
 pub const ITEM_DEFAULT_DRS_TX_HASH: &str = "default_genesis_hash";
 pub const MAX_METADATA_BYTES: usize = 800;
 pub const TX_HASH_LENGTH: usize = 32;
+// Maximum number of outputs a single transaction may pay to
+pub const MAX_TX_OUTPUTS: usize = 500;
+
+// Version of the binary preimage signed for each transaction input
+pub const SIGNABLE_PREIMAGE_VERSION: u8 = 1;
 
 /*------- ADDRESS CONSTANTS -------*/
 pub const V0_ADDRESS_LENGTH: usize = 16;
@@ -27,6 +32,20 @@ pub const D_DISPLAY_PLACES: f64 = 72072000.0;
 // Number of possible tokens in existence (5 billion)
 pub const TOTAL_TOKENS: u64 = D_DISPLAY_PLACES_U64 * 5000000000;
 
+/*------- FEE CONSTANTS --------*/
+// Minimum fee rate, in tokens per byte, for a transaction to be relayed
+pub const MIN_RELAY_FEE_RATE: u64 = 1;
+// Minimum absolute fee, in tokens, for a transaction to be relayed
+pub const MIN_RELAY_FEE: u64 = 0;
+// Maximum number of branches explored when searching for a changeless coin selection
+pub const COIN_SELECTION_MAX_TRIES: usize = 100000;
+// Increase in fee rate, in tokens per byte, a replacement transaction must pay
+pub const MIN_REPLACEMENT_FEE_RATE_INCREMENT: u64 = 1;
+
+/*------- MEMPOOL CONSTANTS --------*/
+// Maximum number of bytes of transactions held in the mempool
+pub const MEMPOOL_MAX_SIZE: usize = 50_000_000;
+
 /*------- ASSET CONSTANTS -------*/
 // The value to sign/verify for item-based payments
 pub const ITEM_ACCEPT_VAL: &str = "PAYMENT_ACCEPT";
@@ -34,6 +53,20 @@ pub const ITEM_ACCEPT_VAL: &str = "PAYMENT_ACCEPT";
 /*------- BLOCK CONSTANTS --------*/
 // Maximum number of bytes that a block can contain
 pub const MAX_BLOCK_SIZE: usize = 1000;
+// Length of a hex encoded block header or merkle hash
+pub const BLOCK_HASH_LENGTH: usize = 64;
+// Compact encoding of the easiest proof-of-work target a block may be mined at
+pub const MAX_POW_TARGET_COMPACT: u32 = 0x207fffff;
+
+/*------- DIFFICULTY CONSTANTS --------*/
+// Desired number of seconds between blocks
+pub const DIFFICULTY_TARGET_BLOCK_TIME: i64 = 60;
+// Number of solve times averaged when retargeting every block
+pub const DIFFICULTY_LWMA_WINDOW: usize = 60;
+// Number of previous blocks whose median timestamp a new block must follow
+pub const MEDIAN_TIME_PAST_WINDOW: usize = 11;
+// Maximum number of seconds a block timestamp may be ahead of the current time
+pub const MAX_FUTURE_BLOCK_TIME: i64 = 600;
 
 /*------- SCRIPT CONSTANTS -------*/
 // Maximum number of bytes pushable to the stack
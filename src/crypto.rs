@@ -307,6 +307,324 @@ pub mod pbkdf2 {
     }
 }
 
+pub mod bip39 {
+    use super::generate_random;
+    use ring::digest::{digest, SHA256};
+    use ring::pbkdf2::{derive, PBKDF2_HMAC_SHA512};
+    use std::num::NonZeroU32;
+    use tracing::warn;
+
+    pub const SEED_LEN: usize = 512 / 8;
+    pub const MNEMONIC_WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+    // English wordlist from BIP-39, sorted
+    const WORDLIST: &str = include_str!("crypto/bip39_english.txt");
+    const BITS_PER_WORD: usize = 11;
+    const SEED_ITERATIONS: u32 = 2048;
+
+    /// Gets the BIP-39 English wordlist
+    pub fn get_wordlist() -> Vec<&'static str> {
+        WORDLIST.lines().collect()
+    }
+
+    /// Generates a random mnemonic of 12, 15, 18, 21 or 24 words
+    ///
+    /// ### Arguments
+    ///
+    /// * `word_count`  - Number of words in the mnemonic
+    pub fn generate_mnemonic(word_count: usize) -> Option<String> {
+        if !MNEMONIC_WORD_COUNTS.contains(&word_count) {
+            warn!("Invalid mnemonic word count");
+            return None;
+        }
+
+        let entropy: [u8; 32] = generate_random();
+        entropy_to_mnemonic(&entropy[..word_count * 4 / 3])
+    }
+
+    /// Encodes 16 to 32 bytes of entropy, in steps of 4, as a mnemonic
+    ///
+    /// ### Arguments
+    ///
+    /// * `entropy` - Entropy to encode
+    pub fn entropy_to_mnemonic(entropy: &[u8]) -> Option<String> {
+        if entropy.len() < 16 || entropy.len() > 32 || entropy.len() & 3 != 0 {
+            warn!("Invalid mnemonic entropy length");
+            return None;
+        }
+
+        let checksum = digest(&SHA256, entropy);
+        let mut bits: Vec<bool> = get_bits(entropy).collect();
+        bits.extend(get_bits(checksum.as_ref()).take(entropy.len() / 4));
+
+        let wordlist = get_wordlist();
+        let words: Vec<&str> = bits
+            .chunks(BITS_PER_WORD)
+            .map(|word_bits| {
+                let index = word_bits
+                    .iter()
+                    .fold(0, |index, bit| (index << 1) | *bit as usize);
+                wordlist[index]
+            })
+            .collect();
+        Some(words.join(" "))
+    }
+
+    /// Decodes the entropy of a mnemonic, checking its words and checksum
+    ///
+    /// ### Arguments
+    ///
+    /// * `mnemonic`    - Mnemonic to decode
+    pub fn mnemonic_to_entropy(mnemonic: &str) -> Option<Vec<u8>> {
+        let wordlist = get_wordlist();
+        let words: Vec<&str> = mnemonic.split_whitespace().collect();
+        if !MNEMONIC_WORD_COUNTS.contains(&words.len()) {
+            return None;
+        }
+
+        let mut bits = Vec::with_capacity(words.len() * BITS_PER_WORD);
+        for word in words {
+            let index = wordlist.binary_search(&word).ok()?;
+            bits.extend((0..BITS_PER_WORD).rev().map(|i| (index >> i) & 1 == 1));
+        }
+
+        let entropy_bits = bits.len() * 32 / 33;
+        let entropy: Vec<u8> = bits[..entropy_bits]
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |value, bit| (value << 1) | *bit as u8))
+            .collect();
+
+        let checksum = digest(&SHA256, &entropy);
+        let checksum_bits = get_bits(checksum.as_ref()).take(bits.len() - entropy_bits);
+        if !checksum_bits.eq(bits[entropy_bits..].iter().copied()) {
+            return None;
+        }
+        Some(entropy)
+    }
+
+    /// Checks that a mnemonic has known words and a valid checksum
+    ///
+    /// ### Arguments
+    ///
+    /// * `mnemonic`    - Mnemonic to check
+    pub fn validate_mnemonic(mnemonic: &str) -> bool {
+        mnemonic_to_entropy(mnemonic).is_some()
+    }
+
+    /// Derives the seed of a valid mnemonic. The passphrase is used as given,
+    /// without Unicode normalization.
+    ///
+    /// ### Arguments
+    ///
+    /// * `mnemonic`    - Mnemonic to derive the seed of
+    /// * `passphrase`  - Optional passphrase, empty if unused
+    pub fn mnemonic_to_seed(mnemonic: &str, passphrase: &str) -> Option<[u8; SEED_LEN]> {
+        if !validate_mnemonic(mnemonic) {
+            warn!("Invalid mnemonic");
+            return None;
+        }
+
+        let mnemonic = mnemonic.split_whitespace().collect::<Vec<_>>().join(" ");
+        let salt = format!("mnemonic{passphrase}");
+        let iterations = NonZeroU32::new(SEED_ITERATIONS)?;
+
+        let mut seed = [0; SEED_LEN];
+        derive(
+            PBKDF2_HMAC_SHA512,
+            iterations,
+            salt.as_bytes(),
+            mnemonic.as_bytes(),
+            &mut seed,
+        );
+        Some(seed)
+    }
+
+    /// Iterates over the bits of bytes, most significant first
+    fn get_bits(bytes: &[u8]) -> impl Iterator<Item = bool> + '_ {
+        bytes
+            .iter()
+            .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+    }
+}
+
+pub mod slip10_ed25519 {
+    use super::sign_ed25519::{PublicKey, SecretKey, SecretKeyBase};
+    use ring::hmac::{Context, Key, HMAC_SHA512};
+    use ring::signature::KeyPair;
+    use std::convert::TryInto;
+    use std::fmt;
+    use std::str::FromStr;
+    use tracing::warn;
+
+    pub const HARDENED_OFFSET: u32 = 0x8000_0000;
+    pub const KEY_LEN: usize = 256 / 8;
+
+    const SEED_KEY: &[u8] = b"ed25519 seed";
+    // PKCS8 v2 framing of an Ed25519 key, as generated by ring
+    const PKCS8_PREFIX: [u8; 16] = [
+        0x30, 0x53, 0x02, 0x01, 0x01, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04,
+        0x20,
+    ];
+    const PKCS8_PUBLIC_KEY_PREFIX: [u8; 5] = [0xa1, 0x23, 0x03, 0x21, 0x00];
+
+    /// A path of hardened child indices from a master key, written as `m/44'/0'/0'`
+    #[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct DerivationPath(Vec<u32>);
+
+    impl DerivationPath {
+        /// Creates a path from hardened child indices
+        ///
+        /// ### Arguments
+        ///
+        /// * `indices` - Child indices, including `HARDENED_OFFSET`
+        pub fn from_indices(indices: Vec<u32>) -> Option<Self> {
+            indices
+                .iter()
+                .all(|index| *index >= HARDENED_OFFSET)
+                .then_some(Self(indices))
+        }
+
+        /// Gets the path to the hardened child with the given unhardened index
+        ///
+        /// ### Arguments
+        ///
+        /// * `index`   - Child index, without `HARDENED_OFFSET`
+        pub fn child(&self, index: u32) -> Option<Self> {
+            let mut indices = self.0.clone();
+            indices.push(index.checked_add(HARDENED_OFFSET)?);
+            Some(Self(indices))
+        }
+
+        /// Gets the child indices, including `HARDENED_OFFSET`
+        pub fn indices(&self) -> &[u32] {
+            &self.0
+        }
+    }
+
+    impl FromStr for DerivationPath {
+        type Err = String;
+
+        fn from_str(path: &str) -> Result<Self, Self::Err> {
+            let mut segments = path.split('/');
+            if segments.next() != Some("m") {
+                return Err("Derivation path must start with m".to_string());
+            }
+
+            segments
+                .map(|segment| {
+                    let index = segment
+                        .strip_suffix(|c| c == '\'' || c == 'h' || c == 'H')
+                        .ok_or_else(|| format!("Index {segment} is not hardened"))?;
+                    index
+                        .parse::<u32>()
+                        .ok()
+                        .and_then(|index| index.checked_add(HARDENED_OFFSET))
+                        .ok_or_else(|| format!("Invalid index {segment}"))
+                })
+                .collect::<Result<_, _>>()
+                .map(Self)
+        }
+    }
+
+    impl fmt::Display for DerivationPath {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "m")?;
+            for index in &self.0 {
+                write!(f, "/{}'", index - HARDENED_OFFSET)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// An Ed25519 secret key seed with the chain code its children are derived with
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct ExtendedSecretKey {
+        pub key: [u8; KEY_LEN],
+        pub chain_code: [u8; KEY_LEN],
+    }
+
+    impl ExtendedSecretKey {
+        /// Derives the master key of a seed
+        ///
+        /// ### Arguments
+        ///
+        /// * `seed`    - Seed, such as one derived from a mnemonic
+        pub fn from_seed(seed: &[u8]) -> Self {
+            hmac_sha512(SEED_KEY, &[seed])
+        }
+
+        /// Derives a hardened child key. Ed25519 has no unhardened derivation.
+        ///
+        /// ### Arguments
+        ///
+        /// * `index`   - Child index, including `HARDENED_OFFSET`
+        pub fn derive_child(&self, index: u32) -> Option<Self> {
+            if index < HARDENED_OFFSET {
+                warn!("Unhardened derivation is not supported for Ed25519");
+                return None;
+            }
+            Some(self.derive_hardened_child(index))
+        }
+
+        /// Derives the key at a path from this key
+        ///
+        /// ### Arguments
+        ///
+        /// * `path`    - Path to derive
+        pub fn derive_path(&self, path: &DerivationPath) -> Self {
+            path.indices()
+                .iter()
+                .fold(self.clone(), |key, index| key.derive_hardened_child(*index))
+        }
+
+        /// Gets the key pair of this key, with the secret key PKCS8 encoded
+        pub fn to_keypair(&self) -> Option<(PublicKey, SecretKey)> {
+            let secret = SecretKeyBase::from_seed_unchecked(&self.key).ok()?;
+            let public_key = secret.public_key().as_ref();
+
+            let mut pkcs8 = PKCS8_PREFIX.to_vec();
+            pkcs8.extend_from_slice(&self.key);
+            pkcs8.extend_from_slice(&PKCS8_PUBLIC_KEY_PREFIX);
+            pkcs8.extend_from_slice(public_key);
+
+            Some((
+                PublicKey::from_slice(public_key)?,
+                SecretKey::from_slice(&pkcs8)?,
+            ))
+        }
+
+        /// Derives a child key, with `index` already hardened
+        fn derive_hardened_child(&self, index: u32) -> Self {
+            hmac_sha512(&self.chain_code, &[&[0], &self.key, &index.to_be_bytes()])
+        }
+    }
+
+    /// Derives the key pair at a path from a seed
+    ///
+    /// ### Arguments
+    ///
+    /// * `seed`    - Seed, such as one derived from a mnemonic
+    /// * `path`    - Path to derive
+    pub fn derive_keypair(seed: &[u8], path: &DerivationPath) -> Option<(PublicKey, SecretKey)> {
+        ExtendedSecretKey::from_seed(seed)
+            .derive_path(path)
+            .to_keypair()
+    }
+
+    /// Computes HMAC-SHA512 over data, split into a key and a chain code
+    fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> ExtendedSecretKey {
+        let mut context = Context::with_key(&Key::new(HMAC_SHA512, key));
+        data.iter().for_each(|data| context.update(data));
+        let tag = context.sign();
+        let (key, chain_code) = tag.as_ref().split_at(KEY_LEN);
+
+        ExtendedSecretKey {
+            key: key.try_into().unwrap_or_default(),
+            chain_code: chain_code.try_into().unwrap_or_default(),
+        }
+    }
+}
+
 pub mod sha3_256 {
     pub use sha3::digest::Output;
     pub use sha3::Digest;
@@ -344,3 +662,137 @@ pub fn generate_random<const N: usize>() -> [u8; N] {
 
     value
 }
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::bip39::*;
+    use super::sign_ed25519::{sign_detached, verify_detached};
+    use super::slip10_ed25519::*;
+    use crate::utils::transaction_utils::construct_address;
+
+    const SLIP10_SEED: &str = "000102030405060708090a0b0c0d0e0f";
+
+    #[test]
+    /// Checks mnemonics and seeds against the BIP-39 test vectors
+    fn test_bip39_vectors() {
+        let vectors = [
+            ("00".repeat(16), format!("{}about", "abandon ".repeat(11))),
+            (
+                "7f".repeat(16),
+                "legal winner thank year wave sausage worth useful legal winner thank yellow"
+                    .to_owned(),
+            ),
+            (
+                "80".repeat(32),
+                format!(
+                    "{}letter advice cage absurd amount doctor acoustic bless",
+                    "letter advice cage absurd amount doctor acoustic avoid ".repeat(2)
+                ),
+            ),
+            ("ff".repeat(32), format!("{}vote", "zoo ".repeat(23))),
+            (
+                "9e885d952ad362caeb4efe34a8e91bd2".to_owned(),
+                "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic"
+                    .to_owned(),
+            ),
+        ];
+
+        for (entropy, mnemonic) in &vectors {
+            let entropy = hex::decode(entropy).unwrap();
+            assert_eq!(entropy_to_mnemonic(&entropy).as_ref(), Some(mnemonic));
+            assert_eq!(mnemonic_to_entropy(mnemonic), Some(entropy));
+        }
+
+        let seed = mnemonic_to_seed(&vectors[0].1, "TREZOR").unwrap();
+        assert_eq!(
+            hex::encode(seed),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    /// Checks that generated mnemonics are valid and malformed ones are rejected
+    fn test_validate_mnemonic() {
+        let abandon = "abandon ".repeat(11);
+
+        assert!(validate_mnemonic(&generate_mnemonic(12).unwrap()));
+        assert_eq!(generate_mnemonic(24).unwrap().split(' ').count(), 24);
+        assert_eq!(generate_mnemonic(13), None);
+        assert_eq!(entropy_to_mnemonic(&[0; 15]), None);
+
+        assert!(validate_mnemonic(&format!("{abandon}about")));
+        assert!(!validate_mnemonic(&format!("{abandon}abandon")));
+        assert!(!validate_mnemonic(&format!("{abandon}notaword")));
+        assert!(!validate_mnemonic(&format!("{abandon}about about")));
+        assert_eq!(mnemonic_to_seed(&format!("{abandon}abandon"), ""), None);
+    }
+
+    #[test]
+    /// Checks derived keys against the SLIP-0010 Ed25519 test vectors
+    fn test_slip10_vectors() {
+        let seed = hex::decode(SLIP10_SEED).unwrap();
+        let master = ExtendedSecretKey::from_seed(&seed);
+        let path: DerivationPath = "m/0'/1'/2'/2'/1000000000'".parse().unwrap();
+        let child = master.derive_path(&path);
+        let (child_pk, _) = child.to_keypair().unwrap();
+
+        assert_eq!(
+            hex::encode(master.key),
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+        );
+        assert_eq!(
+            hex::encode(master.chain_code),
+            "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb"
+        );
+        assert_eq!(
+            hex::encode(master.derive_child(HARDENED_OFFSET).unwrap().key),
+            "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3"
+        );
+        assert_eq!(master.derive_child(0), None);
+        assert_eq!(
+            hex::encode(child.key),
+            "8f94d394a8e8fd6b1bc2f3f49f5c47e385281d5c17e65324b0f62483e37e8793"
+        );
+        assert_eq!(
+            hex::encode(child.chain_code),
+            "68789923a0cac2cd5a29172a475fe9e0fb14cd6adb5ad98a3fa70333e7afa230"
+        );
+        assert_eq!(
+            hex::encode(child_pk.as_ref()),
+            "3c24da049451555d51a7014a37337aa4e12d41e485abccfa46b47dfb2af54b7a"
+        );
+    }
+
+    #[test]
+    /// Checks that derivation paths parse, display and reject unhardened indices
+    fn test_derivation_path() {
+        let path: DerivationPath = "m/44'/0h/1H".parse().unwrap();
+
+        assert_eq!(path.to_string(), "m/44'/0'/1'");
+        assert_eq!(DerivationPath::default().child(44), "m/44'".parse().ok());
+        assert_eq!(DerivationPath::from_indices(vec![0]), None);
+        assert!("m/0".parse::<DerivationPath>().is_err());
+        assert!("0'/1'".parse::<DerivationPath>().is_err());
+        assert!("m/2147483648'".parse::<DerivationPath>().is_err());
+    }
+
+    #[test]
+    /// Checks that a wallet restored from its mnemonic recreates its keys and addresses
+    fn test_restore_keypair_from_mnemonic() {
+        let mnemonic = generate_mnemonic(24).unwrap();
+        let path: DerivationPath = "m/44'/0'/0'".parse().unwrap();
+        let seed = mnemonic_to_seed(&mnemonic, "").unwrap();
+        let (pk, sk) = derive_keypair(&seed, &path).unwrap();
+
+        let restored_seed = mnemonic_to_seed(&mnemonic, "").unwrap();
+        let (restored_pk, _) = derive_keypair(&restored_seed, &path).unwrap();
+        let (other_pk, _) = derive_keypair(&seed, &path.child(1).unwrap()).unwrap();
+        let signature = sign_detached(b"message", &sk);
+
+        assert!(verify_detached(&signature, b"message", &pk));
+        assert_eq!(construct_address(&pk), construct_address(&restored_pk));
+        assert_ne!(construct_address(&pk), construct_address(&other_pk));
+    }
+}
@@ -1,5 +1,7 @@
+pub mod chain;
 pub mod constants;
 pub mod crypto;
+pub mod mempool;
 pub mod primitives;
 pub mod script;
 pub mod utils;
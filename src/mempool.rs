@@ -0,0 +1,687 @@
+use crate::constants::{MEMPOOL_MAX_SIZE, MIN_REPLACEMENT_FEE_RATE_INCREMENT};
+use crate::primitives::asset::TokenAmount;
+use crate::primitives::network::Network;
+use crate::primitives::transaction::{OutPoint, Transaction, TxOut};
+use crate::utils::block_template_builder::select_in_dependency_order;
+use crate::utils::consensus_utils::{tx_is_valid_with_rules, ConsensusRules};
+use crate::utils::fee_utils::{get_tx_fees_paid, tx_meets_fee_policy, FeePolicy, FeeRate};
+use crate::utils::transaction_utils::construct_tx_hash;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use tracing::{debug, error};
+
+/// A transaction held in the mempool, with its size and fee
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MempoolEntry {
+    pub tx: Transaction,
+    pub size: usize,
+    pub fee: TokenAmount,
+    pub fee_rate: FeeRate,
+}
+
+impl MempoolEntry {
+    /// Creates a new mempool entry for a transaction
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx`  - Transaction to hold
+    pub fn new(tx: Transaction) -> Self {
+        let size = tx.get_total_size();
+        let fee = get_tx_fees_paid(&tx);
+
+        Self {
+            tx,
+            size,
+            fee,
+            fee_rate: FeeRate::from_fee_and_size(fee, size),
+        }
+    }
+}
+
+/// Pool of valid, unconfirmed transactions waiting to be included in a block.
+/// Transactions may spend the outputs of other transactions in the pool, but no
+/// two transactions may spend the same `OutPoint`.
+#[derive(Clone, Debug)]
+pub struct Mempool {
+    entries: BTreeMap<String, MempoolEntry>,
+    spent: BTreeMap<OutPoint, String>,
+    policy: FeePolicy,
+    consensus_rules: ConsensusRules,
+    network: Network,
+    max_size: usize,
+    size: usize,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new(FeePolicy::default(), MEMPOOL_MAX_SIZE)
+    }
+}
+
+impl Mempool {
+    /// Creates a new, empty mempool
+    ///
+    /// ### Arguments
+    ///
+    /// * `policy`      - Fee policy transactions must meet to be accepted
+    /// * `max_size`    - Maximum number of bytes of transactions to hold
+    pub fn new(policy: FeePolicy, max_size: usize) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            spent: BTreeMap::new(),
+            policy,
+            consensus_rules: ConsensusRules::default(),
+            network: Network::Mainnet,
+            max_size,
+            size: 0,
+        }
+    }
+
+    /// Sets the consensus rules transactions are validated under. Defaults to
+    /// `ConsensusRules::default()`
+    ///
+    /// ### Arguments
+    ///
+    /// * `consensus_rules` - Rules of each transaction version
+    pub fn with_consensus_rules(mut self, consensus_rules: ConsensusRules) -> Self {
+        self.consensus_rules = consensus_rules;
+        self
+    }
+
+    /// Sets the network transactions are validated for. Defaults to mainnet
+    ///
+    /// ### Arguments
+    ///
+    /// * `network` - Network the pool's transactions are signed for
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Gets the number of transactions in the pool
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks whether the pool holds no transactions
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Gets the total size in bytes of the transactions in the pool
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Checks whether a transaction is in the pool
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx_hash` - Hash of the transaction
+    pub fn contains(&self, tx_hash: &str) -> bool {
+        self.entries.contains_key(tx_hash)
+    }
+
+    /// Gets a transaction in the pool
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx_hash` - Hash of the transaction
+    pub fn get(&self, tx_hash: &str) -> Option<&MempoolEntry> {
+        self.entries.get(tx_hash)
+    }
+
+    /// Gets the hash of the pooled transaction spending an `OutPoint`, if any
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_point`   - `OutPoint` to check
+    pub fn get_spending_tx(&self, out_point: &OutPoint) -> Option<&String> {
+        self.spent.get(out_point)
+    }
+
+    /// Gets an output created by a transaction in the pool
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_point`   - `OutPoint` of the output
+    pub fn get_pool_output(&self, out_point: &OutPoint) -> Option<&TxOut> {
+        let entry = self.entries.get(&out_point.t_hash)?;
+        entry.tx.outputs.get(usize::try_from(out_point.n).ok()?)
+    }
+
+    /// Validates and adds a transaction to the pool, returning its hash. A transaction
+    /// conflicting with pooled transactions replaces them only if it pays a higher fee
+    /// rate and covers the fees of everything it replaces.
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx`                      - Transaction to add
+    /// * `current_block_number`    - Current block number
+    /// * `is_in_utxo`              - Function to check if a `TxOut` is in the UTXO set
+    /// * `utxo_height`             - Function to get the block number a UTXO was included in
+    pub fn add_transaction<'a>(
+        &mut self,
+        tx: Transaction,
+        current_block_number: u64,
+        is_in_utxo: impl Fn(&OutPoint) -> Option<&'a TxOut>,
+        utxo_height: impl Fn(&OutPoint) -> Option<u64>,
+    ) -> Result<String, String> {
+        let tx_hash = construct_tx_hash(&tx);
+        if self.contains(&tx_hash) {
+            return Err("Transaction is already in the mempool".to_string());
+        }
+
+        let (is_valid, reason) = tx_meets_fee_policy(&tx, &self.policy);
+        if !is_valid {
+            return Err(reason);
+        }
+
+        let (is_valid, reason) = tx_is_valid_with_rules(
+            &tx,
+            &self.consensus_rules,
+            self.network,
+            current_block_number,
+            |out_point| is_in_utxo(out_point).or_else(|| self.get_pool_output(out_point)),
+            utxo_height,
+        );
+        if !is_valid {
+            return Err(reason);
+        }
+
+        let entry = MempoolEntry::new(tx);
+        let replaced = self.get_replaced_txs(&entry)?;
+        for hash in &replaced {
+            self.remove_entry(hash);
+        }
+
+        debug!(
+            "Adding transaction {} to mempool, replacing {} transactions",
+            tx_hash,
+            replaced.len()
+        );
+        self.insert_entry(tx_hash.clone(), entry);
+        self.evict_to_max_size();
+
+        if !self.contains(&tx_hash) {
+            return Err("Mempool is full".to_string());
+        }
+        Ok(tx_hash)
+    }
+
+    /// Removes transactions included in a block, along with any pooled transactions
+    /// which conflict with them and those transactions' descendants
+    ///
+    /// ### Arguments
+    ///
+    /// * `txs` - Transactions included in the block
+    pub fn remove_block_transactions(&mut self, txs: &[Transaction]) {
+        for tx in txs {
+            let tx_hash = construct_tx_hash(tx);
+            if self.remove_entry(&tx_hash).is_some() {
+                debug!("Removed confirmed transaction {} from mempool", tx_hash);
+                continue;
+            }
+
+            for tx_in in &tx.inputs {
+                let conflict = tx_in
+                    .previous_out
+                    .as_ref()
+                    .and_then(|out_point| self.spent.get(out_point).cloned());
+
+                if let Some(conflict) = conflict {
+                    for hash in self.get_descendants(&conflict) {
+                        self.remove_entry(&hash);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Selects transactions for a block in descending order of fee rate, without
+    /// exceeding the given size. Transactions are only selected after any pooled
+    /// transactions whose outputs they spend.
+    ///
+    /// ### Arguments
+    ///
+    /// * `max_block_size`  - Maximum total size in bytes of the selected transactions
+    pub fn select_block_transactions(&self, max_block_size: usize) -> Vec<Transaction> {
+        let mut candidates: Vec<(&String, &MempoolEntry)> = self.entries.iter().collect();
+        candidates.sort_by(|a, b| b.1.fee_rate.cmp(&a.1.fee_rate).then_with(|| a.0.cmp(b.0)));
+        let candidates: Vec<(String, &Transaction)> = candidates
+            .into_iter()
+            .map(|(tx_hash, entry)| (tx_hash.clone(), &entry.tx))
+            .collect();
+
+        let mut block_size = 0;
+        let selected = select_in_dependency_order(&candidates, |tx_hash, _| {
+            let size = self.entries[tx_hash].size;
+            if block_size + size > max_block_size {
+                return false;
+            }
+            block_size += size;
+            true
+        });
+
+        selected.into_iter().map(|(_, tx)| tx.clone()).collect()
+    }
+
+    /// Gets the pooled transactions an entry would replace, checking that the entry
+    /// pays enough to replace them
+    ///
+    /// ### Arguments
+    ///
+    /// * `entry`   - Entry to be added
+    fn get_replaced_txs(&self, entry: &MempoolEntry) -> Result<BTreeSet<String>, String> {
+        let conflicts: BTreeSet<String> = entry
+            .tx
+            .inputs
+            .iter()
+            .filter_map(|tx_in| self.spent.get(tx_in.previous_out.as_ref()?).cloned())
+            .collect();
+
+        if conflicts.is_empty() {
+            return Ok(conflicts);
+        }
+
+        let increment = FeeRate(MIN_REPLACEMENT_FEE_RATE_INCREMENT);
+        let max_conflict_rate = conflicts
+            .iter()
+            .filter_map(|hash| self.entries.get(hash))
+            .map(|conflict| conflict.fee_rate)
+            .max()
+            .unwrap_or_default();
+
+        if entry.fee_rate.0 < max_conflict_rate.0 + increment.0 {
+            error!("REPLACEMENT TRANSACTION FEE RATE TOO LOW");
+            return Err("Transaction conflicts with the mempool".to_string());
+        }
+
+        let mut replaced = BTreeSet::new();
+        for conflict in &conflicts {
+            replaced.extend(self.get_descendants(conflict));
+        }
+
+        let spends_replaced = entry.tx.inputs.iter().any(|tx_in| {
+            tx_in
+                .previous_out
+                .as_ref()
+                .is_some_and(|out_point| replaced.contains(&out_point.t_hash))
+        });
+        if spends_replaced {
+            return Err("Replacement transaction spends a transaction it replaces".to_string());
+        }
+
+        let replaced_fees: TokenAmount = replaced
+            .iter()
+            .filter_map(|hash| self.entries.get(hash))
+            .map(|replaced| replaced.fee)
+            .sum();
+
+        if entry.fee < replaced_fees + increment.fee_for_size(entry.size) {
+            error!("REPLACEMENT TRANSACTION FEE TOO LOW");
+            return Err("Replacement transaction does not cover replaced fees".to_string());
+        }
+
+        Ok(replaced)
+    }
+
+    /// Gets a pooled transaction and all pooled transactions spending its outputs,
+    /// directly or indirectly
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx_hash` - Hash of the transaction
+    fn get_descendants(&self, tx_hash: &str) -> BTreeSet<String> {
+        let mut descendants = BTreeSet::new();
+        let mut to_visit = vec![tx_hash.to_string()];
+
+        while let Some(hash) = to_visit.pop() {
+            let entry = match self.entries.get(&hash) {
+                Some(entry) => entry,
+                None => continue,
+            };
+
+            for n in 0..entry.tx.outputs.len() {
+                let out_point = OutPoint::new(hash.clone(), n as i32);
+                if let Some(child) = self.spent.get(&out_point) {
+                    to_visit.push(child.clone());
+                }
+            }
+            descendants.insert(hash);
+        }
+
+        descendants
+    }
+
+    /// Evicts the lowest fee rate transactions, with their descendants, until the
+    /// pool is within its maximum size
+    fn evict_to_max_size(&mut self) {
+        while self.size > self.max_size {
+            let lowest = self
+                .entries
+                .iter()
+                .min_by(|a, b| a.1.fee_rate.cmp(&b.1.fee_rate).then_with(|| b.0.cmp(a.0)))
+                .map(|(hash, _)| hash.clone());
+
+            let lowest = match lowest {
+                Some(lowest) => lowest,
+                None => return,
+            };
+
+            for hash in self.get_descendants(&lowest) {
+                debug!("Evicting transaction {} from mempool", hash);
+                self.remove_entry(&hash);
+            }
+        }
+    }
+
+    /// Inserts an entry, marking the `OutPoint`s it spends
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx_hash` - Hash of the transaction
+    /// * `entry`   - Entry to insert
+    fn insert_entry(&mut self, tx_hash: String, entry: MempoolEntry) {
+        for out_point in entry
+            .tx
+            .inputs
+            .iter()
+            .filter_map(|i| i.previous_out.clone())
+        {
+            self.spent.insert(out_point, tx_hash.clone());
+        }
+        self.size += entry.size;
+        self.entries.insert(tx_hash, entry);
+    }
+
+    /// Removes an entry, unmarking the `OutPoint`s it spends
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx_hash` - Hash of the transaction
+    fn remove_entry(&mut self, tx_hash: &str) -> Option<MempoolEntry> {
+        let entry = self.entries.remove(tx_hash)?;
+        for tx_in in &entry.tx.inputs {
+            if let Some(out_point) = &tx_in.previous_out {
+                self.spent.remove(out_point);
+            }
+        }
+        self.size -= entry.size;
+        Some(entry)
+    }
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MAX_BLOCK_SIZE;
+    use crate::constants::NETWORK_VERSION;
+    use crate::crypto::sign_ed25519::{self as sign, PublicKey, SecretKey};
+    use crate::primitives::asset::Asset;
+    use crate::utils::coin_selection_utils::CoinSelectionStrategy;
+    use crate::utils::consensus_utils::ValidationRules;
+    use crate::utils::transaction_builder::TransactionBuilder;
+    use crate::utils::transaction_utils::{construct_address, ReceiverInfo};
+
+    /// Owner of the outputs spent in tests
+    struct TestWallet {
+        address: String,
+        keys: (PublicKey, SecretKey),
+    }
+
+    impl TestWallet {
+        fn new() -> Self {
+            let (pk, sk) = sign::gen_keypair();
+            Self {
+                address: construct_address(&pk),
+                keys: (pk, sk),
+            }
+        }
+
+        /// Creates outputs of the given token amounts owned by this wallet
+        fn utxos(&self, amounts: &[u64]) -> BTreeMap<OutPoint, TxOut> {
+            amounts
+                .iter()
+                .enumerate()
+                .map(|(n, amount)| {
+                    let out_point = OutPoint::new("tx_hash".to_owned(), n as i32);
+                    let tx_out =
+                        TxOut::new_asset(self.address.clone(), Asset::token_u64(*amount), None);
+                    (out_point, tx_out)
+                })
+                .collect()
+        }
+
+        /// Gets the outputs of a transaction as spendable outputs
+        fn tx_outputs(&self, tx: &Transaction) -> Vec<(OutPoint, TxOut)> {
+            let tx_hash = construct_tx_hash(tx);
+            tx.outputs
+                .iter()
+                .enumerate()
+                .map(|(n, tx_out)| (OutPoint::new(tx_hash.clone(), n as i32), tx_out.clone()))
+                .collect()
+        }
+
+        /// Builds a payment to this wallet spending the given outputs
+        fn pay(&self, utxos: Vec<(OutPoint, TxOut)>, amount: u64, fee_rate: u64) -> Transaction {
+            let key_material = utxos
+                .iter()
+                .map(|(out_point, _)| (out_point.clone(), self.keys.clone()))
+                .collect();
+
+            TransactionBuilder::new()
+                .with_utxos(utxos)
+                .with_key_material(key_material)
+                .with_strategy(CoinSelectionStrategy::LargestFirst)
+                .with_fee(FeeRate(fee_rate), hex::encode(vec![1; 32]))
+                .with_change_address(self.address.clone())
+                .pay_to(
+                    ReceiverInfo {
+                        address: self.address.clone(),
+                        asset: Asset::token_u64(amount),
+                    },
+                    0,
+                )
+                .build()
+                .unwrap()
+        }
+    }
+
+    /// Gets a single output from a set of outputs
+    fn utxo(utxos: &BTreeMap<OutPoint, TxOut>, n: i32) -> Vec<(OutPoint, TxOut)> {
+        let out_point = OutPoint::new("tx_hash".to_owned(), n);
+        vec![(out_point.clone(), utxos[&out_point].clone())]
+    }
+
+    #[test]
+    /// Checks that unconfirmed chains are accepted and selected parent first
+    fn test_add_chained_transactions() {
+        let wallet = TestWallet::new();
+        let utxos = wallet.utxos(&[100_000]);
+        let mut mempool = Mempool::default();
+
+        let parent = wallet.pay(utxo(&utxos, 0), 50_000, 1);
+        let child = wallet.pay(wallet.tx_outputs(&parent), 10_000, 20);
+        let parent_hash = mempool.add_transaction(parent, 0, |v| utxos.get(v), |_| None);
+        let child_hash = mempool.add_transaction(child, 0, |v| utxos.get(v), |_| None);
+        let selected = mempool.select_block_transactions(usize::MAX);
+
+        assert_eq!(mempool.len(), 2);
+        assert_eq!(
+            selected.iter().map(construct_tx_hash).collect::<Vec<_>>(),
+            vec![parent_hash.unwrap(), child_hash.unwrap()]
+        );
+        assert!(mempool
+            .add_transaction(selected[0].clone(), 0, |v| utxos.get(v), |_| None)
+            .is_err());
+    }
+
+    #[test]
+    /// Checks that conflicts are rejected unless they pay enough to replace the
+    /// conflicting transaction and its descendants
+    fn test_conflict_and_replace_by_fee() {
+        let wallet = TestWallet::new();
+        let utxos = wallet.utxos(&[100_000]);
+        let mut mempool = Mempool::default();
+
+        let original = wallet.pay(utxo(&utxos, 0), 50_000, 2);
+        let child = wallet.pay(wallet.tx_outputs(&original), 10_000, 2);
+        mempool
+            .add_transaction(original, 0, |v| utxos.get(v), |_| None)
+            .unwrap();
+        mempool
+            .add_transaction(child, 0, |v| utxos.get(v), |_| None)
+            .unwrap();
+
+        let conflict = wallet.pay(utxo(&utxos, 0), 40_000, 2);
+        let replacement = wallet.pay(utxo(&utxos, 0), 40_000, 10);
+        let conflict_result = mempool.add_transaction(conflict, 0, |v| utxos.get(v), |_| None);
+        let replacement_hash = mempool
+            .add_transaction(replacement, 0, |v| utxos.get(v), |_| None)
+            .unwrap();
+
+        assert!(conflict_result.is_err());
+        assert_eq!(mempool.len(), 1);
+        assert!(mempool.contains(&replacement_hash));
+        assert_eq!(
+            mempool.get_spending_tx(&OutPoint::new("tx_hash".to_owned(), 0)),
+            Some(&replacement_hash)
+        );
+    }
+
+    #[test]
+    /// Checks that the lowest fee rate transactions are evicted when the pool is full
+    fn test_evict_by_fee_rate() {
+        let wallet = TestWallet::new();
+        let utxos = wallet.utxos(&[100_000, 100_000, 100_000]);
+        let low = wallet.pay(utxo(&utxos, 0), 50_000, 1);
+        let high = wallet.pay(utxo(&utxos, 1), 50_000, 5);
+        let lowest = wallet.pay(utxo(&utxos, 2), 50_000, 1);
+        let max_size = high.get_total_size() + 1;
+        let mut mempool = Mempool::new(FeePolicy::default(), max_size);
+
+        let low_hash = mempool
+            .add_transaction(low, 0, |v| utxos.get(v), |_| None)
+            .unwrap();
+        let high_hash = mempool
+            .add_transaction(high, 0, |v| utxos.get(v), |_| None)
+            .unwrap();
+        let lowest_result = mempool.add_transaction(lowest, 0, |v| utxos.get(v), |_| None);
+
+        assert!(!mempool.contains(&low_hash));
+        assert!(mempool.contains(&high_hash));
+        assert_eq!(lowest_result, Err("Mempool is full".to_string()));
+        assert!(mempool.size() <= max_size);
+    }
+
+    #[test]
+    /// Checks that block selection orders by fee rate within the block size and that
+    /// confirmed transactions are removed
+    fn test_select_and_remove_block_transactions() {
+        let wallet = TestWallet::new();
+        let utxos = wallet.utxos(&[100_000, 100_000, 100_000]);
+        let mut mempool = Mempool::default();
+
+        for (n, fee_rate) in [(0, 1), (1, 3), (2, 2)] {
+            let tx = wallet.pay(utxo(&utxos, n), 50_000, fee_rate);
+            mempool
+                .add_transaction(tx, 0, |v| utxos.get(v), |_| None)
+                .unwrap();
+        }
+
+        let selected = mempool.select_block_transactions(MAX_BLOCK_SIZE);
+        let selected_size: usize = selected.iter().map(|tx| tx.get_total_size()).sum();
+        let fee_rates: Vec<FeeRate> = selected
+            .iter()
+            .map(|tx| mempool.get(&construct_tx_hash(tx)).unwrap().fee_rate)
+            .collect();
+
+        mempool.remove_block_transactions(&selected);
+
+        assert!(!selected.is_empty());
+        assert!(selected_size <= MAX_BLOCK_SIZE);
+        assert!(fee_rates.windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(mempool.len(), 3 - selected.len());
+    }
+
+    #[test]
+    /// Checks that transactions are validated under the pool's consensus rules
+    fn test_add_transaction_with_consensus_rules() {
+        let wallet = TestWallet::new();
+        let utxos = wallet.utxos(&[100_000]);
+        let consensus_rules = ConsensusRules::default().with_rule_set(
+            NETWORK_VERSION as usize,
+            10,
+            ValidationRules::default(),
+        );
+        let mut mempool = Mempool::default().with_consensus_rules(consensus_rules);
+
+        let tx = wallet.pay(utxo(&utxos, 0), 50_000, 1);
+        let inactive_result = mempool.add_transaction(tx.clone(), 9, |v| utxos.get(v), |_| None);
+
+        assert!(inactive_result.is_err());
+        assert!(mempool
+            .add_transaction(tx, 10, |v| utxos.get(v), |_| None)
+            .is_ok());
+    }
+
+    #[test]
+    /// Checks that relative-locked transactions are accepted once enough blocks have
+    /// passed since the outputs they spend were included
+    fn test_add_relative_locked_transaction() {
+        let wallet = TestWallet::new();
+        let utxos = wallet.utxos(&[100_000]);
+        let mut mempool = Mempool::default();
+
+        let tx = TransactionBuilder::new()
+            .with_utxos(utxo(&utxos, 0))
+            .with_key_material(
+                utxos
+                    .keys()
+                    .map(|out_point| (out_point.clone(), wallet.keys.clone()))
+                    .collect(),
+            )
+            .with_fee(FeeRate(1), hex::encode(vec![1; 32]))
+            .with_change_address(wallet.address.clone())
+            .with_relative_lock(3)
+            .pay_to(
+                ReceiverInfo {
+                    address: wallet.address.clone(),
+                    asset: Asset::token_u64(50_000),
+                },
+                0,
+            )
+            .build()
+            .unwrap();
+
+        let unknown_height = mempool.add_transaction(tx.clone(), 8, |v| utxos.get(v), |_| None);
+        let too_early = mempool.add_transaction(tx.clone(), 7, |v| utxos.get(v), |_| Some(5));
+
+        assert!(unknown_height.is_err());
+        assert_eq!(too_early, Err("Relative locktime not expired".to_string()));
+        assert!(mempool
+            .add_transaction(tx, 8, |v| utxos.get(v), |_| Some(5))
+            .is_ok());
+    }
+
+    #[test]
+    /// Checks that transactions are validated for the pool's network
+    fn test_add_transaction_for_network() {
+        let wallet = TestWallet::new();
+        let utxos = wallet.utxos(&[100_000]);
+        let mut mainnet = Mempool::default();
+        let mut testnet = Mempool::default().with_network(Network::Testnet);
+
+        let tx = wallet.pay(utxo(&utxos, 0), 50_000, 1);
+
+        assert!(testnet
+            .add_transaction(tx.clone(), 0, |v| utxos.get(v), |_| None)
+            .is_err());
+        assert!(mainnet
+            .add_transaction(tx, 0, |v| utxos.get(v), |_| None)
+            .is_ok());
+    }
+}
@@ -0,0 +1,447 @@
+use crate::constants::*;
+use crate::crypto::sign_ed25519::PublicKey;
+use crate::script::lang::Script;
+use crate::utils::transaction_utils::{
+    construct_address, construct_address_temp, construct_address_v0, construct_p2sh_address,
+};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// Characters of the checksummed encoding, indexed by 5-bit value
+const CHECKSUM_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Generator of the checksummed encoding's BCH code
+const CHECKSUM_GENERATOR: [u32; 5] = [
+    0x3b6a_57b2,
+    0x2650_8e6d,
+    0x1ea1_19fa,
+    0x3d42_33dd,
+    0x2a14_62b3,
+];
+
+/// Number of 5-bit values in the checksum
+const CHECKSUM_LENGTH: usize = 6;
+
+/// An address that can be paid to, in one of the supported formats.
+/// Temporary addresses have the same hex form as standard ones, so are only told
+/// apart in the checksummed encoding.
+///
+/// `V0`        - Old (network version 0) address, a truncated hash of the public key
+/// `Standard`  - Hash of the public key
+/// `Temp`      - Hash of the public key under the temporary wallet scheme
+/// `P2SH`      - Hash of a script, of which only the first 63 hex digits are kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Address {
+    V0([u8; V0_ADDRESS_LENGTH]),
+    Standard([u8; 32]),
+    Temp([u8; 32]),
+    P2SH([u8; 32]),
+}
+
+impl Address {
+    /// Builds an address from a public key and a specified network version
+    ///
+    /// ### Arguments
+    ///
+    /// * `pub_key`         - A public key to build an address from
+    /// * `address_version` - Network version to use for the address
+    pub fn from_public_key(pub_key: &PublicKey, address_version: Option<u64>) -> Self {
+        match address_version {
+            Some(NETWORK_VERSION_V0) => Self::V0(decode_hash(&construct_address_v0(pub_key))),
+            Some(NETWORK_VERSION_TEMP) => Self::Temp(decode_hash(&construct_address_temp(pub_key))),
+            _ => Self::Standard(decode_hash(&construct_address(pub_key))),
+        }
+    }
+
+    /// Builds a P2SH address from a script
+    ///
+    /// ### Arguments
+    ///
+    /// * `script`  - Script to build the address for
+    pub fn from_script(script: &Script) -> Self {
+        let address = construct_p2sh_address(script);
+        Self::P2SH(decode_hash(&format!("{}0", &address[1..])))
+    }
+
+    /// Parses an address from its hex form, as built by `construct_address` and its
+    /// variants
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address to parse
+    pub fn parse(address: &str) -> Result<Self, String> {
+        if let Some(script_hash) = address.strip_prefix(P2SH_PREPEND as char) {
+            if script_hash.len() != STANDARD_ADDRESS_LENGTH - 1 {
+                return Err("P2SH address has invalid length".to_string());
+            }
+            let mut hash = [0; 32];
+            hex::decode_to_slice(format!("{script_hash}0"), &mut hash)
+                .map_err(|e| format!("Invalid P2SH address: {e}"))?;
+            return Ok(Self::P2SH(hash));
+        }
+
+        match address.len() {
+            STANDARD_ADDRESS_LENGTH => {
+                let mut hash = [0; 32];
+                hex::decode_to_slice(address, &mut hash)
+                    .map_err(|e| format!("Invalid address: {e}"))?;
+                Ok(Self::Standard(hash))
+            }
+            len if len == V0_ADDRESS_LENGTH * 2 => {
+                let mut hash = [0; V0_ADDRESS_LENGTH];
+                hex::decode_to_slice(address, &mut hash)
+                    .map_err(|e| format!("Invalid address: {e}"))?;
+                Ok(Self::V0(hash))
+            }
+            _ => Err("Address has invalid length".to_string()),
+        }
+    }
+
+    /// Gets the hash the address is made of
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::V0(hash) => hash,
+            Self::Standard(hash) | Self::Temp(hash) | Self::P2SH(hash) => hash,
+        }
+    }
+
+    /// Returns whether the address pays to a script hash
+    pub fn is_p2sh(&self) -> bool {
+        matches!(self, Self::P2SH(_))
+    }
+
+    /// Gets the version of the address in the checksummed encoding
+    fn get_version(&self) -> u8 {
+        match self {
+            Self::V0(_) => 0,
+            Self::Standard(_) => 1,
+            Self::Temp(_) => 2,
+            Self::P2SH(_) => 3,
+        }
+    }
+
+    /// Encodes the address in a bech32-style form, with a network prefix and a
+    /// checksum catching mistyped characters
+    ///
+    /// ### Arguments
+    ///
+    /// * `prefix`  - Human-readable prefix of the network
+    pub fn to_checksummed(&self, prefix: &str) -> String {
+        let mut data = vec![self.get_version()];
+        data.extend(convert_bits(self.as_bytes(), 8, 5, true).unwrap_or_default());
+        let checksum = create_checksum(prefix, &data);
+
+        let mut encoded = format!("{prefix}1");
+        encoded.extend(
+            data.iter()
+                .chain(checksum.iter())
+                .map(|v| CHECKSUM_CHARSET[*v as usize] as char),
+        );
+        encoded
+    }
+
+    /// Decodes an address from its checksummed form, checking the network prefix
+    ///
+    /// ### Arguments
+    ///
+    /// * `encoded` - Checksummed address
+    /// * `prefix`  - Human-readable prefix of the expected network
+    pub fn from_checksummed(encoded: &str, prefix: &str) -> Result<Self, String> {
+        let encoded = if encoded.chars().any(|c| c.is_ascii_uppercase()) {
+            if encoded.chars().any(|c| c.is_ascii_lowercase()) {
+                return Err("Checksummed address has mixed case".to_string());
+            }
+            encoded.to_ascii_lowercase()
+        } else {
+            encoded.to_string()
+        };
+
+        let (encoded_prefix, encoded_data) = encoded
+            .rsplit_once('1')
+            .ok_or_else(|| "Checksummed address has no separator".to_string())?;
+        if encoded_prefix != prefix {
+            return Err(format!(
+                "Address is for network {encoded_prefix}, expected {prefix}"
+            ));
+        }
+
+        let data = encoded_data
+            .bytes()
+            .map(|c| {
+                CHECKSUM_CHARSET
+                    .iter()
+                    .position(|v| *v == c)
+                    .map(|v| v as u8)
+            })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| "Checksummed address has invalid characters".to_string())?;
+        if data.len() <= CHECKSUM_LENGTH || !verify_checksum(prefix, &data) {
+            return Err("Checksummed address has invalid checksum".to_string());
+        }
+
+        let data = &data[..data.len() - CHECKSUM_LENGTH];
+        let hash = convert_bits(&data[1..], 5, 8, false)
+            .ok_or_else(|| "Checksummed address has invalid padding".to_string())?;
+
+        let address = match (data[0], hash.len()) {
+            (0, V0_ADDRESS_LENGTH) => Self::V0(to_array(&hash)),
+            (1, 32) => Self::Standard(to_array(&hash)),
+            (2, 32) => Self::Temp(to_array(&hash)),
+            (3, 32) if hash[31] & 0x0f == 0 => Self::P2SH(to_array(&hash)),
+            _ => return Err("Checksummed address has invalid version".to_string()),
+        };
+        Ok(address)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::P2SH(hash) => {
+                let mut address = hex::encode(hash);
+                address.truncate(STANDARD_ADDRESS_LENGTH - 1);
+                write!(f, "{}{}", P2SH_PREPEND as char, address)
+            }
+            _ => write!(f, "{}", hex::encode(self.as_bytes())),
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        Self::parse(address)
+    }
+}
+
+impl TryFrom<String> for Address {
+    type Error = String;
+
+    fn try_from(address: String) -> Result<Self, Self::Error> {
+        Self::parse(&address)
+    }
+}
+
+impl From<Address> for String {
+    fn from(address: Address) -> Self {
+        address.to_string()
+    }
+}
+
+/// Decodes the hex hash of an address built by `construct_address` or one of its
+/// variants, which is always valid hex of the right length
+///
+/// ### Arguments
+///
+/// * `address` - Hex address to decode
+fn decode_hash<const N: usize>(address: &str) -> [u8; N] {
+    let mut hash = [0; N];
+    hex::decode_to_slice(address, &mut hash).unwrap_or_default();
+    hash
+}
+
+/// Copies a slice of the right length into an array
+///
+/// ### Arguments
+///
+/// * `bytes`   - Bytes to copy
+fn to_array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    let mut array = [0; N];
+    array.copy_from_slice(bytes);
+    array
+}
+
+/// Regroups bits from values of `from` bits into values of `to` bits
+///
+/// ### Arguments
+///
+/// * `data`    - Values to regroup
+/// * `from`    - Number of bits in each input value
+/// * `to`      - Number of bits in each output value
+/// * `pad`     - Whether to pad the last output value with zeros
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut acc = 0u32;
+    let mut bits = 0u32;
+    let max_value = (1u32 << to) - 1;
+    let mut converted = Vec::new();
+
+    for value in data {
+        if u32::from(*value) >> from != 0 {
+            return None;
+        }
+        acc = (acc << from) | u32::from(*value);
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            converted.push(((acc >> bits) & max_value) as u8);
+        }
+    }
+
+    if pad {
+        if bits > 0 {
+            converted.push(((acc << (to - bits)) & max_value) as u8);
+        }
+    } else if bits >= from || (acc << (to - bits)) & max_value != 0 {
+        return None;
+    }
+    Some(converted)
+}
+
+/// Computes the BCH checksum polynomial over 5-bit values
+///
+/// ### Arguments
+///
+/// * `values`  - Values to compute the checksum of
+fn polymod(values: &[u8]) -> u32 {
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x01ff_ffff) << 5) ^ u32::from(*value);
+        for (i, generator) in CHECKSUM_GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+/// Expands a prefix into 5-bit values for the checksum
+///
+/// ### Arguments
+///
+/// * `prefix`  - Human-readable prefix
+fn expand_prefix(prefix: &str) -> Vec<u8> {
+    let mut expanded: Vec<u8> = prefix.bytes().map(|b| b >> 5).collect();
+    expanded.push(0);
+    expanded.extend(prefix.bytes().map(|b| b & 31));
+    expanded
+}
+
+/// Creates the checksum of a prefix and data
+///
+/// ### Arguments
+///
+/// * `prefix`  - Human-readable prefix
+/// * `data`    - 5-bit data values
+fn create_checksum(prefix: &str, data: &[u8]) -> Vec<u8> {
+    let mut values = expand_prefix(prefix);
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0; CHECKSUM_LENGTH]);
+
+    let checksum = polymod(&values) ^ 1;
+    (0..CHECKSUM_LENGTH)
+        .map(|i| ((checksum >> (5 * (CHECKSUM_LENGTH - 1 - i))) & 31) as u8)
+        .collect()
+}
+
+/// Checks the checksum at the end of data
+///
+/// ### Arguments
+///
+/// * `prefix`  - Human-readable prefix
+/// * `data`    - 5-bit data values, ending with the checksum
+fn verify_checksum(prefix: &str, data: &[u8]) -> bool {
+    let mut values = expand_prefix(prefix);
+    values.extend_from_slice(data);
+    polymod(&values) == 1
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::sign_ed25519 as sign;
+    use crate::utils::transaction_utils::{
+        construct_address, construct_address_temp, construct_address_v0, construct_p2sh_address,
+    };
+
+    #[test]
+    /// Checks that addresses match their string forms and parse back
+    fn test_address_matches_constructed_addresses() {
+        let (pk, _) = sign::gen_keypair();
+        let script = Script::new_for_coinbase(7);
+
+        let standard = Address::from_public_key(&pk, None);
+        let v0 = Address::from_public_key(&pk, Some(NETWORK_VERSION_V0));
+        let temp = Address::from_public_key(&pk, Some(NETWORK_VERSION_TEMP));
+        let p2sh = Address::from_script(&script);
+
+        assert_eq!(standard.to_string(), construct_address(&pk));
+        assert_eq!(v0.to_string(), construct_address_v0(&pk));
+        assert_eq!(temp.to_string(), construct_address_temp(&pk));
+        assert_eq!(p2sh.to_string(), construct_p2sh_address(&script));
+        assert_eq!(Address::parse(&standard.to_string()), Ok(standard));
+        assert_eq!(Address::parse(&v0.to_string()), Ok(v0));
+        assert_eq!(
+            Address::parse(&temp.to_string()),
+            Ok(Address::Standard(to_array(temp.as_bytes())))
+        );
+        assert_eq!(Address::parse(&p2sh.to_string()), Ok(p2sh));
+        assert!(p2sh.is_p2sh());
+    }
+
+    #[test]
+    /// Checks that malformed hex addresses are rejected
+    fn test_parse_invalid_address() {
+        assert!(Address::parse(&hex::encode([0; 31])).is_err());
+        assert!(Address::parse(&"z".repeat(STANDARD_ADDRESS_LENGTH)).is_err());
+        assert!(Address::parse(&format!("H{}", "0".repeat(STANDARD_ADDRESS_LENGTH))).is_err());
+        assert!(Address::parse("").is_err());
+    }
+
+    #[test]
+    /// Checks that checksummed addresses round trip and catch typos and wrong networks
+    fn test_checksummed_address() {
+        let (pk, _) = sign::gen_keypair();
+        let addresses = [
+            Address::from_public_key(&pk, None),
+            Address::from_public_key(&pk, Some(NETWORK_VERSION_V0)),
+            Address::from_public_key(&pk, Some(NETWORK_VERSION_TEMP)),
+            Address::from_script(&Script::new_for_coinbase(0)),
+        ];
+
+        for address in addresses {
+            let encoded = address.to_checksummed("tw");
+            let mut mistyped = encoded.clone().into_bytes();
+            let last = mistyped.len() - 1;
+            mistyped[last] = if mistyped[last] == b'q' { b'p' } else { b'q' };
+
+            assert!(encoded.starts_with("tw1"));
+            assert_eq!(Address::from_checksummed(&encoded, "tw"), Ok(address));
+            assert_eq!(
+                Address::from_checksummed(&encoded.to_ascii_uppercase(), "tw"),
+                Ok(address)
+            );
+            assert!(Address::from_checksummed(&encoded, "twt").is_err());
+            assert!(
+                Address::from_checksummed(&String::from_utf8(mistyped).unwrap(), "tw").is_err()
+            );
+        }
+    }
+
+    #[test]
+    /// Checks the checksum against a BIP-173 test vector and the serde form of addresses
+    fn test_checksummed_address_vector() {
+        let data: Vec<u8> = "qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw"
+            .bytes()
+            .map(|c| CHECKSUM_CHARSET.iter().position(|v| *v == c).unwrap() as u8)
+            .collect();
+        let address = Address::Standard([0; 32]);
+        let encoded = address.to_checksummed("tw");
+
+        assert!(verify_checksum("abcdef", &data));
+        assert!(!verify_checksum("abcdeg", &data));
+        assert_eq!(encoded.len(), "tw1".len() + 1 + 52 + CHECKSUM_LENGTH);
+        assert!(encoded.starts_with("tw1pqqqqqq"));
+        assert_eq!(
+            serde_json::to_string(&address).unwrap(),
+            format!("\"{}\"", "0".repeat(64))
+        );
+    }
+}
@@ -4,11 +4,13 @@ use crate::crypto::sha3_256::{self, Sha3_256};
 use crate::crypto::sign_ed25519::PublicKey;
 use crate::primitives::asset::Asset;
 use crate::primitives::transaction::{Transaction, TxIn, TxOut};
+use crate::utils::pow_utils::{hash_meets_target, target_from_compact};
+use crate::utils::transaction_utils::construct_tx_hash;
 use bincode::{deserialize, serialize};
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use std::convert::TryInto;
-use tracing::warn;
+use tracing::{error, warn};
 
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
@@ -17,7 +19,7 @@ use merkle_log::{MemoryStore, MerkleLog, Store};
 
 /// Block header, which contains a smaller footprint view of the block.
 /// Hash records are assumed to be 256 bit
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BlockHeader {
     pub version: u32,
     pub bits: usize,
@@ -56,10 +58,66 @@ impl BlockHeader {
     pub fn is_null(&self) -> bool {
         self.bits == 0
     }
+
+    /// Gets the hex encoded sha3 hash of the header's preimage. This links the next
+    /// block to this one and is checked against the proof-of-work target.
+    pub fn hash(&self) -> String {
+        hex::encode(sha3_256::digest(&self.preimage()))
+    }
+
+    /// Constructs the canonical binary preimage hashed for the header. All integers
+    /// are little-endian and byte strings are prefixed with their length as u32. The
+    /// layout is `version` (u32), `bits` (u64), nonce and mining tx hash, `b_num` (u64),
+    /// `timestamp` (i64), `difficulty`, `seed_value`, `previous_hash` as a u8 presence
+    /// flag followed by the hash if present, then the merkle root and txs hash.
+    pub fn preimage(&self) -> Vec<u8> {
+        let mut preimage = Vec::new();
+        let push_bytes = |preimage: &mut Vec<u8>, bytes: &[u8]| {
+            preimage.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            preimage.extend_from_slice(bytes);
+        };
+
+        preimage.extend_from_slice(&self.version.to_le_bytes());
+        preimage.extend_from_slice(&(self.bits as u64).to_le_bytes());
+        push_bytes(&mut preimage, &self.nonce_and_mining_tx_hash.0);
+        push_bytes(&mut preimage, self.nonce_and_mining_tx_hash.1.as_bytes());
+        preimage.extend_from_slice(&self.b_num.to_le_bytes());
+        preimage.extend_from_slice(&self.timestamp.to_le_bytes());
+        push_bytes(&mut preimage, &self.difficulty);
+        push_bytes(&mut preimage, &self.seed_value);
+        match &self.previous_hash {
+            Some(previous_hash) => {
+                preimage.push(1);
+                push_bytes(&mut preimage, previous_hash.as_bytes());
+            }
+            None => preimage.push(0),
+        }
+        push_bytes(&mut preimage, self.txs_merkle_root_and_hash.0.as_bytes());
+        push_bytes(&mut preimage, self.txs_merkle_root_and_hash.1.as_bytes());
+
+        preimage
+    }
+
+    /// Gets the proof-of-work target encoded in the header's difficulty
+    pub fn get_target(&self) -> Option<[u8; 32]> {
+        let compact: [u8; 4] = self.difficulty.as_slice().try_into().ok()?;
+        target_from_compact(u32::from_be_bytes(compact))
+    }
+
+    /// Checks whether the header's hash meets its proof-of-work target
+    pub fn meets_target(&self) -> bool {
+        match self.get_target() {
+            Some(target) => hash_meets_target(&self.hash(), &target),
+            None => {
+                warn!("Block header has an invalid difficulty");
+                false
+            }
+        }
+    }
 }
 
 /// A block, a collection of transactions for processing
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Block {
     pub header: BlockHeader,
     pub transactions: Vec<String>,
@@ -106,11 +164,153 @@ impl Block {
 
     /// Get the merkle root for the current set of transactions
     pub async fn set_txs_merkle_root_and_hash(&mut self) {
-        let merkle_root = build_hex_merkle_root(&self.transactions).await;
+        self.set_txs_merkle_root_and_hash_sync();
+    }
+
+    /// Get the merkle root for the current set of transactions, without needing an
+    /// async runtime
+    pub fn set_txs_merkle_root_and_hash_sync(&mut self) {
+        let merkle_root = build_hex_merkle_root_sync(&self.transactions);
         let txs_hash = build_hex_txs_hash(&self.transactions);
 
         self.header.txs_merkle_root_and_hash = (merkle_root, txs_hash);
     }
+
+    /// Builds a proof that a transaction is included in the block's merkle root.
+    /// Returns `None` if the transaction is not in the block.
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx_hash` - Hash of the transaction to prove
+    pub fn merkle_proof(&self, tx_hash: &str) -> Option<TxInclusionProof> {
+        let index = self.transactions.iter().position(|tx| tx == tx_hash)?;
+        let leaves: Vec<[u8; 32]> = self
+            .transactions
+            .iter()
+            .map(|tx| hash_merkle_leaf(tx))
+            .collect();
+
+        let mut path = Vec::new();
+        build_merkle_path(&leaves, index, &mut path);
+
+        Some(TxInclusionProof {
+            index: index as u64,
+            tx_count: leaves.len() as u64,
+            path: path.iter().map(hex::encode).collect(),
+        })
+    }
+}
+
+/// Proof that a transaction is included in a block, checked against the merkle
+/// root in the block's header
+///
+/// `index`     - Position of the transaction in the block
+/// `tx_count`  - Number of transactions in the block
+/// `path`      - Hex encoded sibling hashes from the transaction's leaf up to the root
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxInclusionProof {
+    pub index: u64,
+    pub tx_count: u64,
+    pub path: Vec<String>,
+}
+
+/// A block carrying its full transactions rather than their hashes, so that it can
+/// be shipped or stored as one unit
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FullBlock {
+    pub header: BlockHeader,
+    pub transactions: Vec<Transaction>,
+}
+
+impl FullBlock {
+    /// Creates a full block from a block and its transactions, checking that each
+    /// transaction hashes to the block's hash at the same position
+    ///
+    /// ### Arguments
+    ///
+    /// * `block`           - Block holding the transaction hashes
+    /// * `transactions`    - Transactions in the same order as the block's hashes
+    pub fn from_block(block: Block, transactions: Vec<Transaction>) -> Result<Self, String> {
+        if block.transactions.len() != transactions.len() {
+            return Err(format!(
+                "Block has {} transaction hashes but {} transactions were given",
+                block.transactions.len(),
+                transactions.len()
+            ));
+        }
+
+        let mismatch = block
+            .transactions
+            .iter()
+            .zip(transactions.iter())
+            .position(|(tx_hash, tx)| *tx_hash != construct_tx_hash(tx));
+        if let Some(index) = mismatch {
+            error!("TRANSACTION DOES NOT MATCH BLOCK HASH");
+            return Err(format!(
+                "Transaction {index} does not match its hash in the block"
+            ));
+        }
+
+        Ok(Self {
+            header: block.header,
+            transactions,
+        })
+    }
+
+    /// Gets the hashes of the block's transactions
+    pub fn get_tx_hashes(&self) -> Vec<String> {
+        self.transactions.iter().map(construct_tx_hash).collect()
+    }
+
+    /// Gets the block holding only the hashes of the transactions
+    pub fn to_block(&self) -> Block {
+        Block {
+            header: self.header.clone(),
+            transactions: self.get_tx_hashes(),
+        }
+    }
+
+    /// Checks that the header's merkle root and transactions hash commit to the
+    /// block's transactions
+    pub fn has_valid_txs_merkle_root_and_hash(&self) -> bool {
+        let tx_hashes = self.get_tx_hashes();
+        let expected = (
+            build_hex_merkle_root_sync(&tx_hashes),
+            build_hex_txs_hash(&tx_hashes),
+        );
+
+        if self.header.txs_merkle_root_and_hash != expected {
+            error!("BLOCK MERKLE ROOT DOES NOT MATCH TRANSACTIONS");
+            return false;
+        }
+        true
+    }
+
+    /// Encodes the block in its canonical binary form
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        serialize(self).map_err(|e| format!("Failed to serialize full block: {e:?}"))
+    }
+
+    /// Decodes a block from its canonical binary form, rejecting trailing bytes
+    ///
+    /// ### Arguments
+    ///
+    /// * `bytes`   - Encoded block
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let block: Self =
+            deserialize(bytes).map_err(|e| format!("Failed to deserialize full block: {e:?}"))?;
+
+        if block.encode()?.len() != bytes.len() {
+            return Err("Full block encoding has trailing bytes".to_string());
+        }
+        Ok(block)
+    }
+}
+
+impl From<FullBlock> for Block {
+    fn from(full_block: FullBlock) -> Self {
+        full_block.to_block()
+    }
 }
 
 /*---- FUNCTIONS ----*/
@@ -153,15 +353,83 @@ pub fn build_hex_txs_hash(transactions: &[String]) -> String {
     hex::encode(sha3_256::digest(&txs))
 }
 
+/// Verifies that a transaction is included under a merkle root, as found in a
+/// block header's `txs_merkle_root_and_hash`
+///
+/// ### Arguments
+///
+/// * `proof`       - Inclusion proof for the transaction
+/// * `tx_hash`     - Hash of the transaction
+/// * `merkle_root` - Hex encoded merkle root to check against
+pub fn verify_inclusion(proof: &TxInclusionProof, tx_hash: &str, merkle_root: &str) -> bool {
+    if proof.index >= proof.tx_count {
+        return false;
+    }
+
+    let mut index = proof.index;
+    let mut last_index = proof.tx_count - 1;
+    let mut node = hash_merkle_leaf(tx_hash);
+
+    for sibling in &proof.path {
+        let sibling: [u8; 32] = match hex::decode(sibling).map(|s| s.try_into()) {
+            Ok(Ok(sibling)) => sibling,
+            _ => return false,
+        };
+        if last_index == 0 {
+            return false;
+        }
+
+        if index & 1 == 1 || index == last_index {
+            node = hash_merkle_node(&sibling, &node);
+            // A last node without a sibling is carried up until it becomes a right child
+            while index & 1 == 0 && index != 0 {
+                index >>= 1;
+                last_index >>= 1;
+            }
+        } else {
+            node = hash_merkle_node(&node, &sibling);
+        }
+        index >>= 1;
+        last_index >>= 1;
+    }
+
+    last_index == 0 && hex::encode(node) == merkle_root
+}
+
 /// Builds hex encoded merkle root of the passed transactions
 ///
 /// ### Arguments
 ///
 /// * `transactions`    - Transactions to construct a merkle tree for
 pub async fn build_hex_merkle_root(transactions: &[String]) -> String {
-    let merkle_result = build_merkle_tree(transactions).await;
-    let merkle_root = merkle_result.map(|(t, _)| hex::encode(t.root()));
-    merkle_root.unwrap_or_default()
+    build_hex_merkle_root_sync(transactions)
+}
+
+/// Builds hex encoded merkle root of the passed transactions, without needing an
+/// async runtime. The root is empty if there are no transactions.
+///
+/// ### Arguments
+///
+/// * `transactions`    - Transactions to construct a merkle tree for
+pub fn build_hex_merkle_root_sync(transactions: &[String]) -> String {
+    build_merkle_root(transactions)
+        .map(hex::encode)
+        .unwrap_or_default()
+}
+
+/// Builds the merkle root of the passed transactions. This gives the same root as
+/// `build_merkle_tree` while only holding the leaf hashes in memory.
+///
+/// ### Arguments
+///
+/// * `transactions`    - Transactions to construct a merkle tree for
+pub fn build_merkle_root(transactions: &[String]) -> Option<[u8; 32]> {
+    if transactions.is_empty() {
+        return None;
+    }
+
+    let leaves: Vec<[u8; 32]> = transactions.iter().map(|tx| hash_merkle_leaf(tx)).collect();
+    Some(build_merkle_subtree_root(&leaves))
 }
 
 /// Builds a merkle tree of the passed transactions
@@ -199,11 +467,102 @@ pub async fn build_merkle_tree(
     None
 }
 
+/// Hashes a transaction hash into a merkle tree leaf
+///
+/// ### Arguments
+///
+/// * `tx_hash` - Hash of the transaction
+fn hash_merkle_leaf(tx_hash: &str) -> [u8; 32] {
+    from_slice(&sha3_256::digest(tx_hash.as_bytes()))
+}
+
+/// Hashes two child nodes into their parent merkle tree node
+///
+/// ### Arguments
+///
+/// * `left`    - Left child node
+/// * `right`   - Right child node
+fn hash_merkle_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    from_slice(&sha3_256::digest_all(
+        [&left[..], &right[..]].iter().copied(),
+    ))
+}
+
+/// Gets the merkle root of a non-empty set of leaves. The leaves are split at the
+/// largest power of two below their count, matching the shape of `MerkleLog`.
+///
+/// ### Arguments
+///
+/// * `leaves`  - Leaf hashes of the tree
+fn build_merkle_subtree_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    if leaves.len() == 1 {
+        return leaves[0];
+    }
+
+    let split = get_merkle_split(leaves.len());
+    hash_merkle_node(
+        &build_merkle_subtree_root(&leaves[..split]),
+        &build_merkle_subtree_root(&leaves[split..]),
+    )
+}
+
+/// Adds the sibling hashes proving a leaf to `path`, from the leaf up to the root
+///
+/// ### Arguments
+///
+/// * `leaves`  - Leaf hashes of the tree
+/// * `index`   - Index of the leaf to prove
+/// * `path`    - Sibling hashes found so far
+fn build_merkle_path(leaves: &[[u8; 32]], index: usize, path: &mut Vec<[u8; 32]>) {
+    if leaves.len() <= 1 {
+        return;
+    }
+
+    let split = get_merkle_split(leaves.len());
+    if index < split {
+        build_merkle_path(&leaves[..split], index, path);
+        path.push(build_merkle_subtree_root(&leaves[split..]));
+    } else {
+        build_merkle_path(&leaves[split..], index - split, path);
+        path.push(build_merkle_subtree_root(&leaves[..split]));
+    }
+}
+
+/// Gets the largest power of two strictly below a leaf count of at least two
+///
+/// ### Arguments
+///
+/// * `count`   - Number of leaves
+fn get_merkle_split(count: usize) -> usize {
+    1 << (usize::BITS - 1 - (count - 1).leading_zeros())
+}
+
 /*---- TESTS ----*/
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::primitives::asset::TokenAmount;
+    use crate::primitives::transaction::OutPoint;
+    use crate::script::lang::Script;
+    use crate::utils::transaction_utils::construct_tx_core;
+
+    #[test]
+    /// Ensures that the header hash is computed over its canonical preimage
+    fn should_construct_a_valid_header_hash() {
+        let mut header = BlockHeader::new();
+        header.b_num = 1;
+        header.previous_hash = Some("00".to_owned());
+
+        assert_eq!(
+            header.hash(),
+            hex::encode(sha3_256::digest(&header.preimage()))
+        );
+        assert_eq!(
+            header.hash(),
+            "387a7c02bbad66789e187bfeec48733a59d695203a08d3c111de2f0b9f92ade1"
+        );
+    }
 
     #[actix_rt::test]
     /// Ensures that the merkle root is set to a valid empty string when no tx's are present
@@ -266,4 +625,142 @@ mod tests {
 
         assert!(mtree.verify(0, &converted_entry, &proof));
     }
+
+    #[test]
+    /// Ensures that the synchronous merkle root matches the static vectors
+    fn should_construct_merkle_root_without_runtime() {
+        let mut block = Block::new();
+        block.transactions = vec![
+            "f479fc771c19c64b14b1b9e446ccccf36b6d705c891eb9a7662c82134e362732".to_string(),
+            "ac24e4c5dc8d0a29cac34ddcf7902bc2f2e8a98ec376def02c06db267d0f5477".to_string(),
+            "4d04366cb153bdcc11b97a9d1176fc889eafc63edbd2c010a6a62a4f9232d156".to_string(),
+            "6486b86af39db28e4f61c7b484e0869ad478e8cb2475b91e92d1b721b70d1746".to_string(),
+            "03b45b843d60b1e43241553c9aeb95fed82cc1bbb599c6c066ddaa75709b3186".to_string(),
+            "8d0250ea0864ac426fe4f4142dae721c74da732476de83d424e1ba0b638238a7".to_string(),
+            "f57e38fb8499b7c2b3d4cf75a24a5dd8a8f7b46f28b9671eb8168ffb93a85424".to_string(),
+            "e0acad209b680e61c3ef4624d9a61b32a5e7e3f0691a8f8d41fd50b1c946e338".to_string(),
+        ];
+        block.set_txs_merkle_root_and_hash_sync();
+
+        assert_eq!(
+            block.header.txs_merkle_root_and_hash,
+            (
+                "49adba4740eb78c38318bbe2951a3c49e8a5bda6b892870bdcbe0713cf1e0af2".to_owned(),
+                "2bf86b48530112f14cbc516f2f7085cdc886a88b475d52e9eaa8cef526479e0f".to_owned()
+            )
+        );
+        assert_eq!(build_hex_merkle_root_sync(&[]), String::new());
+    }
+
+    #[actix_rt::test]
+    /// Ensures that the synchronous merkle root matches `MerkleLog` for unbalanced trees
+    async fn should_match_merkle_log_root() {
+        let transactions: Vec<String> = (0..20).map(|_| gen_random_hash()).collect();
+
+        for tx_count in 1..transactions.len() {
+            let (mtree, _) = build_merkle_tree(&transactions[..tx_count]).await.unwrap();
+
+            assert_eq!(
+                build_merkle_root(&transactions[..tx_count]),
+                Some(*mtree.root())
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    /// Ensures that inclusion proofs for every tx verify against the block's merkle root
+    async fn should_verify_tx_inclusion_proofs() {
+        for tx_count in 1..10 {
+            let mut block = Block::new();
+            block.transactions = (0..tx_count).map(|_| gen_random_hash()).collect();
+            block.set_txs_merkle_root_and_hash().await;
+            let merkle_root = &block.header.txs_merkle_root_and_hash.0;
+
+            for tx_hash in &block.transactions {
+                let proof = block.merkle_proof(tx_hash).unwrap();
+                let json = serde_json::to_string(&proof).unwrap();
+                let proof: TxInclusionProof = serde_json::from_str(&json).unwrap();
+
+                assert!(verify_inclusion(&proof, tx_hash, merkle_root));
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    /// Ensures that inclusion proofs fail for the wrong tx, position or merkle root
+    async fn should_reject_invalid_tx_inclusion_proofs() {
+        let mut block = Block::new();
+        block.transactions = (0..5).map(|_| gen_random_hash()).collect();
+        block.set_txs_merkle_root_and_hash().await;
+        let merkle_root = block.header.txs_merkle_root_and_hash.0.clone();
+        let tx_hash = block.transactions[2].clone();
+        let proof = block.merkle_proof(&tx_hash).unwrap();
+
+        let mut wrong_index = proof.clone();
+        wrong_index.index = 3;
+        let mut short_path = proof.clone();
+        short_path.path.pop();
+
+        assert!(verify_inclusion(&proof, &tx_hash, &merkle_root));
+        assert!(!verify_inclusion(
+            &proof,
+            &block.transactions[1],
+            &merkle_root
+        ));
+        assert!(!verify_inclusion(&proof, &tx_hash, &gen_random_hash()));
+        assert!(!verify_inclusion(&wrong_index, &tx_hash, &merkle_root));
+        assert!(!verify_inclusion(&short_path, &tx_hash, &merkle_root));
+        assert!(block.merkle_proof(&gen_random_hash()).is_none());
+    }
+
+    /// Creates a set of distinct transactions for full block tests
+    fn test_transactions() -> Vec<Transaction> {
+        (0..3)
+            .map(|n| {
+                let tx_in =
+                    TxIn::new_from_input(OutPoint::new(gen_random_hash(), n), Script::new());
+                let tx_out = TxOut::new_token_amount(gen_random_hash(), TokenAmount(1), None);
+                construct_tx_core(vec![tx_in], vec![tx_out], None)
+            })
+            .collect()
+    }
+
+    #[test]
+    /// Ensures that a full block converts to and from a block and round trips its encoding
+    fn should_encode_and_convert_full_block() {
+        let transactions = test_transactions();
+        let mut block = Block::new();
+        block.transactions = transactions.iter().map(construct_tx_hash).collect();
+        block.set_txs_merkle_root_and_hash_sync();
+
+        let full_block = FullBlock::from_block(block.clone(), transactions).unwrap();
+        let mut bytes = full_block.encode().unwrap();
+
+        assert_eq!(full_block.to_block(), block);
+        assert!(full_block.has_valid_txs_merkle_root_and_hash());
+        assert_eq!(FullBlock::decode(&bytes), Ok(full_block.clone()));
+        assert_eq!(Block::from(full_block), block);
+
+        bytes.push(0);
+        assert!(FullBlock::decode(&bytes).is_err());
+        assert!(FullBlock::decode(&bytes[..10]).is_err());
+    }
+
+    #[test]
+    /// Ensures that a full block rejects transactions not matching the block's hashes
+    fn should_reject_inconsistent_full_block() {
+        let transactions = test_transactions();
+        let mut block = Block::new();
+        block.transactions = transactions.iter().map(construct_tx_hash).collect();
+        block.set_txs_merkle_root_and_hash_sync();
+
+        let mut reordered = transactions.clone();
+        reordered.swap(0, 1);
+        let mut stale_header = FullBlock::from_block(block.clone(), transactions.clone()).unwrap();
+        stale_header.transactions.pop();
+
+        assert!(FullBlock::from_block(block.clone(), reordered).is_err());
+        assert!(FullBlock::from_block(block, transactions[1..].to_vec()).is_err());
+        assert!(!stale_header.has_valid_txs_merkle_root_and_hash());
+    }
 }
@@ -1,4 +1,7 @@
+pub mod address;
 pub mod asset;
 pub mod block;
 pub mod druid;
+pub mod network;
+pub mod pstx;
 pub mod transaction;
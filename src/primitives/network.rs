@@ -0,0 +1,125 @@
+use crate::primitives::address::Address;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// A network transactions and addresses belong to. The network id is part of the
+/// signed preimage of every input, so a transaction signed for one network is
+/// never valid on another.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Testnet,
+    Devnet,
+}
+
+impl Network {
+    /// All known networks
+    pub const ALL: [Network; 3] = [Network::Mainnet, Network::Testnet, Network::Devnet];
+
+    /// Gets the id of the network, as committed to in signable preimages
+    pub fn id(&self) -> u32 {
+        match self {
+            Self::Mainnet => 0,
+            Self::Testnet => 1,
+            Self::Devnet => 2,
+        }
+    }
+
+    /// Gets the network with the given id
+    ///
+    /// ### Arguments
+    ///
+    /// * `id`  - Id of the network
+    pub fn from_id(id: u32) -> Option<Self> {
+        Self::ALL.iter().copied().find(|network| network.id() == id)
+    }
+
+    /// Gets the human-readable prefix of the network's checksummed addresses
+    pub fn address_prefix(&self) -> &'static str {
+        match self {
+            Self::Mainnet => "tw",
+            Self::Testnet => "twt",
+            Self::Devnet => "twd",
+        }
+    }
+
+    /// Encodes an address in the network's checksummed form
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address to encode
+    pub fn encode_address(&self, address: &Address) -> String {
+        address.to_checksummed(self.address_prefix())
+    }
+
+    /// Parses an address given either in hex or in checksummed form. Checksummed
+    /// addresses of other networks are rejected.
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address to parse
+    pub fn parse_address(&self, address: &str) -> Result<Address, String> {
+        if let Ok(address) = Address::parse(address) {
+            return Ok(address);
+        }
+
+        let prefix = address
+            .rsplit_once('1')
+            .map(|(prefix, _)| prefix.to_ascii_lowercase())
+            .unwrap_or_default();
+        if let Some(other) = Self::ALL
+            .iter()
+            .find(|n| *n != self && n.address_prefix() == prefix)
+        {
+            error!("ADDRESS IS FOR A FOREIGN NETWORK");
+            return Err(format!("Address is for {other:?}, not {self:?}"));
+        }
+
+        Address::from_checksummed(address, self.address_prefix())
+    }
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that addresses of other networks are rejected
+    fn test_parse_network_address() {
+        let address = Address::Standard([7; 32]);
+        let testnet_address = Network::Testnet.encode_address(&address);
+
+        assert_eq!(
+            Network::Testnet.parse_address(&testnet_address),
+            Ok(address)
+        );
+        assert_eq!(
+            Network::Mainnet.parse_address(&address.to_string()),
+            Ok(address)
+        );
+        assert!(Network::Mainnet.parse_address(&testnet_address).is_err());
+        assert!(Network::Devnet.parse_address(&testnet_address).is_err());
+        assert!(Network::Testnet
+            .parse_address(&Network::Mainnet.encode_address(&address))
+            .is_err());
+    }
+
+    #[test]
+    /// Checks that network ids and names round trip
+    fn test_network_ids() {
+        for network in Network::ALL {
+            assert_eq!(Network::from_id(network.id()), Some(network));
+        }
+        assert_eq!(Network::from_id(3), None);
+        assert_eq!(
+            serde_json::from_str::<Network>("\"testnet\"").unwrap(),
+            Network::Testnet
+        );
+    }
+}
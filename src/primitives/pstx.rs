@@ -0,0 +1,518 @@
+use crate::crypto::sign_ed25519::{self as sign, PublicKey, SecretKey, Signature};
+use crate::primitives::asset::AssetValues;
+use crate::primitives::network::Network;
+use crate::primitives::transaction::{OutPoint, Transaction, TxOut};
+use crate::script::lang::Script;
+use crate::utils::script_utils::tx_in_has_valid_script;
+use crate::utils::transaction_utils::{
+    construct_address_for, construct_tx_in_out_signable_hash_for_network,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use tracing::{debug, warn};
+
+/// A signature collected for a single input of a partially signed transaction
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartialSignature {
+    pub pub_key: PublicKey,
+    pub signature: Signature,
+    pub address_version: Option<u64>,
+}
+
+/// A partially signed transaction (PSTX), used to pass a transaction between
+/// signers which each hold the keys for only some of its inputs.
+///
+/// `unsigned_tx`       - Transaction being signed, with empty input scripts
+/// `spent_tx_outs`     - The `TxOut` spent by each input, in input order
+/// `signatures`        - Signatures collected so far, in input order
+/// `final_scripts`     - Completed input scripts, in input order
+/// `network`           - Network the transaction is signed for
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "PstxFields")]
+pub struct PartiallySignedTransaction {
+    pub unsigned_tx: Transaction,
+    pub spent_tx_outs: Vec<TxOut>,
+    pub signatures: Vec<Option<PartialSignature>>,
+    pub final_scripts: Vec<Option<Script>>,
+    pub network: Network,
+}
+
+/// Deserialized fields of a PSTX, checked before being accepted
+#[derive(Deserialize)]
+struct PstxFields {
+    unsigned_tx: Transaction,
+    spent_tx_outs: Vec<TxOut>,
+    signatures: Vec<Option<PartialSignature>>,
+    final_scripts: Vec<Option<Script>>,
+    network: Network,
+}
+
+impl TryFrom<PstxFields> for PartiallySignedTransaction {
+    type Error = String;
+
+    fn try_from(fields: PstxFields) -> Result<Self, Self::Error> {
+        let pstx = Self {
+            unsigned_tx: fields.unsigned_tx,
+            spent_tx_outs: fields.spent_tx_outs,
+            signatures: fields.signatures,
+            final_scripts: fields.final_scripts,
+            network: fields.network,
+        };
+        pstx.check_lengths()?;
+        Ok(pstx)
+    }
+}
+
+impl PartiallySignedTransaction {
+    /// Creates a new PSTX from a transaction and the outputs its inputs spend.
+    /// Any scripts already present on the inputs are removed.
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx`              - Transaction to be signed
+    /// * `spent_tx_outs`   - The `TxOut` spent by each input, in input order
+    pub fn new(mut tx: Transaction, spent_tx_outs: Vec<TxOut>) -> Result<Self, String> {
+        if tx.inputs.len() != spent_tx_outs.len() {
+            return Err("Each input must have a corresponding spent output".to_string());
+        }
+        if tx.inputs.iter().any(|tx_in| tx_in.previous_out.is_none()) {
+            return Err("Every input must spend a previous outpoint".to_string());
+        }
+
+        tx.inputs
+            .iter_mut()
+            .for_each(|tx_in| tx_in.script_signature = Script::new());
+
+        let input_count = tx.inputs.len();
+        Ok(Self {
+            unsigned_tx: tx,
+            spent_tx_outs,
+            signatures: vec![None; input_count],
+            final_scripts: vec![None; input_count],
+            network: Network::Mainnet,
+        })
+    }
+
+    /// Checks that there is a spent output, signature and final script entry for
+    /// every input
+    pub fn check_lengths(&self) -> Result<(), String> {
+        let input_count = self.unsigned_tx.inputs.len();
+        if self.spent_tx_outs.len() != input_count
+            || self.signatures.len() != input_count
+            || self.final_scripts.len() != input_count
+        {
+            warn!("PSTX entries do not match its {} inputs", input_count);
+            return Err("PSTX must have one entry per input".to_string());
+        }
+        Ok(())
+    }
+
+    /// Sets the network the transaction is signed for. Defaults to mainnet
+    ///
+    /// ### Arguments
+    ///
+    /// * `network` - Network the transaction is signed for
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Gets the hash to be signed for the input at `index`
+    ///
+    /// ### Arguments
+    ///
+    /// * `index`   - Index of the input
+    pub fn signable_hash(&self, index: usize) -> Option<String> {
+        let tx_in = self.unsigned_tx.inputs.get(index)?;
+        Some(construct_tx_in_out_signable_hash_for_network(
+            tx_in,
+            &self.unsigned_tx,
+            self.network,
+        ))
+    }
+
+    /// Gets the total value of the outputs spent by this transaction
+    pub fn get_tx_ins_spent(&self) -> AssetValues {
+        let mut tx_ins_spent = AssetValues::default();
+        for (tx_in, tx_out) in self.unsigned_tx.inputs.iter().zip(&self.spent_tx_outs) {
+            match &tx_in.previous_out {
+                Some(out_point) => {
+                    tx_ins_spent.update_add(&tx_out.value.clone().with_fixed_hash(out_point))
+                }
+                None => tx_ins_spent.update_add(&tx_out.value),
+            }
+        }
+        tx_ins_spent
+    }
+
+    /// Signs every input for which key material is provided. Keys are only used
+    /// for inputs whose spent output is addressed to them. Returns the number of
+    /// inputs signed.
+    ///
+    /// ### Arguments
+    ///
+    /// * `key_material`    - Keys for the inputs this signer controls
+    pub fn sign(&mut self, key_material: &BTreeMap<OutPoint, (PublicKey, SecretKey)>) -> usize {
+        let to_sign: Vec<(usize, PublicKey, SecretKey)> = self
+            .unsigned_tx
+            .inputs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, tx_in)| {
+                let (pk, sk) = key_material.get(tx_in.previous_out.as_ref()?)?;
+                Some((index, *pk, sk.clone()))
+            })
+            .collect();
+
+        to_sign
+            .into_iter()
+            .filter(|(index, pk, sk)| self.sign_input(*index, pk, sk, None).is_ok())
+            .count()
+    }
+
+    /// Signs a single input
+    ///
+    /// ### Arguments
+    ///
+    /// * `index`           - Index of the input to sign
+    /// * `pub_key`         - Public key of the spent output
+    /// * `secret_key`      - Corresponding secret key
+    /// * `address_version` - Address version of the spent output
+    pub fn sign_input(
+        &mut self,
+        index: usize,
+        pub_key: &PublicKey,
+        secret_key: &SecretKey,
+        address_version: Option<u64>,
+    ) -> Result<(), String> {
+        let hash = self
+            .signable_hash(index)
+            .ok_or_else(|| format!("Input {index} does not exist"))?;
+        let signature = sign::sign_detached(hash.as_bytes(), secret_key);
+
+        self.add_signature(
+            index,
+            PartialSignature {
+                pub_key: *pub_key,
+                signature,
+                address_version,
+            },
+        )
+    }
+
+    /// Adds a signature produced elsewhere, such as on a hardware wallet, after
+    /// checking it against the input's signable hash and spent output address
+    ///
+    /// ### Arguments
+    ///
+    /// * `index`       - Index of the input the signature is for
+    /// * `signature`   - Signature to add
+    pub fn add_signature(
+        &mut self,
+        index: usize,
+        signature: PartialSignature,
+    ) -> Result<(), String> {
+        let hash = self
+            .signable_hash(index)
+            .ok_or_else(|| format!("Input {index} does not exist"))?;
+
+        let address = construct_address_for(&signature.pub_key, signature.address_version);
+        let spent_tx_out = self
+            .spent_tx_outs
+            .get(index)
+            .ok_or_else(|| format!("Input {index} has no spent output"))?;
+        if spent_tx_out.script_public_key.as_ref() != Some(&address) {
+            warn!("Public key does not match the address of input {}", index);
+            return Err(format!("Public key does not own input {index}"));
+        }
+
+        if !sign::verify_detached(&signature.signature, hash.as_bytes(), &signature.pub_key) {
+            warn!("Invalid signature provided for input {}", index);
+            return Err(format!("Invalid signature for input {index}"));
+        }
+
+        let entry = self
+            .signatures
+            .get_mut(index)
+            .ok_or_else(|| format!("Input {index} has no signature entry"))?;
+        *entry = Some(signature);
+        Ok(())
+    }
+
+    /// Sets the completed script for an input directly, for inputs such as P2SH
+    /// redemptions which are not a single P2PKH signature. The script must validly
+    /// spend the input's spent output.
+    ///
+    /// ### Arguments
+    ///
+    /// * `index`   - Index of the input
+    /// * `script`  - Completed input script
+    pub fn set_final_script(&mut self, index: usize, script: Script) -> Result<(), String> {
+        let hash = self
+            .signable_hash(index)
+            .ok_or_else(|| format!("Input {index} does not exist"))?;
+        let address = self
+            .spent_tx_outs
+            .get(index)
+            .and_then(|tx_out| tx_out.script_public_key.as_ref())
+            .ok_or_else(|| format!("Input {index} has no spent output address"))?;
+
+        if !tx_in_has_valid_script(&script, &hash, address) {
+            warn!("Invalid final script provided for input {}", index);
+            return Err(format!("Invalid final script for input {index}"));
+        }
+
+        let final_script = self
+            .final_scripts
+            .get_mut(index)
+            .ok_or_else(|| format!("Input {index} does not exist"))?;
+        *final_script = Some(script);
+        Ok(())
+    }
+
+    /// Merges the signatures collected by another signer of the same transaction
+    ///
+    /// ### Arguments
+    ///
+    /// * `other`   - PSTX signed by another party
+    pub fn combine(&mut self, other: &Self) -> Result<(), String> {
+        if self.unsigned_tx != other.unsigned_tx
+            || self.spent_tx_outs != other.spent_tx_outs
+            || self.network != other.network
+        {
+            return Err("Cannot combine PSTXs for different transactions".to_string());
+        }
+        self.check_lengths()?;
+        other.check_lengths()?;
+
+        for (index, signature) in other.signatures.iter().enumerate() {
+            if let Some(signature) = signature {
+                if self.signatures.get(index) == Some(&None) {
+                    self.add_signature(index, signature.clone())?;
+                }
+            }
+        }
+
+        for (index, script) in other.final_scripts.iter().enumerate() {
+            if let Some(script) = script {
+                if self.final_scripts.get(index) == Some(&None) {
+                    self.set_final_script(index, script.clone())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether every input has a signature or completed script
+    pub fn is_complete(&self) -> bool {
+        self.check_lengths().is_ok()
+            && self
+                .signatures
+                .iter()
+                .zip(&self.final_scripts)
+                .all(|(signature, script)| signature.is_some() || script.is_some())
+    }
+
+    /// Builds the completed script for every signed input
+    pub fn finalize(&mut self) -> Result<(), String> {
+        self.check_lengths()?;
+
+        for index in 0..self.unsigned_tx.inputs.len() {
+            if self.final_scripts[index].is_some() {
+                continue;
+            }
+
+            let signature = self.signatures[index]
+                .as_ref()
+                .ok_or_else(|| format!("Input {index} is not signed"))?;
+            let hash = self
+                .signable_hash(index)
+                .ok_or_else(|| format!("Input {index} does not exist"))?;
+
+            self.final_scripts[index] = Some(Script::pay2pkh(
+                hash,
+                signature.signature,
+                signature.pub_key,
+                signature.address_version,
+            ));
+        }
+
+        debug!("Finalized PSTX with {} inputs", self.final_scripts.len());
+        Ok(())
+    }
+
+    /// Extracts the fully signed transaction from a finalized PSTX
+    pub fn extract(&self) -> Result<Transaction, String> {
+        self.check_lengths()?;
+        let mut tx = self.unsigned_tx.clone();
+
+        for (tx_in, script) in tx.inputs.iter_mut().zip(&self.final_scripts) {
+            tx_in.script_signature = script
+                .clone()
+                .ok_or_else(|| "PSTX has not been finalized".to_string())?;
+        }
+
+        Ok(tx)
+    }
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::asset::{Asset, TokenAmount};
+    use crate::primitives::transaction::TxIn;
+    use crate::utils::script_utils::tx_is_valid;
+    use crate::utils::transaction_utils::{construct_address, construct_tx_core};
+
+    type KeyMaterial = BTreeMap<OutPoint, (PublicKey, SecretKey)>;
+
+    /// Creates a PSTX spending one output from each of two signers
+    fn test_pstx() -> (
+        PartiallySignedTransaction,
+        BTreeMap<OutPoint, TxOut>,
+        KeyMaterial,
+        KeyMaterial,
+    ) {
+        let mut utxo_set = BTreeMap::new();
+        let mut key_materials = vec![BTreeMap::new(), BTreeMap::new()];
+        let mut tx_ins = Vec::new();
+
+        for (n, key_material) in key_materials.iter_mut().enumerate() {
+            let (pk, sk) = sign::gen_keypair();
+            let out_point = OutPoint::new("tx_hash".to_owned(), n as i32);
+            let tx_out = TxOut::new_token_amount(construct_address(&pk), TokenAmount(10), None);
+
+            utxo_set.insert(out_point.clone(), tx_out);
+            key_material.insert(out_point.clone(), (pk, sk));
+            tx_ins.push(TxIn::new_from_input(out_point, Script::new()));
+        }
+
+        let tx_outs = vec![TxOut::new_token_amount(
+            hex::encode(vec![0; 32]),
+            TokenAmount(20),
+            None,
+        )];
+        let tx = construct_tx_core(tx_ins, tx_outs, None);
+        let spent = utxo_set.values().cloned().collect();
+        let pstx = PartiallySignedTransaction::new(tx, spent).unwrap();
+        let second = key_materials.pop().unwrap();
+        let first = key_materials.pop().unwrap();
+
+        (pstx, utxo_set, first, second)
+    }
+
+    #[test]
+    /// Checks that two signers can each sign their own input and combine the result
+    fn test_pstx_multi_party_signing() {
+        let (pstx, utxo_set, first_keys, second_keys) = test_pstx();
+
+        let mut first = pstx.clone();
+        let mut second = pstx;
+        assert_eq!(first.sign(&first_keys), 1);
+        assert_eq!(second.sign(&second_keys), 1);
+        assert!(!first.is_complete());
+        assert!(first.extract().is_err());
+
+        first.combine(&second).unwrap();
+        first.finalize().unwrap();
+        let tx = first.extract().unwrap();
+
+        assert!(first.is_complete());
+        assert_eq!(first.get_tx_ins_spent(), AssetValues::token_u64(20));
+        assert!(tx_is_valid(&tx, 0, |v| utxo_set.get(v)).0);
+    }
+
+    #[test]
+    /// Checks that keys are not used for inputs they do not own
+    fn test_pstx_rejects_foreign_keys() {
+        let (mut pstx, _, first_keys, second_keys) = test_pstx();
+        let (first_pk, first_sk) = first_keys.values().next().unwrap().clone();
+        let foreign_keys = second_keys
+            .keys()
+            .map(|out_point| (out_point.clone(), (first_pk, first_sk.clone())))
+            .collect();
+
+        assert_eq!(pstx.sign(&foreign_keys), 0);
+        assert!(pstx.sign_input(1, &first_pk, &first_sk, None).is_err());
+        assert!(pstx.finalize().is_err());
+    }
+
+    #[test]
+    /// Checks that PSTXs survive serialization and only combine with the same transaction
+    fn test_pstx_serialization_and_combine_mismatch() {
+        let (mut pstx, _, first_keys, _) = test_pstx();
+        pstx.sign(&first_keys);
+
+        let bytes = bincode::serialize(&pstx).unwrap();
+        let mut other: PartiallySignedTransaction = bincode::deserialize(&bytes).unwrap();
+
+        assert_eq!(other, pstx);
+        assert!(pstx
+            .combine(&other.clone().with_network(Network::Testnet))
+            .is_err());
+
+        other.unsigned_tx.outputs[0].value = Asset::token_u64(19);
+        assert!(pstx.combine(&other).is_err());
+    }
+
+    #[test]
+    /// Checks that PSTXs without an entry for every input are rejected instead of
+    /// being indexed out of bounds
+    fn test_pstx_rejects_mismatched_lengths() {
+        let (mut pstx, _, first_keys, _) = test_pstx();
+        let mut short = pstx.clone();
+        short.sign(&first_keys);
+        short.signatures.pop();
+        let mut no_scripts = pstx.clone();
+        no_scripts.final_scripts.clear();
+
+        let bytes = bincode::serialize(&short).unwrap();
+        let deserialized: Result<PartiallySignedTransaction, _> = bincode::deserialize(&bytes);
+
+        assert!(deserialized.is_err());
+        assert!(pstx.combine(&short).is_err());
+        assert!(pstx.combine(&no_scripts).is_err());
+        assert!(!no_scripts.is_complete());
+        assert!(no_scripts.finalize().is_err());
+        assert!(no_scripts.extract().is_err());
+    }
+
+    #[test]
+    /// Checks that final scripts are verified before being accepted directly or
+    /// through combining
+    fn test_pstx_verifies_final_scripts() {
+        let (pstx, utxo_set, first_keys, second_keys) = test_pstx();
+        let mut first = pstx.clone();
+        first.sign(&first_keys);
+        let mut second = pstx.clone();
+        second.sign(&second_keys);
+        let signature = second.signatures[1].clone().unwrap();
+        let signed_script = Script::pay2pkh(
+            second.signable_hash(1).unwrap(),
+            signature.signature,
+            signature.pub_key,
+            None,
+        );
+        second.signatures[1] = None;
+        second.set_final_script(1, signed_script.clone()).unwrap();
+
+        let mut forged = pstx.clone();
+        forged.final_scripts[0] = Some(signed_script.clone());
+        let mut unchecked = pstx;
+
+        assert!(unchecked
+            .set_final_script(0, signed_script.clone())
+            .is_err());
+        assert!(unchecked.set_final_script(1, Script::new()).is_err());
+        assert!(first.combine(&forged).is_err());
+        assert!(unchecked.set_final_script(1, signed_script).is_ok());
+
+        first.combine(&second).unwrap();
+        first.finalize().unwrap();
+        assert!(tx_is_valid(&first.extract().unwrap(), 0, |v| utxo_set.get(v)).0);
+    }
+}
@@ -38,7 +38,8 @@ pub struct TxConstructor {
     pub address_version: Option<u64>,
 }
 
-/// An outpoint - a combination of a transaction hash and an index n into its vout
+/// An outpoint - a combination of a transaction hash and an index n into its vout.
+/// The hash is the transaction id, which does not commit to input signatures.
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct OutPoint {
     pub t_hash: String,
@@ -66,11 +67,13 @@ impl Default for OutPoint {
 
 /// An input of a transaction. It contains the location of the previous
 /// transaction's output that it claims and a signature that matches the
-/// output's public key.
+/// output's public key. A non-zero `sequence` is a relative lock: the number
+/// of blocks that must pass after the claimed output was included.
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct TxIn {
     pub previous_out: Option<OutPoint>,
     pub script_signature: Script,
+    pub sequence: u64,
 }
 
 impl Default for TxIn {
@@ -88,6 +91,7 @@ impl TxIn {
         TxIn {
             previous_out: None,
             script_signature: script_sig,
+            sequence: 0,
         }
     }
 
@@ -100,6 +104,7 @@ impl TxIn {
         TxIn {
             previous_out: None,
             script_signature: script_sig,
+            sequence: 0,
         }
     }
 
@@ -113,8 +118,19 @@ impl TxIn {
         TxIn {
             previous_out: Some(previous_out),
             script_signature: script_sig,
+            sequence: 0,
         }
     }
+
+    /// Sets the relative lock of this input, in blocks
+    ///
+    /// ### Arguments
+    ///
+    /// * `sequence`    - Blocks that must pass after the claimed output was included
+    pub fn with_sequence(mut self, sequence: u64) -> TxIn {
+        self.sequence = sequence;
+        self
+    }
 }
 
 /// An output of a transaction. It contains the public key that the next input
@@ -177,7 +193,8 @@ impl TxOut {
 }
 
 /// The basic transaction that is broadcasted on the network and contained in
-/// blocks. A transaction can contain multiple inputs and outputs.
+/// blocks. A transaction can contain multiple inputs and outputs. A non-zero
+/// `lock_height` is the first block number the transaction may be included in.
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Transaction {
     pub inputs: Vec<TxIn>,
@@ -185,6 +202,7 @@ pub struct Transaction {
     pub version: usize,
     pub fees: Vec<TxOut>,
     pub druid_info: Option<DdeValues>,
+    pub lock_height: u64,
 }
 
 impl Default for Transaction {
@@ -202,6 +220,7 @@ impl Transaction {
             fees: Vec::new(),
             version: NETWORK_VERSION as usize,
             druid_info: None,
+            lock_height: 0,
         }
     }
 
@@ -214,6 +233,18 @@ impl Transaction {
         bytes.len()
     }
 
+    /// Gets a copy of this transaction with the signatures of spending inputs removed.
+    /// Scripts of inputs without a previous output are kept, as they carry the
+    /// block number of coinbase and create transactions.
+    pub fn without_witness(&self) -> Transaction {
+        let mut tx = self.clone();
+        tx.inputs
+            .iter_mut()
+            .filter(|tx_in| tx_in.previous_out.is_some())
+            .for_each(|tx_in| tx_in.script_signature = Script::new());
+        tx
+    }
+
     /// Gets the create asset assigned to this transaction, if it exists
     fn get_create_asset(&self) -> Option<&Asset> {
         let is_create = self.inputs.len() == 1
@@ -0,0 +1,365 @@
+use crate::constants::{BLOCK_HASH_LENGTH, MAX_BLOCK_SIZE, NETWORK_VERSION};
+use crate::primitives::block::{Block, BlockHeader};
+use crate::primitives::transaction::{OutPoint, Transaction};
+use crate::script::lang::Script;
+use crate::utils::transaction_utils::construct_tx_hash;
+use bincode::{serialize, serialized_size};
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::debug;
+
+/// A block ready for proof-of-work, with the transactions it contains
+///
+/// `block`         - Block with its header filled in, apart from the nonce
+/// `coinbase`      - Coinbase transaction paying the miner
+/// `transactions`  - Transactions in the block, in the order of their hashes
+#[derive(Clone, Debug)]
+pub struct BlockTemplate {
+    pub block: Block,
+    pub coinbase: Transaction,
+    pub transactions: Vec<Transaction>,
+}
+
+/// Assembles a block from a coinbase and candidate transactions. Candidates are
+/// packed in the order given, after any candidates whose outputs they spend, until
+/// the block reaches its maximum size.
+#[derive(Debug, Clone)]
+pub struct BlockTemplateBuilder {
+    previous_header: Option<BlockHeader>,
+    coinbase: Option<Transaction>,
+    candidates: Vec<Transaction>,
+    timestamp: i64,
+    difficulty: Vec<u8>,
+    max_block_size: usize,
+}
+
+impl Default for BlockTemplateBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockTemplateBuilder {
+    /// Creates a new builder for a genesis block
+    pub fn new() -> Self {
+        Self {
+            previous_header: None,
+            coinbase: None,
+            candidates: Vec::new(),
+            timestamp: 0,
+            difficulty: Vec::new(),
+            max_block_size: MAX_BLOCK_SIZE,
+        }
+    }
+
+    /// Sets the header of the block being built on
+    ///
+    /// ### Arguments
+    ///
+    /// * `previous_header` - Header of the previous block
+    pub fn with_previous_header(mut self, previous_header: BlockHeader) -> Self {
+        self.previous_header = Some(previous_header);
+        self
+    }
+
+    /// Sets the coinbase transaction paying the miner
+    ///
+    /// ### Arguments
+    ///
+    /// * `coinbase`    - Coinbase transaction for the block's number
+    pub fn with_coinbase(mut self, coinbase: Transaction) -> Self {
+        self.coinbase = Some(coinbase);
+        self
+    }
+
+    /// Adds candidate transactions, in order of priority
+    ///
+    /// ### Arguments
+    ///
+    /// * `candidates`  - Validated transactions to include if space allows
+    pub fn with_transactions(mut self, candidates: impl IntoIterator<Item = Transaction>) -> Self {
+        self.candidates.extend(candidates);
+        self
+    }
+
+    /// Sets the block timestamp
+    ///
+    /// ### Arguments
+    ///
+    /// * `timestamp`   - Timestamp of the block
+    pub fn with_timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Sets the difficulty the block must be mined at
+    ///
+    /// ### Arguments
+    ///
+    /// * `difficulty`  - Required difficulty
+    pub fn with_difficulty(mut self, difficulty: Vec<u8>) -> Self {
+        self.difficulty = difficulty;
+        self
+    }
+
+    /// Sets the maximum serialized size of the block
+    ///
+    /// ### Arguments
+    ///
+    /// * `max_block_size`  - Maximum block size in bytes
+    pub fn with_max_block_size(mut self, max_block_size: usize) -> Self {
+        self.max_block_size = max_block_size;
+        self
+    }
+
+    /// Packs the transactions and builds the block header
+    pub fn build(self) -> Result<BlockTemplate, String> {
+        let b_num = match &self.previous_header {
+            Some(previous_header) => previous_header.b_num + 1,
+            None => 0,
+        };
+
+        let coinbase = self
+            .coinbase
+            .clone()
+            .ok_or_else(|| "No coinbase transaction provided".to_string())?;
+        if !coinbase.is_coinbase()
+            || coinbase.inputs[0].script_signature != Script::new_for_coinbase(b_num)
+        {
+            return Err(format!("Invalid coinbase transaction for block {b_num}"));
+        }
+
+        let mut block = Block::new();
+        block.header.version = NETWORK_VERSION;
+        block.header.b_num = b_num;
+        block.header.timestamp = self.timestamp;
+        block.header.difficulty = self.difficulty.clone();
+        block.header.previous_hash = self.previous_header.as_ref().map(|h| h.hash());
+        block.header.nonce_and_mining_tx_hash = (Vec::new(), construct_tx_hash(&coinbase));
+
+        // Placeholders of full length so that size checks include the merkle root and hash
+        let placeholder_hash = "0".repeat(BLOCK_HASH_LENGTH);
+        block.header.txs_merkle_root_and_hash = (placeholder_hash.clone(), placeholder_hash);
+
+        if get_block_size(&block) > self.max_block_size {
+            return Err("Block header exceeds the maximum block size".to_string());
+        }
+
+        let transactions = self.pack_transactions(&mut block);
+        debug!(
+            "Packed {} of {} candidate transactions into block {}",
+            transactions.len(),
+            self.candidates.len(),
+            b_num
+        );
+
+        block.set_txs_merkle_root_and_hash_sync();
+        block.set_bits();
+
+        Ok(BlockTemplate {
+            block,
+            coinbase,
+            transactions,
+        })
+    }
+
+    /// Adds candidate hashes to the block in dependency order while it remains
+    /// within the maximum size, returning the transactions added
+    ///
+    /// ### Arguments
+    ///
+    /// * `block`   - Block to add transaction hashes to
+    fn pack_transactions(&self, block: &mut Block) -> Vec<Transaction> {
+        let candidates: Vec<(String, &Transaction)> = self
+            .candidates
+            .iter()
+            .map(|tx| (construct_tx_hash(tx), tx))
+            .collect();
+
+        let mut block_size = get_block_size(block);
+        let selected = select_in_dependency_order(&candidates, |tx_hash, _| {
+            let hash_size = serialized_size(tx_hash).unwrap_or_default() as usize;
+            if block_size + hash_size > self.max_block_size {
+                return false;
+            }
+            block_size += hash_size;
+            true
+        });
+
+        selected
+            .into_iter()
+            .map(|(tx_hash, tx)| {
+                block.transactions.push(tx_hash);
+                tx.clone()
+            })
+            .collect()
+    }
+}
+
+/// Selects transactions in priority order, placing each after any candidates whose
+/// outputs it spends. A transaction is skipped if it spends an `OutPoint` already spent
+/// by a selected transaction or if `fits` rejects it, in which case nothing spending
+/// its outputs is selected.
+///
+/// ### Arguments
+///
+/// * `candidates`  - Transactions with their hashes, in order of priority
+/// * `fits`        - Called before selecting a transaction, returning whether it fits
+pub fn select_in_dependency_order<'a>(
+    candidates: &[(String, &'a Transaction)],
+    mut fits: impl FnMut(&str, &Transaction) -> bool,
+) -> Vec<(String, &'a Transaction)> {
+    let candidate_hashes: BTreeSet<&str> = candidates.iter().map(|(h, _)| h.as_str()).collect();
+    let mut included: BTreeSet<&str> = BTreeSet::new();
+    let mut spent: BTreeSet<&OutPoint> = BTreeSet::new();
+    let mut waiting: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    let mut selected = Vec::new();
+
+    for index in 0..candidates.len() {
+        let mut to_check = vec![index];
+
+        while let Some(index) = to_check.pop() {
+            let (tx_hash, tx) = &candidates[index];
+            if included.contains(tx_hash.as_str()) {
+                continue;
+            }
+
+            let previous_outs: Vec<&OutPoint> = tx
+                .inputs
+                .iter()
+                .filter_map(|tx_in| tx_in.previous_out.as_ref())
+                .collect();
+
+            // Wait for an unselected parent, to be checked again once it is selected
+            let missing_parent = previous_outs
+                .iter()
+                .map(|out_point| out_point.t_hash.as_str())
+                .find(|hash| candidate_hashes.contains(hash) && !included.contains(hash));
+            if let Some(parent) = missing_parent {
+                waiting.entry(parent).or_default().push(index);
+                continue;
+            }
+
+            if previous_outs.iter().any(|o| spent.contains(o)) || !fits(tx_hash, tx) {
+                continue;
+            }
+
+            spent.extend(previous_outs);
+            included.insert(tx_hash);
+            selected.push((tx_hash.clone(), *tx));
+            if let Some(children) = waiting.remove(tx_hash.as_str()) {
+                to_check.extend(children.into_iter().rev());
+            }
+        }
+    }
+
+    selected
+}
+
+/// Gets the serialized size of a block in bytes
+///
+/// ### Arguments
+///
+/// * `block`   - Block to size
+fn get_block_size(block: &Block) -> usize {
+    serialize(block)
+        .map(|bytes| bytes.len())
+        .unwrap_or_default()
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::asset::{Asset, TokenAmount};
+    use crate::primitives::block::build_hex_merkle_root_sync;
+    use crate::primitives::transaction::{TxIn, TxOut};
+    use crate::utils::transaction_utils::construct_tx_core;
+
+    /// Creates a coinbase transaction for a block number
+    fn test_coinbase(b_num: u64) -> Transaction {
+        let tx_in = TxIn::new_from_script(Script::new_for_coinbase(b_num));
+        let tx_out = TxOut::new_token_amount(hex::encode(vec![0; 32]), TokenAmount(100), None);
+        construct_tx_core(vec![tx_in], vec![tx_out], None)
+    }
+
+    /// Creates a transaction spending an output of the given transaction hash
+    fn test_tx(t_hash: &str, n: i32) -> Transaction {
+        let tx_in = TxIn::new_from_input(OutPoint::new(t_hash.to_owned(), n), Script::new());
+        let tx_out = TxOut::new_token_amount(hex::encode(vec![1; 32]), TokenAmount(1), None);
+        construct_tx_core(vec![tx_in], vec![tx_out], None)
+    }
+
+    #[test]
+    /// Checks that the header links to the previous block and commits to the transactions
+    fn test_build_block_template() {
+        let mut previous_header = BlockHeader::new();
+        previous_header.b_num = 4;
+        let candidates = vec![test_tx("tx_hash", 0), test_tx("tx_hash", 1)];
+
+        let template = BlockTemplateBuilder::new()
+            .with_previous_header(previous_header.clone())
+            .with_coinbase(test_coinbase(5))
+            .with_transactions(candidates.clone())
+            .with_timestamp(1_000)
+            .build()
+            .unwrap();
+
+        let header = &template.block.header;
+        let hashes: Vec<String> = candidates.iter().map(construct_tx_hash).collect();
+
+        assert_eq!(header.b_num, 5);
+        assert_eq!(header.timestamp, 1_000);
+        assert_eq!(header.previous_hash, Some(previous_header.hash()));
+        assert_eq!(
+            header.nonce_and_mining_tx_hash.1,
+            construct_tx_hash(&template.coinbase)
+        );
+        assert_eq!(template.block.transactions, hashes);
+        assert_eq!(
+            header.txs_merkle_root_and_hash.0,
+            build_hex_merkle_root_sync(&hashes)
+        );
+        assert_eq!(header.bits, get_block_size(&template.block));
+        assert!(header.bits <= MAX_BLOCK_SIZE);
+    }
+
+    #[test]
+    /// Checks that children are packed after their parents and conflicts are skipped
+    fn test_build_block_template_dependency_order() {
+        let parent = test_tx("tx_hash", 0);
+        let child = test_tx(&construct_tx_hash(&parent), 0);
+        let conflict = {
+            let mut tx = test_tx("tx_hash", 0);
+            tx.outputs[0].value = Asset::token_u64(2);
+            tx
+        };
+
+        let template = BlockTemplateBuilder::new()
+            .with_coinbase(test_coinbase(0))
+            .with_transactions(vec![child.clone(), parent.clone(), conflict])
+            .build()
+            .unwrap();
+
+        assert_eq!(template.transactions, vec![parent, child]);
+    }
+
+    #[test]
+    /// Checks that packing stops at the maximum block size and invalid coinbases are rejected
+    fn test_build_block_template_size_limit() {
+        let candidates: Vec<Transaction> = (0..100).map(|n| test_tx("tx_hash", n)).collect();
+
+        let template = BlockTemplateBuilder::new()
+            .with_coinbase(test_coinbase(0))
+            .with_transactions(candidates)
+            .build()
+            .unwrap();
+        let invalid = BlockTemplateBuilder::new()
+            .with_coinbase(test_coinbase(1))
+            .build();
+
+        assert!(template.transactions.len() < 100);
+        assert!(get_block_size(&template.block) <= MAX_BLOCK_SIZE);
+        assert!(invalid.is_err());
+    }
+}
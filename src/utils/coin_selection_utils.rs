@@ -0,0 +1,258 @@
+use crate::constants::COIN_SELECTION_MAX_TRIES;
+use crate::primitives::transaction::OutPoint;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Strategy used to select which outputs to spend for a payment
+///
+/// * `LargestFirst`     - Spend the largest outputs first until the target is met
+/// * `BranchAndBound`   - Search for outputs matching the target closely enough that no
+///   change is needed, falling back to `LargestFirst`
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CoinSelectionStrategy {
+    LargestFirst,
+    #[default]
+    BranchAndBound,
+}
+
+/// Selects outputs in descending order of value until their total meets the target
+///
+/// ### Arguments
+///
+/// * `utxos`   - Candidate outputs and their values
+/// * `target`  - Value to be met
+pub fn select_largest_first(utxos: &[(OutPoint, u64)], target: u64) -> Option<Vec<OutPoint>> {
+    let mut sorted = utxos.to_vec();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut selected = Vec::new();
+    let mut total: u64 = 0;
+
+    for (out_point, value) in sorted {
+        if total >= target && !selected.is_empty() {
+            break;
+        }
+        total = total.saturating_add(value);
+        selected.push(out_point);
+    }
+
+    if total < target || selected.is_empty() {
+        debug!("Largest first selection failed to meet target {}", target);
+        return None;
+    }
+
+    Some(selected)
+}
+
+/// Searches for a set of outputs whose total lies between `target` and
+/// `target + cost_of_change`, so that the excess can be given up as fee rather
+/// than creating a change output. Of the matches found the one wasting least is
+/// returned, with the search bounded by `COIN_SELECTION_MAX_TRIES`.
+///
+/// ### Arguments
+///
+/// * `utxos`           - Candidate outputs and their values
+/// * `target`          - Value to be met
+/// * `cost_of_change`  - Largest excess that may be given up instead of creating change
+pub fn select_branch_and_bound(
+    utxos: &[(OutPoint, u64)],
+    target: u64,
+    cost_of_change: u64,
+) -> Option<Vec<OutPoint>> {
+    let mut sorted = utxos.to_vec();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let values: Vec<u64> = sorted.iter().map(|(_, v)| *v).collect();
+    let total = values.iter().fold(0u64, |acc, v| acc.saturating_add(*v));
+    if total < target || target == 0 {
+        return None;
+    }
+
+    // Totals of the values from each index onward, saturating like `TokenAmount`
+    let mut remaining = vec![0u64; values.len() + 1];
+    for index in (0..values.len()).rev() {
+        remaining[index] = remaining[index + 1].saturating_add(values[index]);
+    }
+
+    let mut search = BranchAndBound {
+        values: &values,
+        remaining: &remaining,
+        target,
+        upper_bound: target.saturating_add(cost_of_change),
+        tries: 0,
+        selection: Vec::new(),
+        best: None,
+    };
+    search.explore();
+
+    let (selection, waste) = search.best?;
+    debug!("Branch and bound selection found with waste {}", waste);
+
+    Some(selection.into_iter().map(|i| sorted[i].0.clone()).collect())
+}
+
+/// State of a branch and bound search over output values sorted in descending order
+struct BranchAndBound<'a> {
+    values: &'a [u64],
+    remaining: &'a [u64],
+    target: u64,
+    upper_bound: u64,
+    tries: usize,
+    selection: Vec<usize>,
+    best: Option<(Vec<usize>, u64)>,
+}
+
+/// Branch of the search waiting to be explored
+///
+/// `index`         - Index of the value to branch on next
+/// `current`       - Total of the values selected on this branch
+/// `depth`         - Number of values selected before this branch
+/// `included`      - Index of the value this branch includes, if any
+struct Branch {
+    index: usize,
+    current: u64,
+    depth: usize,
+    included: Option<usize>,
+}
+
+impl BranchAndBound<'_> {
+    /// Explores the inclusion and exclusion branches of each value in turn, depth
+    /// first and trying inclusion first, using an explicit stack of branches
+    fn explore(&mut self) {
+        let mut branches = vec![Branch {
+            index: 0,
+            current: 0,
+            depth: 0,
+            included: None,
+        }];
+
+        while let Some(branch) = branches.pop() {
+            if self.tries >= COIN_SELECTION_MAX_TRIES {
+                return;
+            }
+            if branch.current > self.upper_bound {
+                continue;
+            }
+            self.tries += 1;
+
+            self.selection.truncate(branch.depth);
+            self.selection.extend(branch.included);
+            let Branch { index, current, .. } = branch;
+
+            if current >= self.target {
+                let waste = current - self.target;
+                let is_better = match &self.best {
+                    Some((_, best_waste)) => waste < *best_waste,
+                    None => true,
+                };
+                if is_better {
+                    self.best = Some((self.selection.clone(), waste));
+                }
+                continue;
+            }
+
+            let exact_match_found = matches!(self.best, Some((_, 0)));
+            if index == self.values.len()
+                || current.saturating_add(self.remaining[index]) < self.target
+                || exact_match_found
+            {
+                continue;
+            }
+
+            let value = self.values[index];
+            let depth = self.selection.len();
+            branches.push(Branch {
+                index: index + 1,
+                current,
+                depth,
+                included: None,
+            });
+
+            // A selection overflowing `u64` exceeds any upper bound, so isn't explored
+            if let Some(current) = current.checked_add(value) {
+                branches.push(Branch {
+                    index: index + 1,
+                    current,
+                    depth,
+                    included: Some(index),
+                });
+            }
+        }
+    }
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_utxos(values: &[u64]) -> Vec<(OutPoint, u64)> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(n, v)| (OutPoint::new("tx_hash".to_owned(), n as i32), *v))
+            .collect()
+    }
+
+    fn selected_total(utxos: &[(OutPoint, u64)], selected: &[OutPoint]) -> u64 {
+        utxos
+            .iter()
+            .filter(|(o, _)| selected.contains(o))
+            .map(|(_, v)| v)
+            .sum()
+    }
+
+    #[test]
+    /// Checks that largest first selection spends the fewest, largest outputs
+    fn test_select_largest_first() {
+        let utxos = test_utxos(&[5, 50, 20, 10]);
+
+        let selected = select_largest_first(&utxos, 60).unwrap();
+
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected_total(&utxos, &selected), 70);
+        assert!(select_largest_first(&utxos, 86).is_none());
+    }
+
+    #[test]
+    /// Checks that branch and bound finds an exact match that largest first would overshoot
+    fn test_select_branch_and_bound_exact() {
+        let utxos = test_utxos(&[5, 50, 20, 10]);
+
+        let selected = select_branch_and_bound(&utxos, 35, 0).unwrap();
+
+        assert_eq!(selected_total(&utxos, &selected), 35);
+    }
+
+    #[test]
+    /// Checks that branch and bound only accepts matches within the cost of change
+    fn test_select_branch_and_bound_cost_of_change() {
+        let utxos = test_utxos(&[50, 20, 10]);
+
+        assert!(select_branch_and_bound(&utxos, 29, 0).is_none());
+        let selected = select_branch_and_bound(&utxos, 29, 1).unwrap();
+        assert_eq!(selected_total(&utxos, &selected), 30);
+    }
+
+    #[test]
+    /// Checks that branch and bound can select deeply without exhausting the stack
+    fn test_select_branch_and_bound_many_outputs() {
+        let utxos = test_utxos(&[1; 60_000]);
+
+        let selected = select_branch_and_bound(&utxos, 50_000, 0).unwrap();
+
+        assert_eq!(selected.len(), 50_000);
+    }
+
+    #[test]
+    /// Checks that branch and bound handles values whose totals overflow a `u64`
+    fn test_select_branch_and_bound_large_values() {
+        let utxos = test_utxos(&[u64::MAX - 1, u64::MAX - 1, 1]);
+
+        let selected = select_branch_and_bound(&utxos, u64::MAX, 0).unwrap();
+
+        assert_eq!(selected, vec![utxos[0].0.clone(), utxos[2].0.clone()]);
+        assert!(select_branch_and_bound(&utxos, 3, 0).is_none());
+    }
+}
@@ -0,0 +1,414 @@
+use crate::constants::*;
+use crate::primitives::address::Address;
+use crate::primitives::network::Network;
+use crate::primitives::transaction::{OutPoint, Transaction, TxOut};
+use crate::script::{OpCodes, StackEntry};
+use crate::utils::migration_utils::{tx_follows_address_policy, LegacyAddressPolicy};
+use crate::utils::script_utils::tx_passes_common_checks;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::error;
+
+/// Validation rules applied to transactions of one version, on top of the checks
+/// every transaction must pass
+///
+/// `max_outputs`               - Maximum number of outputs, no more than `MAX_TX_OUTPUTS`
+/// `allow_lock_height`         - Whether transactions may set a lock height
+/// `allow_relative_locks`      - Whether inputs may set a relative lock
+/// `allow_p2sh`                - Whether P2SH outputs may be spent
+/// `disabled_opcodes`          - Opcodes input scripts may not contain
+/// `require_valid_addresses`   - Whether output and fee addresses must parse as an
+///                               `Address`, not only have a valid length
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationRules {
+    pub max_outputs: usize,
+    pub allow_lock_height: bool,
+    pub allow_relative_locks: bool,
+    pub allow_p2sh: bool,
+    pub disabled_opcodes: Vec<OpCodes>,
+    #[serde(default)]
+    pub require_valid_addresses: bool,
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        Self {
+            max_outputs: MAX_TX_OUTPUTS,
+            allow_lock_height: true,
+            allow_relative_locks: true,
+            allow_p2sh: true,
+            disabled_opcodes: Vec::new(),
+            require_valid_addresses: false,
+        }
+    }
+}
+
+/// Validation rules of a transaction version and the block number they activate at
+///
+/// `activation_height` - First block number transactions of the version are valid at
+/// `rules`             - Rules applied to transactions of the version
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub activation_height: u64,
+    pub rules: ValidationRules,
+}
+
+/// Validation rule sets keyed by transaction version. New rules are soft-forked in
+/// by adding a version activated at a future block number, leaving transactions of
+/// older versions valid under their own rules. The legacy address policy applies to
+/// transactions of every version.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsensusRules {
+    rule_sets: BTreeMap<usize, RuleSet>,
+    #[serde(default)]
+    address_policy: LegacyAddressPolicy,
+}
+
+impl Default for ConsensusRules {
+    fn default() -> Self {
+        (NETWORK_VERSION_V0 as usize..=NETWORK_VERSION as usize).fold(
+            Self::new(),
+            |consensus_rules, version| {
+                consensus_rules.with_rule_set(version, 0, ValidationRules::default())
+            },
+        )
+    }
+}
+
+impl ConsensusRules {
+    /// Creates consensus rules without any transaction versions
+    pub fn new() -> Self {
+        Self {
+            rule_sets: BTreeMap::new(),
+            address_policy: LegacyAddressPolicy::default(),
+        }
+    }
+
+    /// Sets the policy retiring legacy address schemes
+    ///
+    /// ### Arguments
+    ///
+    /// * `address_policy`  - Legacy address policy
+    pub fn with_address_policy(mut self, address_policy: LegacyAddressPolicy) -> Self {
+        self.address_policy = address_policy;
+        self
+    }
+
+    /// Gets the policy retiring legacy address schemes
+    pub fn get_address_policy(&self) -> &LegacyAddressPolicy {
+        &self.address_policy
+    }
+
+    /// Adds or replaces the rules of a transaction version
+    ///
+    /// ### Arguments
+    ///
+    /// * `version`             - Transaction version the rules apply to
+    /// * `activation_height`   - First block number transactions of the version are valid at
+    /// * `rules`               - Rules applied to transactions of the version
+    pub fn with_rule_set(
+        mut self,
+        version: usize,
+        activation_height: u64,
+        rules: ValidationRules,
+    ) -> Self {
+        let rule_set = RuleSet {
+            activation_height,
+            rules,
+        };
+        self.rule_sets.insert(version, rule_set);
+        self
+    }
+
+    /// Gets the rules of a transaction version, if it is active at a block number
+    ///
+    /// ### Arguments
+    ///
+    /// * `version`         - Transaction version
+    /// * `block_number`    - Block number the transaction is included at
+    pub fn get_rules(&self, version: usize, block_number: u64) -> Result<&ValidationRules, String> {
+        match self.rule_sets.get(&version) {
+            Some(rule_set) if rule_set.activation_height <= block_number => Ok(&rule_set.rules),
+            Some(rule_set) => Err(format!(
+                "Transaction version {version} is not active until block {}",
+                rule_set.activation_height
+            )),
+            None => Err(format!("Unknown transaction version {version}")),
+        }
+    }
+
+    /// Gets the latest transaction version active at a block number
+    ///
+    /// ### Arguments
+    ///
+    /// * `block_number`    - Block number the transaction is included at
+    pub fn get_latest_version(&self, block_number: u64) -> Option<usize> {
+        self.rule_sets
+            .iter()
+            .rev()
+            .find(|(_, rule_set)| rule_set.activation_height <= block_number)
+            .map(|(version, _)| *version)
+    }
+}
+
+/// Verifies a transaction under the rules of its version and the legacy address
+/// policy, then performs the checks every transaction must pass. Transactions of
+/// unknown or not yet active versions are rejected.
+///
+/// ### Arguments
+///
+/// * `tx`                   - Transaction to verify
+/// * `consensus_rules`      - Rules of each transaction version
+/// * `network`              - Network the transaction is validated for
+/// * `current_block_number` - Current block number
+/// * `is_in_utxo`           - Function to check if a `TxOut` is in the UTXO set
+/// * `utxo_height`          - Function to get the block number a `TxOut` was included in
+pub fn tx_is_valid_with_rules<'a>(
+    tx: &Transaction,
+    consensus_rules: &ConsensusRules,
+    network: Network,
+    current_block_number: u64,
+    is_in_utxo: impl Fn(&OutPoint) -> Option<&'a TxOut> + 'a,
+    utxo_height: impl Fn(&OutPoint) -> Option<u64>,
+) -> (bool, String) {
+    let rules = match consensus_rules.get_rules(tx.version, current_block_number) {
+        Ok(rules) => rules,
+        Err(e) => {
+            error!("TRANSACTION VERSION IS NOT ACTIVE");
+            return (false, e);
+        }
+    };
+
+    if let Err(e) = tx_follows_rules(tx, rules, &is_in_utxo) {
+        error!("TRANSACTION BREAKS THE RULES OF ITS VERSION");
+        return (false, e);
+    }
+
+    let (follows_policy, reason) =
+        tx_follows_address_policy(tx, &consensus_rules.address_policy, current_block_number);
+    if !follows_policy {
+        return (false, reason);
+    }
+
+    tx_passes_common_checks(tx, network, current_block_number, is_in_utxo, utxo_height)
+}
+
+/// Checks a transaction against the rules of its version
+///
+/// ### Arguments
+///
+/// * `tx`          - Transaction to check
+/// * `rules`       - Rules of the transaction's version
+/// * `is_in_utxo`  - Function to check if a `TxOut` is in the UTXO set
+fn tx_follows_rules<'a>(
+    tx: &Transaction,
+    rules: &ValidationRules,
+    is_in_utxo: &impl Fn(&OutPoint) -> Option<&'a TxOut>,
+) -> Result<(), String> {
+    if tx.outputs.len() > rules.max_outputs {
+        return Err("Transaction has too many outputs".to_string());
+    }
+
+    if tx.lock_height > 0 && !rules.allow_lock_height {
+        return Err("Transaction version does not allow lock heights".to_string());
+    }
+
+    if rules.require_valid_addresses {
+        let has_invalid_address = tx
+            .outputs
+            .iter()
+            .chain(&tx.fees)
+            .filter_map(|tx_out| tx_out.script_public_key.as_ref())
+            .any(|address| Address::parse(address).is_err());
+        if has_invalid_address {
+            return Err("Transaction version requires valid addresses".to_string());
+        }
+    }
+
+    for tx_in in &tx.inputs {
+        if tx_in.sequence > 0 && !rules.allow_relative_locks {
+            return Err("Transaction version does not allow relative locks".to_string());
+        }
+
+        let spends_p2sh = tx_in
+            .previous_out
+            .as_ref()
+            .and_then(is_in_utxo)
+            .is_some_and(TxOut::is_p2sh_tx_out);
+        if spends_p2sh && !rules.allow_p2sh {
+            return Err("Transaction version does not allow P2SH spends".to_string());
+        }
+
+        let has_disabled_opcode = tx_in.script_signature.stack.iter().any(
+            |entry| matches!(entry, StackEntry::Op(op) if rules.disabled_opcodes.contains(op)),
+        );
+        if has_disabled_opcode {
+            return Err("Input script contains a disabled opcode".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::sign_ed25519 as sign;
+    use crate::primitives::asset::Asset;
+    use crate::utils::fee_utils::FeeRate;
+    use crate::utils::script_utils::tx_is_valid;
+    use crate::utils::transaction_builder::TransactionBuilder;
+    use crate::utils::transaction_utils::{construct_address, ReceiverInfo};
+
+    /// Creates a spendable output and a transaction builder paying from it
+    fn test_builder() -> (BTreeMap<OutPoint, TxOut>, TransactionBuilder) {
+        let (pk, sk) = sign::gen_keypair();
+        let out_point = OutPoint::new("tx_hash".to_owned(), 0);
+        let tx_out = TxOut::new_asset(construct_address(&pk), Asset::token_u64(100), None);
+        let utxos = BTreeMap::from([(out_point.clone(), tx_out)]);
+
+        let builder = TransactionBuilder::new()
+            .with_utxos(utxos.clone())
+            .with_key_material(BTreeMap::from([(out_point, (pk, sk))]))
+            .with_fee(FeeRate(0), hex::encode([1; 32]))
+            .with_change_address(hex::encode([2; 32]))
+            .pay_to(
+                ReceiverInfo {
+                    address: hex::encode([0; 32]),
+                    asset: Asset::token_u64(60),
+                },
+                0,
+            );
+        (utxos, builder)
+    }
+
+    #[test]
+    /// Checks that transaction versions are only valid once activated, and that unknown
+    /// versions are rejected by the default validation path
+    fn test_version_activation() {
+        let (utxos, builder) = test_builder();
+        let consensus_rules =
+            ConsensusRules::default().with_rule_set(7, 10, ValidationRules::default());
+        let is_valid_at = |tx: &Transaction, block_number| {
+            tx_is_valid_with_rules(
+                tx,
+                &consensus_rules,
+                Network::Mainnet,
+                block_number,
+                |v| utxos.get(v),
+                |_| None,
+            )
+            .0
+        };
+
+        let tx = builder.clone().build().unwrap();
+        let tx_v7 = builder.clone().with_version(7).build().unwrap();
+        let tx_v8 = builder.with_version(8).build().unwrap();
+
+        assert!(is_valid_at(&tx, 0));
+        assert_eq!(consensus_rules.get_latest_version(9), Some(6));
+        assert_eq!(consensus_rules.get_latest_version(10), Some(7));
+
+        assert!(!is_valid_at(&tx_v7, 9));
+        assert!(is_valid_at(&tx_v7, 10));
+        assert!(!is_valid_at(&tx_v8, 10));
+        assert!(tx_is_valid(&tx, 0, |v| utxos.get(v)).0);
+        assert!(!tx_is_valid(&tx_v8, 10, |v| utxos.get(v)).0);
+    }
+
+    #[test]
+    /// Checks that the rules of a version only apply to transactions of that version
+    fn test_version_rules() {
+        let (utxos, builder) = test_builder();
+        let builder = builder.with_relative_lock(1);
+        let tx = builder.clone().build().unwrap();
+        let tx_v7 = builder.with_version(7).build().unwrap();
+        let rules = ValidationRules {
+            allow_relative_locks: false,
+            ..Default::default()
+        };
+        let consensus_rules = ConsensusRules::default().with_rule_set(7, 0, rules);
+        let is_valid = |tx: &Transaction| {
+            tx_is_valid_with_rules(
+                tx,
+                &consensus_rules,
+                Network::Mainnet,
+                5,
+                |v| utxos.get(v),
+                |_| Some(0),
+            )
+            .0
+        };
+
+        assert!(is_valid(&tx));
+        assert!(!is_valid(&tx_v7));
+    }
+
+    #[test]
+    /// Checks that input scripts with disabled opcodes are rejected
+    fn test_disabled_opcodes() {
+        let (utxos, builder) = test_builder();
+        let tx = builder.build().unwrap();
+        let rules = ValidationRules {
+            disabled_opcodes: vec![OpCodes::OP_CHECKSIG],
+            ..Default::default()
+        };
+        let consensus_rules =
+            ConsensusRules::default().with_rule_set(NETWORK_VERSION as usize, 0, rules);
+
+        assert!(
+            !tx_is_valid_with_rules(
+                &tx,
+                &consensus_rules,
+                Network::Mainnet,
+                0,
+                |v| utxos.get(v),
+                |_| None,
+            )
+            .0
+        );
+    }
+
+    #[test]
+    /// Checks that malformed addresses are only rejected by versions requiring valid
+    /// addresses, once those versions activate
+    fn test_require_valid_addresses() {
+        let (utxos, builder) = test_builder();
+        let builder = builder.pay_to(
+            ReceiverInfo {
+                address: "g".repeat(64),
+                asset: Asset::token_u64(1),
+            },
+            0,
+        );
+        let tx = builder.clone().build().unwrap();
+        let tx_v7 = builder.with_version(7).build().unwrap();
+        let rules = ValidationRules {
+            require_valid_addresses: true,
+            ..Default::default()
+        };
+        let consensus_rules = ConsensusRules::default().with_rule_set(7, 10, rules);
+        let is_valid_at = |tx: &Transaction, block_number| {
+            tx_is_valid_with_rules(
+                tx,
+                &consensus_rules,
+                Network::Mainnet,
+                block_number,
+                |v| utxos.get(v),
+                |_| None,
+            )
+        };
+
+        assert!(is_valid_at(&tx, 10).0);
+        assert!(!is_valid_at(&tx_v7, 9).0);
+        assert_eq!(
+            is_valid_at(&tx_v7, 10),
+            (
+                false,
+                "Transaction version requires valid addresses".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,452 @@
+use crate::constants::{
+    DIFFICULTY_LWMA_WINDOW, DIFFICULTY_TARGET_BLOCK_TIME, MAX_FUTURE_BLOCK_TIME,
+    MAX_POW_TARGET_COMPACT, MEDIAN_TIME_PAST_WINDOW,
+};
+use crate::primitives::block::BlockHeader;
+use crate::utils::pow_utils::{compact_from_target, target_from_compact};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use tracing::{debug, error};
+
+/// Algorithm used to retarget the difficulty
+///
+/// * `Lwma`    - Retarget every block from a linearly weighted moving average of
+///   the solve times over `window` blocks
+/// * `Epoch`   - Retarget every `interval` blocks from the time the last interval
+///   took, limited to a factor of four either way
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum DifficultyAlgorithm {
+    Lwma { window: usize },
+    Epoch { interval: u64 },
+}
+
+/// Parameters for difficulty retargeting
+///
+/// `algorithm`             - Retargeting algorithm
+/// `target_block_time`     - Desired seconds between blocks
+/// `pow_limit_compact`     - Compact encoding of the easiest allowed target
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DifficultyParams {
+    pub algorithm: DifficultyAlgorithm,
+    pub target_block_time: i64,
+    pub pow_limit_compact: u32,
+}
+
+impl Default for DifficultyParams {
+    fn default() -> Self {
+        Self {
+            algorithm: DifficultyAlgorithm::Lwma {
+                window: DIFFICULTY_LWMA_WINDOW,
+            },
+            target_block_time: DIFFICULTY_TARGET_BLOCK_TIME,
+            pow_limit_compact: MAX_POW_TARGET_COMPACT,
+        }
+    }
+}
+
+impl DifficultyParams {
+    /// Gets the number of previous headers, ending at the tip, needed to retarget
+    pub fn get_retarget_window(&self) -> usize {
+        match self.algorithm {
+            DifficultyAlgorithm::Lwma { window } => window + 1,
+            DifficultyAlgorithm::Epoch { interval } => interval as usize + 1,
+        }
+    }
+}
+
+/// Calculates the compact target required of the block following `previous_headers`
+///
+/// ### Arguments
+///
+/// * `previous_headers`    - Preceding headers in ascending block order, ending at the tip
+/// * `params`              - Difficulty parameters
+pub fn get_next_compact_target(previous_headers: &[BlockHeader], params: &DifficultyParams) -> u32 {
+    let pow_limit = match target_from_compact(params.pow_limit_compact) {
+        Some(pow_limit) => pow_limit,
+        None => return params.pow_limit_compact,
+    };
+
+    let tip_target = match previous_headers.last().and_then(|h| h.get_target()) {
+        Some(target) => target,
+        None => return params.pow_limit_compact,
+    };
+
+    let next_target = match params.algorithm {
+        DifficultyAlgorithm::Lwma { window } => {
+            get_lwma_target(previous_headers, window, params.target_block_time)
+        }
+        DifficultyAlgorithm::Epoch { interval } => {
+            get_epoch_target(previous_headers, interval, params.target_block_time)
+        }
+    }
+    .unwrap_or(tip_target);
+
+    // Scaling a tiny target down can round it to zero, which no hash could ever meet
+    let mut min_target = [0; 32];
+    min_target[31] = 1;
+    let next_target = std::cmp::max(std::cmp::min(next_target, pow_limit), min_target);
+
+    debug!("Next target: {}", hex::encode(next_target));
+    compact_from_target(&next_target)
+}
+
+/// Checks that a header carries the difficulty required after `previous_headers`
+///
+/// ### Arguments
+///
+/// * `header`              - Header to check
+/// * `previous_headers`    - Preceding headers in ascending block order, ending at the tip
+/// * `params`              - Difficulty parameters
+pub fn header_has_valid_difficulty(
+    header: &BlockHeader,
+    previous_headers: &[BlockHeader],
+    params: &DifficultyParams,
+) -> bool {
+    let expected = get_next_compact_target(previous_headers, params);
+    let actual: Option<[u8; 4]> = header.difficulty.as_slice().try_into().ok();
+
+    if actual.map(u32::from_be_bytes) != Some(expected) {
+        error!("BLOCK HEADER HAS INCORRECT DIFFICULTY");
+        return false;
+    }
+    true
+}
+
+/// Gets the median timestamp of the last `MEDIAN_TIME_PAST_WINDOW` headers
+///
+/// ### Arguments
+///
+/// * `previous_headers`    - Preceding headers in ascending block order, ending at the tip
+pub fn get_median_time_past(previous_headers: &[BlockHeader]) -> Option<i64> {
+    let start = previous_headers
+        .len()
+        .saturating_sub(MEDIAN_TIME_PAST_WINDOW);
+    let mut timestamps: Vec<i64> = previous_headers[start..]
+        .iter()
+        .map(|h| h.timestamp)
+        .collect();
+    timestamps.sort_unstable();
+
+    timestamps.get(timestamps.len() / 2).copied()
+}
+
+/// Checks that a header's timestamp is after the median time past and not too far
+/// in the future
+///
+/// ### Arguments
+///
+/// * `header`              - Header to check
+/// * `previous_headers`    - Preceding headers in ascending block order, ending at the tip
+/// * `current_time`        - Current time, in seconds
+pub fn header_has_valid_timestamp(
+    header: &BlockHeader,
+    previous_headers: &[BlockHeader],
+    current_time: i64,
+) -> (bool, String) {
+    if let Some(median_time_past) = get_median_time_past(previous_headers) {
+        if header.timestamp <= median_time_past {
+            error!("BLOCK TIMESTAMP NOT AFTER MEDIAN TIME PAST");
+            return (
+                false,
+                "Block timestamp is not after the median time past".to_string(),
+            );
+        }
+    }
+
+    if header.timestamp > current_time.saturating_add(MAX_FUTURE_BLOCK_TIME) {
+        error!("BLOCK TIMESTAMP TOO FAR IN THE FUTURE");
+        return (
+            false,
+            "Block timestamp is too far in the future".to_string(),
+        );
+    }
+
+    (true, "Block timestamp is valid".to_string())
+}
+
+/// Calculates the next target from a linearly weighted moving average of solve times,
+/// where each solve time is limited to between 1 and 6 target block times
+///
+/// ### Arguments
+///
+/// * `previous_headers`    - Preceding headers in ascending block order, ending at the tip
+/// * `window`              - Number of solve times to average
+/// * `target_block_time`   - Desired seconds between blocks
+fn get_lwma_target(
+    previous_headers: &[BlockHeader],
+    window: usize,
+    target_block_time: i64,
+) -> Option<[u8; 32]> {
+    let window = std::cmp::min(window, previous_headers.len().checked_sub(1)?);
+    if window == 0 || target_block_time <= 0 {
+        return None;
+    }
+
+    let headers = &previous_headers[previous_headers.len() - window - 1..];
+    let mut weighted_solve_times: u64 = 0;
+    let mut target_sum = [0; 32];
+
+    // Timestamps are only bounded by the median time past, so solve times saturate
+    let max_solve_time = target_block_time.saturating_mul(6);
+    for (i, pair) in headers.windows(2).enumerate() {
+        let solve_time = pair[1]
+            .timestamp
+            .saturating_sub(pair[0].timestamp)
+            .clamp(1, max_solve_time);
+        weighted_solve_times =
+            weighted_solve_times.saturating_add((i as u64 + 1).saturating_mul(solve_time as u64));
+
+        let target = mul_div_target(&pair[1].get_target()?, 1, window as u64);
+        target_sum = add_targets(&target_sum, &target);
+    }
+
+    let weights = (window * (window + 1) / 2) as u64;
+    let expected = weights.saturating_mul(target_block_time as u64);
+    Some(mul_div_target(&target_sum, weighted_solve_times, expected))
+}
+
+/// Calculates the next target at the end of an epoch from the time the epoch took,
+/// keeping the tip's target within an epoch
+///
+/// ### Arguments
+///
+/// * `previous_headers`    - Preceding headers in ascending block order, ending at the tip
+/// * `interval`            - Number of blocks per epoch
+/// * `target_block_time`   - Desired seconds between blocks
+fn get_epoch_target(
+    previous_headers: &[BlockHeader],
+    interval: u64,
+    target_block_time: i64,
+) -> Option<[u8; 32]> {
+    let tip = previous_headers.last()?;
+    let tip_target = tip.get_target()?;
+    if interval == 0 || (tip.b_num + 1) % interval != 0 || target_block_time <= 0 {
+        return Some(tip_target);
+    }
+
+    let first_index = previous_headers.len().checked_sub(interval as usize + 1)?;
+    let first = &previous_headers[first_index];
+
+    let expected = (interval as i64).saturating_mul(target_block_time);
+    let actual = tip
+        .timestamp
+        .saturating_sub(first.timestamp)
+        .clamp(std::cmp::max(expected / 4, 1), expected.saturating_mul(4));
+    Some(mul_div_target(&tip_target, actual as u64, expected as u64))
+}
+
+/// Adds two 256 bit big-endian targets, saturating at the maximum
+///
+/// ### Arguments
+///
+/// * `a`   - First target
+/// * `b`   - Second target
+fn add_targets(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut result = [0; 32];
+    let mut carry = 0u16;
+
+    for i in (0..32).rev() {
+        let sum = a[i] as u16 + b[i] as u16 + carry;
+        result[i] = sum as u8;
+        carry = sum >> 8;
+    }
+
+    if carry > 0 {
+        return [0xff; 32];
+    }
+    result
+}
+
+/// Multiplies a 256 bit big-endian target and then divides it, saturating at the maximum
+///
+/// ### Arguments
+///
+/// * `target`  - Target to scale
+/// * `mul`     - Value to multiply by
+/// * `div`     - Value to divide by
+fn mul_div_target(target: &[u8; 32], mul: u64, div: u64) -> [u8; 32] {
+    if div == 0 {
+        return [0xff; 32];
+    }
+
+    // Little-endian 64 bit limbs, with one extra limb for the product's overflow
+    let mut limbs = [0u64; 5];
+    for (i, chunk) in target.rchunks(8).enumerate() {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(chunk);
+        limbs[i] = u64::from_be_bytes(bytes);
+    }
+
+    let mut carry: u128 = 0;
+    for limb in limbs.iter_mut() {
+        let product = *limb as u128 * mul as u128 + carry;
+        *limb = product as u64;
+        carry = product >> 64;
+    }
+
+    let mut remainder: u128 = 0;
+    for limb in limbs.iter_mut().rev() {
+        let current = (remainder << 64) | *limb as u128;
+        *limb = (current / div as u128) as u64;
+        remainder = current % div as u128;
+    }
+
+    if limbs[4] != 0 {
+        return [0xff; 32];
+    }
+
+    let mut result = [0; 32];
+    for (i, chunk) in result.rchunks_mut(8).enumerate() {
+        chunk.copy_from_slice(&limbs[i].to_be_bytes());
+    }
+    result
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::pow_utils::difficulty_from_compact;
+
+    /// Creates a chain of headers at a fixed target with the given solve time
+    fn test_headers(count: u64, compact: u32, solve_time: i64) -> Vec<BlockHeader> {
+        (0..count)
+            .map(|b_num| {
+                let mut header = BlockHeader::new();
+                header.b_num = b_num;
+                header.timestamp = b_num as i64 * solve_time;
+                header.difficulty = difficulty_from_compact(compact);
+                header
+            })
+            .collect()
+    }
+
+    #[test]
+    /// Checks that LWMA keeps the target when blocks arrive on time and raises or
+    /// lowers the difficulty when they are fast or slow
+    fn test_lwma_retarget() {
+        let params = DifficultyParams::default();
+        let compact = 0x1d00ffff;
+
+        let on_time = test_headers(100, compact, params.target_block_time);
+        let fast = test_headers(100, compact, params.target_block_time / 2);
+        let slow = test_headers(100, compact, params.target_block_time * 2);
+        let target = target_from_compact(compact).unwrap();
+
+        assert_eq!(get_next_compact_target(&on_time, &params), compact);
+        assert!(target_from_compact(get_next_compact_target(&fast, &params)).unwrap() < target);
+        assert!(target_from_compact(get_next_compact_target(&slow, &params)).unwrap() > target);
+        assert_eq!(
+            get_next_compact_target(&[], &params),
+            params.pow_limit_compact
+        );
+    }
+
+    #[test]
+    /// Checks that epoch retargeting only changes the target at epoch boundaries and
+    /// never beyond the proof-of-work limit
+    fn test_epoch_retarget() {
+        let params = DifficultyParams {
+            algorithm: DifficultyAlgorithm::Epoch { interval: 10 },
+            ..Default::default()
+        };
+        let compact = 0x1d00ffff;
+
+        let mid_epoch = test_headers(15, compact, params.target_block_time / 2);
+        let fast_epoch = test_headers(20, compact, params.target_block_time / 2);
+        let slow_at_limit =
+            test_headers(20, params.pow_limit_compact, params.target_block_time * 8);
+
+        assert_eq!(get_next_compact_target(&mid_epoch, &params), compact);
+        assert_eq!(get_next_compact_target(&fast_epoch, &params), 0x1c7fff80);
+        assert_eq!(
+            get_next_compact_target(&slow_at_limit, &params),
+            params.pow_limit_compact
+        );
+    }
+
+    #[test]
+    /// Checks header difficulty and timestamp validation
+    fn test_header_difficulty_and_timestamp_validation() {
+        let params = DifficultyParams::default();
+        let previous = test_headers(20, 0x1d00ffff, params.target_block_time);
+        let tip_time = previous.last().unwrap().timestamp;
+
+        let mut header = BlockHeader::new();
+        header.b_num = 20;
+        header.timestamp = tip_time + params.target_block_time;
+        header.difficulty = difficulty_from_compact(get_next_compact_target(&previous, &params));
+
+        let mut wrong_difficulty = header.clone();
+        wrong_difficulty.difficulty = difficulty_from_compact(0x1c00ffff);
+        let mut stale = header.clone();
+        stale.timestamp = get_median_time_past(&previous).unwrap();
+
+        assert!(header_has_valid_difficulty(&header, &previous, &params));
+        assert!(!header_has_valid_difficulty(
+            &wrong_difficulty,
+            &previous,
+            &params
+        ));
+        assert!(header_has_valid_timestamp(&header, &previous, tip_time).0);
+        assert!(!header_has_valid_timestamp(&stale, &previous, tip_time).0);
+        assert!(
+            !header_has_valid_timestamp(
+                &header,
+                &previous,
+                header.timestamp - MAX_FUTURE_BLOCK_TIME - 1
+            )
+            .0
+        );
+    }
+
+    #[test]
+    /// Checks that retargeting over extreme timestamps saturates rather than overflowing
+    fn test_retarget_extreme_timestamps() {
+        let lwma = DifficultyParams::default();
+        let epoch = DifficultyParams {
+            algorithm: DifficultyAlgorithm::Epoch { interval: 10 },
+            ..Default::default()
+        };
+        let compact = 0x1d00ffff;
+
+        let mut alternating = test_headers(20, compact, lwma.target_block_time);
+        for (n, header) in alternating.iter_mut().enumerate() {
+            header.timestamp = if n % 2 == 0 { i64::MIN } else { i64::MAX };
+        }
+        let mut epoch_span = test_headers(20, compact, epoch.target_block_time);
+        for (n, header) in epoch_span.iter_mut().enumerate() {
+            header.timestamp = if n < 10 { i64::MIN } else { i64::MAX };
+        }
+        let target = target_from_compact(compact).unwrap();
+
+        let lwma_target = target_from_compact(get_next_compact_target(&alternating, &lwma));
+        let epoch_target = target_from_compact(get_next_compact_target(&epoch_span, &epoch));
+
+        assert!(lwma_target.unwrap() > target);
+        assert!(epoch_target.unwrap() > target);
+    }
+
+    #[test]
+    /// Checks that retargeting from the smallest target never reaches a zero target
+    fn test_retarget_never_zero() {
+        let lwma = DifficultyParams {
+            algorithm: DifficultyAlgorithm::Lwma { window: 2 },
+            target_block_time: 1,
+            ..Default::default()
+        };
+        let epoch = DifficultyParams {
+            algorithm: DifficultyAlgorithm::Epoch { interval: 1 },
+            target_block_time: 1,
+            ..Default::default()
+        };
+        let min_compact = 0x01010000;
+
+        // A burst of blocks sharing a timestamp
+        let burst = test_headers(4, min_compact, 0);
+
+        assert_eq!(get_next_compact_target(&burst, &lwma), min_compact);
+        assert_eq!(get_next_compact_target(&burst, &epoch), min_compact);
+    }
+}
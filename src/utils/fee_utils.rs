@@ -0,0 +1,425 @@
+use crate::constants::*;
+use crate::crypto::sign_ed25519::{
+    PublicKey, Signature, ED25519_PUBLIC_KEY_LEN, ED25519_SIGNATURE_LEN,
+};
+use crate::primitives::asset::{Asset, AssetValues, TokenAmount};
+use crate::primitives::transaction::{Transaction, TxIn, TxOut};
+use crate::script::lang::Script;
+use crate::utils::transaction_utils::{construct_tx_core, ReceiverInfo};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tracing::{debug, error};
+
+/// A fee rate, expressed as the number of `Token`s paid per byte of a transaction
+#[derive(Deserialize, Serialize, Default, Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord)]
+pub struct FeeRate(pub u64);
+
+impl fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/byte", TokenAmount(self.0))
+    }
+}
+
+impl FeeRate {
+    /// Creates a new fee rate
+    ///
+    /// ### Arguments
+    ///
+    /// * `tokens_per_byte` - Number of tokens to pay per transaction byte
+    pub fn new(tokens_per_byte: u64) -> Self {
+        Self(tokens_per_byte)
+    }
+
+    /// Calculates the fee for a transaction of the given size
+    ///
+    /// ### Arguments
+    ///
+    /// * `size` - Size of the transaction in bytes
+    pub fn fee_for_size(&self, size: usize) -> TokenAmount {
+        TokenAmount(self.0) * size as u64
+    }
+
+    /// Calculates the fee rate paid by a fee over a transaction size, rounded down
+    ///
+    /// ### Arguments
+    ///
+    /// * `fee`  - Fee paid
+    /// * `size` - Size of the transaction in bytes
+    pub fn from_fee_and_size(fee: TokenAmount, size: usize) -> Self {
+        match size {
+            ZERO => Self(0),
+            _ => Self((fee / size as u64).0),
+        }
+    }
+}
+
+/// The minimum fee requirements a transaction must meet to be relayed
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct FeePolicy {
+    pub min_fee_rate: FeeRate,
+    pub min_fee: TokenAmount,
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        Self::new(FeeRate(MIN_RELAY_FEE_RATE), TokenAmount(MIN_RELAY_FEE))
+    }
+}
+
+impl FeePolicy {
+    /// Creates a new fee policy
+    ///
+    /// ### Arguments
+    ///
+    /// * `min_fee_rate` - Minimum fee rate to pay
+    /// * `min_fee`      - Minimum absolute fee to pay, regardless of size
+    pub fn new(min_fee_rate: FeeRate, min_fee: TokenAmount) -> Self {
+        Self {
+            min_fee_rate,
+            min_fee,
+        }
+    }
+
+    /// Gets the minimum fee required for a transaction of the given size
+    ///
+    /// ### Arguments
+    ///
+    /// * `size` - Size of the transaction in bytes
+    pub fn required_fee(&self, size: usize) -> TokenAmount {
+        std::cmp::max(self.min_fee, self.min_fee_rate.fee_for_size(size))
+    }
+}
+
+/// Gets the total amount of `Token`s paid in fees by a transaction
+///
+/// ### Arguments
+///
+/// * `tx`  - Transaction to total fees for
+pub fn get_tx_fees_paid(tx: &Transaction) -> TokenAmount {
+    tx.fees.iter().map(|fee| fee.value.token_amount()).sum()
+}
+
+/// Gets the fee rate paid by a transaction
+///
+/// ### Arguments
+///
+/// * `tx`  - Transaction to get the fee rate for
+pub fn get_tx_fee_rate(tx: &Transaction) -> FeeRate {
+    FeeRate::from_fee_and_size(get_tx_fees_paid(tx), estimate_signed_tx_size(tx))
+}
+
+/// Checks whether a transaction pays enough in fees to satisfy a fee policy.
+/// Coinbase and create transactions are not required to pay fees.
+///
+/// ### Arguments
+///
+/// * `tx`      - Transaction to check
+/// * `policy`  - Fee policy to check against
+pub fn tx_meets_fee_policy(tx: &Transaction, policy: &FeePolicy) -> (bool, String) {
+    if tx.is_coinbase() || tx.is_create_tx() {
+        return (true, "".to_string());
+    }
+
+    if tx.fees.iter().any(|fee| !fee.value.is_token()) {
+        error!("FEES MUST BE PAID IN TOKENS");
+        return (false, "Fees must be paid in tokens".to_string());
+    }
+
+    let required = policy.required_fee(estimate_signed_tx_size(tx));
+    let paid = get_tx_fees_paid(tx);
+    debug!("Fee paid: {:?}, fee required: {:?}", paid, required);
+
+    if paid < required {
+        error!("TRANSACTION FEE BELOW MINIMUM");
+        return (
+            false,
+            format!(
+                "Transaction fee {} is below the required {}",
+                paid.0, required.0
+            ),
+        );
+    }
+
+    (true, "".to_string())
+}
+
+/// Estimates the size of a transaction once all of its inputs are signed.
+/// Inputs that spend a previous output but carry no script yet are sized as
+/// standard P2PKH spends.
+///
+/// ### Arguments
+///
+/// * `tx`  - Transaction to estimate the size of
+pub fn estimate_signed_tx_size(tx: &Transaction) -> usize {
+    if !tx
+        .inputs
+        .iter()
+        .any(|tx_in| tx_in.previous_out.is_some() && tx_in.script_signature.stack.is_empty())
+    {
+        return tx.get_total_size();
+    }
+
+    let mut tx = tx.clone();
+    for tx_in in tx.inputs.iter_mut() {
+        if tx_in.previous_out.is_some() && tx_in.script_signature.stack.is_empty() {
+            tx_in.script_signature = placeholder_p2pkh_script();
+        }
+    }
+    tx.get_total_size()
+}
+
+/// Calculates the fee and change outputs needed to balance a payment. Change is
+/// returned for every asset in `tx_ins_spent` which is not fully spent by `tx_outs`,
+/// and the fee is sized so that the signed transaction meets `fee_rate`.
+///
+/// Returns `None` if the inputs cannot cover the outputs and the fee.
+///
+/// ### Arguments
+///
+/// * `tx_ins`          - Inputs to the transaction
+/// * `tx_outs`         - Outputs to pay
+/// * `tx_ins_spent`    - Total value of the inputs
+/// * `fee_rate`        - Fee rate to pay
+/// * `fee_address`     - Address to pay the fee to
+/// * `change_address`  - Address to return the change to
+pub fn construct_fee_and_change(
+    tx_ins: &[TxIn],
+    tx_outs: &[TxOut],
+    tx_ins_spent: &AssetValues,
+    fee_rate: FeeRate,
+    fee_address: &str,
+    change_address: &str,
+) -> Option<(Option<ReceiverInfo>, Vec<TxOut>)> {
+    let mut fee = TokenAmount(0);
+
+    loop {
+        let change = construct_change_tx_outs(tx_outs, tx_ins_spent, fee, change_address)?;
+        let fee_info = (fee.0 > 0).then(|| ReceiverInfo {
+            address: fee_address.to_owned(),
+            asset: Asset::Token(fee),
+        });
+
+        let mut outputs = tx_outs.to_vec();
+        outputs.extend(change.iter().cloned());
+        let tx = construct_tx_core(tx_ins.to_vec(), outputs, fee_info.clone());
+        let required = fee_rate.fee_for_size(estimate_signed_tx_size(&tx));
+
+        // Amounts are serialized at a fixed width, so the size only grows when
+        // the fee output is first added and this settles within a few rounds
+        if required <= fee {
+            return Some((fee_info, change));
+        }
+        fee = required;
+    }
+}
+
+/// Constructs the change outputs returning everything in `tx_ins_spent`
+/// that is not spent by `tx_outs` or the fee
+///
+/// ### Arguments
+///
+/// * `tx_outs`         - Outputs to pay
+/// * `tx_ins_spent`    - Total value of the inputs
+/// * `fee`             - Fee to pay
+/// * `change_address`  - Address to return the change to
+pub fn construct_change_tx_outs(
+    tx_outs: &[TxOut],
+    tx_ins_spent: &AssetValues,
+    fee: TokenAmount,
+    change_address: &str,
+) -> Option<Vec<TxOut>> {
+    let mut tx_outs_spent = AssetValues::token_u64(fee.0);
+    tx_outs
+        .iter()
+        .for_each(|out| tx_outs_spent.update_add(&out.value));
+
+    if tx_ins_spent.tokens < tx_outs_spent.tokens {
+        debug!("Insufficient tokens to cover outputs and fee");
+        return None;
+    }
+
+    let mut change = Vec::new();
+    for (genesis_hash, amount) in &tx_outs_spent.items {
+        if tx_ins_spent
+            .items
+            .get(genesis_hash)
+            .copied()
+            .unwrap_or_default()
+            < *amount
+        {
+            debug!("Insufficient items for genesis hash {}", genesis_hash);
+            return None;
+        }
+    }
+
+    let token_change = tx_ins_spent.tokens - tx_outs_spent.tokens;
+    if token_change.0 > 0 {
+        change.push(TxOut::new_token_amount(
+            change_address.to_owned(),
+            token_change,
+            None,
+        ));
+    }
+
+    for (genesis_hash, amount) in &tx_ins_spent.items {
+        let spent = tx_outs_spent
+            .items
+            .get(genesis_hash)
+            .copied()
+            .unwrap_or_default();
+        if *amount > spent {
+            let item = Asset::item(*amount - spent, Some(genesis_hash.clone()), None);
+            change.push(TxOut::new_asset(change_address.to_owned(), item, None));
+        }
+    }
+
+    Some(change)
+}
+
+/// Constructs a script of the same size as a standard P2PKH spend
+fn placeholder_p2pkh_script() -> Script {
+    let signature = Signature::from_slice(&[0; ED25519_SIGNATURE_LEN]);
+    let pub_key = PublicKey::from_slice(&[0; ED25519_PUBLIC_KEY_LEN]);
+
+    match (signature, pub_key) {
+        (Some(signature), Some(pub_key)) => Script::pay2pkh(
+            "0".repeat(STANDARD_ADDRESS_LENGTH),
+            signature,
+            pub_key,
+            None,
+        ),
+        _ => Script::new(),
+    }
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::transaction::OutPoint;
+    use crate::primitives::transaction::TxConstructor;
+    use crate::utils::transaction_utils::construct_payment_tx_ins;
+
+    fn test_tx_ins(count: usize) -> Vec<TxIn> {
+        let tx_values = (0..count)
+            .map(|n| TxConstructor {
+                previous_out: OutPoint::new("tx_hash".to_owned(), n as i32),
+                signatures: vec![],
+                pub_keys: vec![],
+                address_version: None,
+            })
+            .collect();
+        construct_payment_tx_ins(tx_values)
+    }
+
+    #[test]
+    /// Checks fee rate arithmetic
+    fn test_fee_rate_calculation() {
+        let fee_rate = FeeRate::new(3);
+
+        assert_eq!(fee_rate.fee_for_size(100), TokenAmount(300));
+        assert_eq!(FeeRate::from_fee_and_size(TokenAmount(301), 100), fee_rate);
+        assert_eq!(FeeRate::from_fee_and_size(TokenAmount(301), 0), FeeRate(0));
+    }
+
+    #[test]
+    /// Checks that the required fee respects both the rate and the absolute minimum
+    fn test_fee_policy_required_fee() {
+        let policy = FeePolicy::new(FeeRate(2), TokenAmount(500));
+
+        assert_eq!(policy.required_fee(100), TokenAmount(500));
+        assert_eq!(policy.required_fee(1000), TokenAmount(2000));
+    }
+
+    #[test]
+    /// Checks that unsigned inputs are sized as signed P2PKH spends
+    fn test_estimate_signed_tx_size() {
+        let tx = construct_tx_core(test_tx_ins(2), vec![], None);
+        let mut signed_tx = tx.clone();
+        signed_tx
+            .inputs
+            .iter_mut()
+            .for_each(|tx_in| tx_in.script_signature = placeholder_p2pkh_script());
+
+        assert!(estimate_signed_tx_size(&tx) > tx.get_total_size());
+        assert_eq!(estimate_signed_tx_size(&tx), signed_tx.get_total_size());
+    }
+
+    #[test]
+    /// Checks that the fee and change returned balance the transaction at the fee rate
+    fn test_construct_fee_and_change() {
+        let tx_ins = test_tx_ins(2);
+        let tx_outs = vec![TxOut::new_token_amount(
+            "payee".to_owned(),
+            TokenAmount(1000),
+            None,
+        )];
+        let mut items = std::collections::BTreeMap::new();
+        items.insert("genesis_hash".to_owned(), 5);
+        let tx_ins_spent = AssetValues::new(TokenAmount(100_000), items);
+        let fee_rate = FeeRate(10);
+
+        let (fee, change) = construct_fee_and_change(
+            &tx_ins,
+            &tx_outs,
+            &tx_ins_spent,
+            fee_rate,
+            "fee_address",
+            "change_address",
+        )
+        .unwrap();
+
+        let fee = fee.unwrap();
+        let mut outputs = tx_outs.clone();
+        outputs.extend(change.clone());
+        let tx = construct_tx_core(tx_ins, outputs, Some(fee.clone()));
+
+        assert_eq!(change.len(), 2);
+        assert_eq!(
+            change[1].value,
+            Asset::item(5, Some("genesis_hash".to_owned()), None)
+        );
+        assert_eq!(
+            fee.asset.token_amount() + change[0].value.token_amount() + TokenAmount(1000),
+            TokenAmount(100_000)
+        );
+        assert!(tx_meets_fee_policy(&tx, &FeePolicy::new(fee_rate, TokenAmount(0))).0);
+    }
+
+    #[test]
+    /// Checks that insufficient inputs produce no fee and change
+    fn test_construct_fee_and_change_insufficient() {
+        let tx_outs = vec![TxOut::new_token_amount(
+            "payee".to_owned(),
+            TokenAmount(1000),
+            None,
+        )];
+
+        let result = construct_fee_and_change(
+            &test_tx_ins(1),
+            &tx_outs,
+            &AssetValues::token_u64(1001),
+            FeeRate(10),
+            "fee_address",
+            "change_address",
+        );
+
+        assert!(result.is_none());
+    }
+
+    #[test]
+    /// Checks that transactions paying below the policy are rejected
+    fn test_tx_below_fee_policy() {
+        let fee = ReceiverInfo {
+            address: "fee_address".to_owned(),
+            asset: Asset::token_u64(1),
+        };
+        let tx = construct_tx_core(test_tx_ins(1), vec![TxOut::new()], Some(fee));
+
+        let (is_valid, _) = tx_meets_fee_policy(&tx, &FeePolicy::default());
+
+        assert!(!is_valid);
+        assert!(tx_meets_fee_policy(&tx, &FeePolicy::new(FeeRate(0), TokenAmount(1))).0);
+    }
+}
@@ -0,0 +1,377 @@
+use crate::constants::*;
+use crate::crypto::sign_ed25519::{sign_detached, PublicKey, SecretKey};
+use crate::primitives::address::Address;
+use crate::primitives::asset::AssetValues;
+use crate::primitives::network::Network;
+use crate::primitives::transaction::{OutPoint, Transaction, TxIn, TxOut};
+use crate::script::lang::Script;
+use crate::script::{OpCodes, StackEntry};
+use crate::utils::fee_utils::{construct_fee_and_change, FeeRate};
+use crate::utils::transaction_utils::{
+    construct_address_for, construct_tx_core, construct_tx_in_out_signable_hash_for_network,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::{debug, error};
+
+/// Policy disabling the legacy address schemes from configured block numbers. Once
+/// a scheme is retired its outputs can no longer be spent, so they must be swept to
+/// standard addresses beforehand. The policy is enforced as part of `ConsensusRules`.
+///
+/// `v0_retirement_height`      - Block number from which v0 addresses are disabled
+/// `temp_retirement_height`    - Block number from which temporary addresses are disabled
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LegacyAddressPolicy {
+    pub v0_retirement_height: Option<u64>,
+    pub temp_retirement_height: Option<u64>,
+}
+
+impl LegacyAddressPolicy {
+    /// Creates a policy which doesn't retire any address scheme
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Retires v0 addresses from a block number
+    ///
+    /// ### Arguments
+    ///
+    /// * `height`  - Block number from which v0 addresses are disabled
+    pub fn with_v0_retirement_height(mut self, height: u64) -> Self {
+        self.v0_retirement_height = Some(height);
+        self
+    }
+
+    /// Retires temporary addresses from a block number
+    ///
+    /// ### Arguments
+    ///
+    /// * `height`  - Block number from which temporary addresses are disabled
+    pub fn with_temp_retirement_height(mut self, height: u64) -> Self {
+        self.temp_retirement_height = Some(height);
+        self
+    }
+
+    /// Checks whether an address version may still be used at a block number
+    ///
+    /// ### Arguments
+    ///
+    /// * `address_version` - Address version to check
+    /// * `block_number`    - Block number the address is used at
+    pub fn is_address_version_allowed(
+        &self,
+        address_version: Option<u64>,
+        block_number: u64,
+    ) -> bool {
+        let retirement_height = match address_version {
+            Some(NETWORK_VERSION_V0) => self.v0_retirement_height,
+            Some(NETWORK_VERSION_TEMP) => self.temp_retirement_height,
+            _ => None,
+        };
+        !matches!(retirement_height, Some(height) if block_number >= height)
+    }
+}
+
+/// An output paid to a legacy address of a known public key
+///
+/// `out_point`         - Outpoint of the output
+/// `tx_out`            - The output
+/// `address_version`   - Network version of the address the output is paid to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LegacyUtxo {
+    pub out_point: OutPoint,
+    pub tx_out: TxOut,
+    pub address_version: u64,
+}
+
+/// Checks that a transaction doesn't pay to or spend with a retired address scheme.
+/// Temporary addresses look like standard ones, so only their spends are checked.
+///
+/// ### Arguments
+///
+/// * `tx`                      - Transaction to check
+/// * `policy`                  - Legacy address policy
+/// * `current_block_number`    - Current block number
+pub fn tx_follows_address_policy(
+    tx: &Transaction,
+    policy: &LegacyAddressPolicy,
+    current_block_number: u64,
+) -> (bool, String) {
+    let pays_to_retired = tx
+        .outputs
+        .iter()
+        .chain(tx.fees.iter())
+        .filter_map(|tx_out| tx_out.script_public_key.as_deref())
+        .any(|address| {
+            matches!(Address::parse(address), Ok(Address::V0(_)))
+                && !policy
+                    .is_address_version_allowed(Some(NETWORK_VERSION_V0), current_block_number)
+        });
+    if pays_to_retired {
+        error!("TRANSACTION PAYS TO A RETIRED ADDRESS");
+        return (false, "Transaction pays to a retired address".to_string());
+    }
+
+    for tx_in in &tx.inputs {
+        let address_version = tx_in
+            .script_signature
+            .stack
+            .iter()
+            .find_map(|entry| match entry {
+                StackEntry::Op(OpCodes::OP_HASH256_V0) => Some(NETWORK_VERSION_V0),
+                StackEntry::Op(OpCodes::OP_HASH256_TEMP) => Some(NETWORK_VERSION_TEMP),
+                _ => None,
+            });
+        if !policy.is_address_version_allowed(address_version, current_block_number) {
+            error!("TRANSACTION SPENDS FROM A RETIRED ADDRESS");
+            return (
+                false,
+                "Transaction spends from a retired address".to_string(),
+            );
+        }
+    }
+
+    (true, "".to_string())
+}
+
+/// Finds the outputs paid to the v0 or temporary address of any of the given keys
+///
+/// ### Arguments
+///
+/// * `utxos`       - UTXO set to scan
+/// * `pub_keys`    - Public keys whose legacy addresses are looked for
+pub fn find_legacy_utxos<'a>(
+    utxos: impl IntoIterator<Item = (&'a OutPoint, &'a TxOut)>,
+    pub_keys: &[PublicKey],
+) -> Vec<LegacyUtxo> {
+    let legacy_addresses: BTreeMap<String, u64> = pub_keys
+        .iter()
+        .flat_map(|pk| {
+            [NETWORK_VERSION_V0, NETWORK_VERSION_TEMP]
+                .iter()
+                .map(move |version| (construct_address_for(pk, Some(*version)), *version))
+        })
+        .collect();
+
+    utxos
+        .into_iter()
+        .filter_map(|(out_point, tx_out)| {
+            let address = tx_out.script_public_key.as_ref()?;
+            let address_version = *legacy_addresses.get(address)?;
+            Some(LegacyUtxo {
+                out_point: out_point.clone(),
+                tx_out: tx_out.clone(),
+                address_version,
+            })
+        })
+        .collect()
+}
+
+/// Builds transactions sweeping legacy outputs to a standard address, each spending
+/// at most `max_inputs` outputs and paying a fee at the given rate. The outputs must
+/// already be spendable.
+///
+/// ### Arguments
+///
+/// * `legacy_utxos`    - Legacy outputs to sweep
+/// * `key_material`    - Keys for the legacy outputs
+/// * `to_address`      - Standard address to sweep to
+/// * `fee_rate`        - Fee rate to pay
+/// * `fee_address`     - Address to pay the fees to
+/// * `network`         - Network the transactions are signed for
+/// * `max_inputs`      - Maximum number of outputs spent by each transaction
+pub fn construct_sweep_txs(
+    legacy_utxos: &[LegacyUtxo],
+    key_material: &BTreeMap<OutPoint, (PublicKey, SecretKey)>,
+    to_address: &str,
+    fee_rate: FeeRate,
+    fee_address: &str,
+    network: Network,
+    max_inputs: usize,
+) -> Result<Vec<Transaction>, String> {
+    if !matches!(Address::parse(to_address), Ok(Address::Standard(_))) {
+        return Err("Sweep must pay to a standard address".to_string());
+    }
+    if max_inputs == ZERO {
+        return Err("Sweep transactions must spend at least one output".to_string());
+    }
+
+    let mut txs = Vec::new();
+    for legacy_utxos in legacy_utxos.chunks(max_inputs) {
+        let mut tx_ins = Vec::new();
+        let mut tx_ins_spent = AssetValues::default();
+        for utxo in legacy_utxos {
+            tx_ins.push(TxIn::new_from_input(utxo.out_point.clone(), Script::new()));
+            tx_ins_spent.update_add(&utxo.tx_out.value.clone().with_fixed_hash(&utxo.out_point));
+        }
+
+        let (fee_info, tx_outs) = construct_fee_and_change(
+            &tx_ins,
+            &[],
+            &tx_ins_spent,
+            fee_rate,
+            fee_address,
+            to_address,
+        )
+        .filter(|(_, tx_outs)| !tx_outs.is_empty())
+        .ok_or_else(|| "Swept outputs cannot cover the fee".to_string())?;
+
+        let mut tx = construct_tx_core(tx_ins, tx_outs, fee_info);
+        let unsigned_tx = tx.clone();
+        for (tx_in, utxo) in tx.inputs.iter_mut().zip(legacy_utxos) {
+            let (pk, sk) = key_material
+                .get(&utxo.out_point)
+                .ok_or_else(|| format!("No key material for input {}", utxo.out_point))?;
+            let signable_hash =
+                construct_tx_in_out_signable_hash_for_network(tx_in, &unsigned_tx, network);
+            let signature = sign_detached(signable_hash.as_bytes(), sk);
+
+            tx_in.script_signature =
+                Script::pay2pkh(signable_hash, signature, *pk, Some(utxo.address_version));
+        }
+
+        txs.push(tx);
+    }
+
+    debug!("Built {} sweep transactions", txs.len());
+    Ok(txs)
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::sign_ed25519 as sign;
+    use crate::primitives::asset::Asset;
+    use crate::utils::consensus_utils::{tx_is_valid_with_rules, ConsensusRules};
+    use crate::utils::script_utils::tx_is_valid;
+    use crate::utils::transaction_utils::construct_address;
+
+    /// UTXOs, their key material and the public key they are paid to
+    type TestUtxos = (
+        BTreeMap<OutPoint, TxOut>,
+        BTreeMap<OutPoint, (PublicKey, SecretKey)>,
+        PublicKey,
+    );
+
+    /// Creates UTXOs paid to the v0, temporary and standard addresses of one key
+    fn test_utxos() -> TestUtxos {
+        let (pk, sk) = sign::gen_keypair();
+        let outputs = vec![
+            (Some(NETWORK_VERSION_V0), Asset::token_u64(5_000)),
+            (
+                Some(NETWORK_VERSION_TEMP),
+                Asset::item(2, Some("genesis".to_owned()), None),
+            ),
+            (Some(NETWORK_VERSION_TEMP), Asset::token_u64(3_000)),
+            (None, Asset::token_u64(1_000)),
+        ];
+
+        let mut utxos = BTreeMap::new();
+        let mut key_material = BTreeMap::new();
+        for (n, (address_version, asset)) in outputs.into_iter().enumerate() {
+            let out_point = OutPoint::new("tx_hash".to_owned(), n as i32);
+            let address = construct_address_for(&pk, address_version);
+            utxos.insert(out_point.clone(), TxOut::new_asset(address, asset, None));
+            key_material.insert(out_point, (pk, sk.clone()));
+        }
+        (utxos, key_material, pk)
+    }
+
+    #[test]
+    /// Checks that legacy outputs are found and swept to a standard address
+    fn test_sweep_legacy_utxos() {
+        let (utxos, key_material, pk) = test_utxos();
+        let to_address = construct_address(&pk);
+        let legacy_utxos = find_legacy_utxos(&utxos, &[pk]);
+
+        let txs = construct_sweep_txs(
+            &legacy_utxos,
+            &key_material,
+            &to_address,
+            FeeRate(1),
+            &hex::encode([1; 32]),
+            Network::Mainnet,
+            2,
+        )
+        .unwrap();
+
+        assert_eq!(legacy_utxos.len(), 3);
+        assert_eq!(legacy_utxos[0].address_version, NETWORK_VERSION_V0);
+        assert_eq!(txs.len(), 2);
+        let mut fee_redirected = txs[0].clone();
+        fee_redirected.fees[0].script_public_key = Some(hex::encode([2; 32]));
+
+        assert!(!tx_is_valid(&fee_redirected, 0, |v| utxos.get(v)).0);
+        for tx in &txs {
+            assert!(tx_is_valid(tx, 0, |v| utxos.get(v)).0);
+            assert!(tx
+                .outputs
+                .iter()
+                .all(|out| out.script_public_key.as_ref() == Some(&to_address)));
+        }
+        assert!(construct_sweep_txs(
+            &legacy_utxos,
+            &key_material,
+            &construct_address_for(&pk, Some(NETWORK_VERSION_V0)),
+            FeeRate(1),
+            &hex::encode([1; 32]),
+            Network::Mainnet,
+            2,
+        )
+        .is_err());
+    }
+
+    #[test]
+    /// Checks that retired address schemes can't be paid to or spent from
+    fn test_legacy_address_policy() {
+        let (utxos, key_material, pk) = test_utxos();
+        let legacy_utxos = find_legacy_utxos(&utxos, &[pk]);
+        let sweep = |legacy_utxos: &[LegacyUtxo], to_address: &str| {
+            construct_sweep_txs(
+                legacy_utxos,
+                &key_material,
+                to_address,
+                FeeRate(0),
+                &hex::encode([1; 32]),
+                Network::Mainnet,
+                1,
+            )
+            .unwrap()
+            .remove(0)
+        };
+        let v0_sweep = sweep(&legacy_utxos[..1], &construct_address(&pk));
+        let temp_sweep = sweep(&legacy_utxos[1..2], &construct_address(&pk));
+        let mut v0_payment = temp_sweep.clone();
+        v0_payment.outputs[0].script_public_key =
+            Some(construct_address_for(&pk, Some(NETWORK_VERSION_V0)));
+
+        let policy = LegacyAddressPolicy::new()
+            .with_v0_retirement_height(10)
+            .with_temp_retirement_height(20);
+
+        assert!(tx_follows_address_policy(&v0_sweep, &policy, 9).0);
+        assert!(!tx_follows_address_policy(&v0_sweep, &policy, 10).0);
+        assert!(tx_follows_address_policy(&v0_payment, &policy, 9).0);
+        assert!(!tx_follows_address_policy(&v0_payment, &policy, 10).0);
+        assert!(tx_follows_address_policy(&temp_sweep, &policy, 19).0);
+        assert!(!tx_follows_address_policy(&temp_sweep, &policy, 20).0);
+        assert!(tx_follows_address_policy(&temp_sweep, &LegacyAddressPolicy::new(), 20).0);
+
+        let consensus_rules = ConsensusRules::default().with_address_policy(policy);
+        let is_valid_at = |block_number| {
+            tx_is_valid_with_rules(
+                &v0_sweep,
+                &consensus_rules,
+                Network::Mainnet,
+                block_number,
+                |v| utxos.get(v),
+                |_| None,
+            )
+            .0
+        };
+        assert!(is_valid_at(9));
+        assert!(!is_valid_at(10));
+    }
+}
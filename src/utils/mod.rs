@@ -5,10 +5,18 @@ use crate::primitives::asset::TokenAmount;
 
 // ------- MODS ------- //
 
+pub mod block_template_builder;
+pub mod coin_selection_utils;
+pub mod consensus_utils;
+pub mod difficulty_utils;
 pub mod druid_utils;
 pub mod error_utils;
+pub mod fee_utils;
+pub mod migration_utils;
+pub mod pow_utils;
 pub mod script_utils;
 pub mod test_utils;
+pub mod transaction_builder;
 pub mod transaction_utils;
 
 // ------- FUNCTIONS ------- //
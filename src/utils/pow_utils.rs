@@ -0,0 +1,278 @@
+use crate::primitives::block::BlockHeader;
+use tracing::debug;
+
+/// Decodes a compact proof-of-work target into a 256 bit big-endian target.
+/// The compact form holds the target's length in bytes in its highest byte and
+/// its three most significant bytes below that. Returns `None` for negative or
+/// overflowing encodings.
+///
+/// ### Arguments
+///
+/// * `compact` - Compact encoding of the target
+pub fn target_from_compact(compact: u32) -> Option<[u8; 32]> {
+    let size = (compact >> 24) as usize;
+    let mut mantissa = compact & 0x007f_ffff;
+
+    if compact & 0x0080_0000 != 0 && mantissa != 0 {
+        debug!("Compact target {:#010x} is negative", compact);
+        return None;
+    }
+
+    let mut target = [0; 32];
+    if size <= 3 {
+        mantissa >>= 8 * (3 - size);
+        target[29..].copy_from_slice(&mantissa.to_be_bytes()[1..]);
+        return Some(target);
+    }
+
+    for (i, byte) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+        match (32 + i).checked_sub(size) {
+            Some(index) if index < 32 => target[index] = *byte,
+            _ if *byte == 0 => {}
+            _ => {
+                debug!("Compact target {:#010x} overflows", compact);
+                return None;
+            }
+        }
+    }
+
+    Some(target)
+}
+
+/// Encodes a 256 bit big-endian target in its compact form, rounding down
+///
+/// ### Arguments
+///
+/// * `target`  - Target to encode
+pub fn compact_from_target(target: &[u8; 32]) -> u32 {
+    let first = match target.iter().position(|b| *b != 0) {
+        Some(first) => first,
+        None => return 0,
+    };
+
+    let mut size = 32 - first;
+    let mut mantissa_bytes = [0; 4];
+    let significant = std::cmp::min(size, 3);
+    mantissa_bytes[4 - significant..].copy_from_slice(&target[first..first + significant]);
+    let mut mantissa = u32::from_be_bytes(mantissa_bytes) << (8 * (3 - significant));
+
+    // The top mantissa bit is a sign bit, so shift it into an extra byte
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+
+    ((size as u32) << 24) | mantissa
+}
+
+/// Gets the difficulty bytes stored in a `BlockHeader` for a compact target
+///
+/// ### Arguments
+///
+/// * `compact` - Compact encoding of the target
+pub fn difficulty_from_compact(compact: u32) -> Vec<u8> {
+    compact.to_be_bytes().to_vec()
+}
+
+/// Checks whether a hex encoded hash, read as a 256 bit big-endian number, is no
+/// greater than the target
+///
+/// ### Arguments
+///
+/// * `hash`    - Hex encoded hash to check
+/// * `target`  - Target to meet
+pub fn hash_meets_target(hash: &str, target: &[u8; 32]) -> bool {
+    match hex::decode(hash) {
+        Ok(hash_bytes) if hash_bytes.len() == target.len() => hash_bytes.as_slice() <= &target[..],
+        _ => false,
+    }
+}
+
+/// Gets the expected number of hashes needed to meet a target, `2^256 / (target + 1)`,
+/// saturating at `u128::MAX`
+///
+/// ### Arguments
+///
+/// * `target`  - Target to get the work for
+pub fn get_target_work(target: &[u8; 32]) -> u128 {
+    // Work is `!target / (target + 1) + 1`, found by binary long division
+    let divisor = to_limbs(target);
+    let divisor = match add_one(&divisor) {
+        Some(divisor) => divisor,
+        None => return 1,
+    };
+    let dividend = to_limbs(&target.map(|b| !b));
+
+    let mut quotient = [0u64; 4];
+    let mut remainder = [0u64; 4];
+    for bit in (0..256).rev() {
+        let overflow = remainder[3] >> 63;
+        remainder = shift_left(&remainder);
+        remainder[0] |= (dividend[bit / 64] >> (bit % 64)) & 1;
+
+        if overflow == 1 || !is_less(&remainder, &divisor) {
+            remainder = subtract(&remainder, &divisor);
+            quotient[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    if quotient[2] != 0 || quotient[3] != 0 {
+        return u128::MAX;
+    }
+    ((quotient[1] as u128) << 64 | quotient[0] as u128).saturating_add(1)
+}
+
+/// Converts a 256 bit big-endian value to little-endian 64 bit limbs
+fn to_limbs(value: &[u8; 32]) -> [u64; 4] {
+    let mut limbs = [0; 4];
+    for (i, chunk) in value.rchunks(8).enumerate() {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(chunk);
+        limbs[i] = u64::from_be_bytes(bytes);
+    }
+    limbs
+}
+
+/// Adds one to a 256 bit value, returning `None` on overflow
+fn add_one(value: &[u64; 4]) -> Option<[u64; 4]> {
+    let mut result = *value;
+    for limb in result.iter_mut() {
+        let (sum, overflow) = limb.overflowing_add(1);
+        *limb = sum;
+        if !overflow {
+            return Some(result);
+        }
+    }
+    None
+}
+
+/// Shifts a 256 bit value left by one bit
+fn shift_left(value: &[u64; 4]) -> [u64; 4] {
+    let mut result = [0; 4];
+    for i in 0..4 {
+        result[i] = value[i] << 1;
+        if i > 0 {
+            result[i] |= value[i - 1] >> 63;
+        }
+    }
+    result
+}
+
+/// Checks whether one 256 bit value is less than another
+fn is_less(a: &[u64; 4], b: &[u64; 4]) -> bool {
+    a.iter().rev().lt(b.iter().rev())
+}
+
+/// Subtracts one 256 bit value from another, wrapping on underflow
+fn subtract(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+    let mut result = [0; 4];
+    let mut borrow = false;
+    for i in 0..4 {
+        let (diff, borrow_a) = a[i].overflowing_sub(b[i]);
+        let (diff, borrow_b) = diff.overflowing_sub(borrow as u64);
+        result[i] = diff;
+        borrow = borrow_a || borrow_b;
+    }
+    result
+}
+
+/// Searches for a nonce giving the header a hash that meets its target. The nonce
+/// is stored as a little-endian u64 and the search starts from zero.
+///
+/// ### Arguments
+///
+/// * `header`      - Header to mine, updated with the nonce found
+/// * `max_tries`   - Maximum number of nonces to try
+pub fn mine_header(header: &mut BlockHeader, max_tries: u64) -> bool {
+    let target = match header.get_target() {
+        Some(target) => target,
+        None => return false,
+    };
+
+    for nonce in 0..max_tries {
+        header.nonce_and_mining_tx_hash.0 = nonce.to_le_bytes().to_vec();
+        if hash_meets_target(&header.hash(), &target) {
+            debug!("Mined block {} with nonce {}", header.b_num, nonce);
+            return true;
+        }
+    }
+
+    debug!(
+        "No nonce found for block {} in {} tries",
+        header.b_num, max_tries
+    );
+    false
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MAX_POW_TARGET_COMPACT;
+
+    #[test]
+    /// Checks compact targets against known encodings and that they round trip
+    fn test_compact_target_encoding() {
+        let mut expected = [0; 32];
+        expected[4] = 0xff;
+        expected[5] = 0xff;
+
+        assert_eq!(target_from_compact(0x1d00ffff), Some(expected));
+        assert_eq!(compact_from_target(&expected), 0x1d00ffff);
+        assert_eq!(
+            target_from_compact(0x03123456).unwrap()[29..],
+            [0x12, 0x34, 0x56]
+        );
+        assert_eq!(target_from_compact(0x02123456).unwrap()[30..], [0x12, 0x34]);
+        assert_eq!(
+            compact_from_target(&target_from_compact(0x05009234).unwrap()),
+            0x05009234
+        );
+        assert_eq!(
+            compact_from_target(&target_from_compact(MAX_POW_TARGET_COMPACT).unwrap()),
+            MAX_POW_TARGET_COMPACT
+        );
+    }
+
+    #[test]
+    /// Checks that negative and overflowing compact targets are rejected
+    fn test_invalid_compact_target() {
+        assert_eq!(target_from_compact(0x04923456), None);
+        assert_eq!(target_from_compact(0x21010000), None);
+        assert!(target_from_compact(0x21000001).is_some());
+        assert_eq!(target_from_compact(0x22000000), Some([0; 32]));
+    }
+
+    #[test]
+    /// Checks the work expected for known targets
+    fn test_target_work() {
+        let mut half = [0xff; 32];
+        half[0] = 0x7f;
+
+        assert_eq!(get_target_work(&[0xff; 32]), 1);
+        assert_eq!(get_target_work(&half), 2);
+        assert_eq!(
+            get_target_work(&target_from_compact(0x1d00ffff).unwrap()),
+            0x100010001
+        );
+        assert_eq!(get_target_work(&[0; 32]), u128::MAX);
+    }
+
+    #[test]
+    /// Checks that a mined header meets its target and that unreachable targets are not met
+    fn test_mine_and_verify_header() {
+        let mut header = BlockHeader::new();
+        header.difficulty = difficulty_from_compact(MAX_POW_TARGET_COMPACT);
+
+        assert!(mine_header(&mut header, 1_000));
+        assert!(header.meets_target());
+
+        let mut hard_header = header.clone();
+        hard_header.difficulty = difficulty_from_compact(0x03000001);
+
+        assert!(!hard_header.meets_target());
+        assert!(!mine_header(&mut hard_header, 10));
+        assert!(!BlockHeader::new().meets_target());
+    }
+}
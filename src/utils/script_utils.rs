@@ -6,14 +6,17 @@ use crate::crypto::sign_ed25519::{
 };
 use crate::primitives::asset::{Asset, AssetValues, ItemAsset, TokenAmount};
 use crate::primitives::druid::DruidExpectation;
+use crate::primitives::network::Network;
 use crate::primitives::transaction::*;
 use crate::script::interface_ops::*;
 use crate::script::lang::{ConditionStack, Script, Stack};
 use crate::script::{OpCodes, StackEntry};
+use crate::utils::consensus_utils::{tx_is_valid_with_rules, ConsensusRules};
 use crate::utils::error_utils::*;
 use crate::utils::transaction_utils::{
     construct_address, construct_tx_hash, construct_tx_in_out_signable_hash,
-    construct_tx_in_signable_asset_hash, construct_tx_in_signable_hash,
+    construct_tx_in_out_signable_hash_for_network, construct_tx_in_signable_asset_hash,
+    construct_tx_in_signable_hash,
 };
 use bincode::serialize;
 use bytes::Bytes;
@@ -39,9 +42,89 @@ pub fn tx_is_valid<'a>(
     tx: &Transaction,
     current_block_number: u64,
     is_in_utxo: impl Fn(&OutPoint) -> Option<&'a TxOut> + 'a,
+) -> (bool, String) {
+    tx_is_valid_with_utxo_heights(tx, current_block_number, is_in_utxo, |_| None)
+}
+
+/// Verifies that all incoming transactions are allowed to be spent, including any
+/// relative locks on the inputs. Returns false if a single transaction doesn't verify
+///
+/// ### Arguments
+///
+/// * `tx`                   - Transaction to verify
+/// * `current_block_number` - Current block number
+/// * `is_in_utxo`           - Function to check if a `TxOut` is in the UTXO set
+/// * `utxo_height`          - Function to get the block number a `TxOut` was included in
+pub fn tx_is_valid_with_utxo_heights<'a>(
+    tx: &Transaction,
+    current_block_number: u64,
+    is_in_utxo: impl Fn(&OutPoint) -> Option<&'a TxOut> + 'a,
+    utxo_height: impl Fn(&OutPoint) -> Option<u64>,
+) -> (bool, String) {
+    tx_is_valid_for_network(
+        tx,
+        Network::Mainnet,
+        current_block_number,
+        is_in_utxo,
+        utxo_height,
+    )
+}
+
+/// Verifies that all incoming transactions are allowed to be spent on a given network,
+/// including any relative locks on the inputs, under the default consensus rules.
+/// Signatures made for other networks are rejected. Returns false if a single
+/// transaction doesn't verify
+///
+/// ### Arguments
+///
+/// * `tx`                   - Transaction to verify
+/// * `network`              - Network the transaction is validated for
+/// * `current_block_number` - Current block number
+/// * `is_in_utxo`           - Function to check if a `TxOut` is in the UTXO set
+/// * `utxo_height`          - Function to get the block number a `TxOut` was included in
+pub fn tx_is_valid_for_network<'a>(
+    tx: &Transaction,
+    network: Network,
+    current_block_number: u64,
+    is_in_utxo: impl Fn(&OutPoint) -> Option<&'a TxOut> + 'a,
+    utxo_height: impl Fn(&OutPoint) -> Option<u64>,
+) -> (bool, String) {
+    tx_is_valid_with_rules(
+        tx,
+        &ConsensusRules::default(),
+        network,
+        current_block_number,
+        is_in_utxo,
+        utxo_height,
+    )
+}
+
+/// Performs the checks every transaction must pass whatever its version: lock heights,
+/// input signatures on the given network, and that outputs and fees match the inputs.
+/// Version-specific rules are checked by `tx_is_valid_with_rules` before this.
+///
+/// ### Arguments
+///
+/// * `tx`                   - Transaction to verify
+/// * `network`              - Network the transaction is validated for
+/// * `current_block_number` - Current block number
+/// * `is_in_utxo`           - Function to check if a `TxOut` is in the UTXO set
+/// * `utxo_height`          - Function to get the block number a `TxOut` was included in
+pub(crate) fn tx_passes_common_checks<'a>(
+    tx: &Transaction,
+    network: Network,
+    current_block_number: u64,
+    is_in_utxo: impl Fn(&OutPoint) -> Option<&'a TxOut> + 'a,
+    utxo_height: impl Fn(&OutPoint) -> Option<u64>,
 ) -> (bool, String) {
     let mut tx_ins_spent: AssetValues = Default::default();
 
+    // Check the transaction may be included at this block number
+    if tx.lock_height > current_block_number {
+        error!("TRANSACTION LOCK HEIGHT NOT MET");
+        return (false, "Transaction lock height not reached".to_string());
+    }
+
     // `Item` assets MUST have an a DRS value associated with them when they are getting on-spent
 
     debug!("tx: {:?}", tx.outputs);
@@ -67,9 +150,6 @@ pub fn tx_is_valid<'a>(
     }
 
     for tx_in in &tx.inputs {
-        let full_tx_hash = construct_tx_in_out_signable_hash(tx_in, &tx.outputs);
-        println!("full_tx_hash: {:?}", full_tx_hash);
-
         // Ensure the transaction is in the `UTXO` set
         let tx_out_point = match tx_in.previous_out.as_ref() {
             Some(v) => v,
@@ -99,18 +179,33 @@ pub fn tx_is_valid<'a>(
             return (false, "Locktime not expired".to_string());
         }
 
+        // Check relative locktime
+        if tx_in.sequence > 0 {
+            match utxo_height(tx_out_point) {
+                Some(height) if height.saturating_add(tx_in.sequence) <= current_block_number => {}
+                Some(_) => {
+                    error!("RELATIVE LOCKTIME NOT MET");
+                    return (false, "Relative locktime not expired".to_string());
+                }
+                None => {
+                    error!("RELATIVE LOCKTIME CANNOT BE CHECKED");
+                    return (
+                        false,
+                        "Relative locktime cannot be checked without output height".to_string(),
+                    );
+                }
+            }
+        }
+
         // At this point `TxIn` will be valid
         let tx_out_pk = tx_out.script_public_key.as_ref();
         let tx_out_hash = construct_tx_in_signable_hash(tx_out_point);
-        let full_tx_hash = construct_tx_in_out_signable_hash(tx_in, &tx.outputs);
+        let full_tx_hash = construct_tx_in_out_signable_hash_for_network(tx_in, tx, network);
 
         debug!("full_tx_hash: {:?}", full_tx_hash);
 
         if let Some(pk) = tx_out_pk {
-            // Check will need to include other signature types here
-            if !tx_has_valid_p2pkh_sig(&tx_in.script_signature, &full_tx_hash, pk)
-                && !tx_has_valid_p2sh_script(&tx_in.script_signature, pk)
-            {
+            if !tx_in_has_valid_script(&tx_in.script_signature, &full_tx_hash, pk) {
                 error!("INVALID SIGNATURE OR SCRIPT TYPE");
                 return (false, "Invalid signature or script structure".to_string());
             }
@@ -146,6 +241,11 @@ pub fn tx_outs_are_valid(
 ) -> (bool, String) {
     let mut tx_outs_spent: AssetValues = Default::default();
 
+    if tx_outs.len() > MAX_TX_OUTPUTS {
+        error!("TRANSACTION HAS TOO MANY OUTPUTS");
+        return (false, "Transaction has too many outputs".to_string());
+    }
+
     for tx_out in tx_outs {
         // Addresses must have valid length
         if let Some(addr) = &tx_out.script_public_key {
@@ -226,6 +326,20 @@ pub fn tx_has_valid_create_script(script: &Script, asset: &Asset) -> bool {
     false
 }
 
+/// Checks whether an input script validly spends an output, either as a P2PKH
+/// signature over the input's signable hash or as a P2SH redemption
+///
+/// ### Arguments
+///
+/// * `script`          - Input script to validate
+/// * `full_tx_hash`    - Signable hash of the input
+/// * `address`         - Address of the spent output
+pub fn tx_in_has_valid_script(script: &Script, full_tx_hash: &str, address: &str) -> bool {
+    // Check will need to include other signature types here
+    tx_has_valid_p2pkh_sig(script, full_tx_hash, address)
+        || tx_has_valid_p2sh_script(script, address)
+}
+
 /// Checks whether a transaction to spend tokens in P2PKH has a valid signature
 ///
 /// ### Arguments
@@ -2826,11 +2940,10 @@ mod tests {
             address_version,
         };
 
-        let tx_outs = vec![];
-        let mut tx_ins = construct_payment_tx_ins(vec![tx_const]);
-        tx_ins = update_input_signatures(&tx_ins, &tx_outs, &key_material);
+        let tx = construct_tx_core(construct_payment_tx_ins(vec![tx_const]), vec![], None);
+        let tx_ins = update_input_signatures(&tx, &key_material);
 
-        let hash_to_sign = construct_tx_in_out_signable_hash(&tx_ins[0], &tx_outs);
+        let hash_to_sign = construct_tx_in_out_signable_hash(&tx_ins[0], &tx);
         let tx_out_pk = construct_address_for(&pk, address_version);
 
         assert!(tx_has_valid_p2pkh_sig(
@@ -3111,9 +3224,11 @@ mod tests {
         let tx_in = TxIn {
             script_signature: Script::new(),
             previous_out: Some(tx_outpoint.clone()),
+            sequence: 0,
         };
 
-        let valid_bytes = construct_tx_in_out_signable_hash(&tx_in, &ongoing_tx_outs.clone());
+        let unsigned_tx = construct_tx_core(vec![tx_in.clone()], ongoing_tx_outs.clone(), None);
+        let valid_bytes = construct_tx_in_out_signable_hash(&tx_in, &unsigned_tx);
         let valid_sig = sign::sign_detached(valid_bytes.as_bytes(), &sk);
 
         // Test cases:
@@ -3146,6 +3261,7 @@ mod tests {
                     stack: script.clone(),
                 },
                 previous_out: Some(tx_outpoint.clone()),
+                sequence: 0,
             }];
 
             let tx = Transaction {
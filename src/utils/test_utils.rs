@@ -60,8 +60,9 @@ pub fn generate_tx_with_ins_and_outs_assets(
             &TxIn {
                 previous_out: Some(tx_previous_out.clone()),
                 script_signature: Script::new(),
+                sequence: 0,
             },
-            &tx.outputs,
+            &tx,
         );
         let signature = sign::sign_detached(signable_hash.as_bytes(), &sk);
         let tx_in = TxIn::new_from_input(
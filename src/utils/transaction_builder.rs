@@ -0,0 +1,634 @@
+use crate::constants::{MAX_TX_OUTPUTS, STANDARD_ADDRESS_LENGTH};
+use crate::crypto::sign_ed25519::{PublicKey, SecretKey};
+use crate::primitives::asset::{Asset, AssetValues, TokenAmount};
+use crate::primitives::network::Network;
+use crate::primitives::transaction::{OutPoint, Transaction, TxConstructor, TxIn, TxOut};
+use crate::utils::coin_selection_utils::{
+    select_branch_and_bound, select_largest_first, CoinSelectionStrategy,
+};
+use crate::utils::fee_utils::{
+    construct_change_tx_outs, construct_fee_and_change, estimate_signed_tx_size, FeeRate,
+};
+use crate::utils::transaction_utils::{
+    construct_payment_tx_ins, construct_tx_core, update_input_signatures_for_network, ReceiverInfo,
+};
+use std::collections::BTreeMap;
+use tracing::debug;
+
+/// Builds signed payment transactions from a set of spendable outputs. Inputs are
+/// chosen by coin selection, a fee is paid at the given fee rate, and any excess
+/// `Token`s or `Item`s are returned to the change address rather than burnt.
+#[derive(Default, Debug, Clone)]
+pub struct TransactionBuilder {
+    utxos: Vec<(OutPoint, TxOut)>,
+    outputs: Vec<TxOut>,
+    fee_rate: FeeRate,
+    fee_address: String,
+    change_address: Option<String>,
+    strategy: CoinSelectionStrategy,
+    current_block_number: Option<u64>,
+    lock_height: u64,
+    sequence: u64,
+    version: Option<usize>,
+    network: Network,
+    key_material: BTreeMap<OutPoint, (PublicKey, SecretKey)>,
+}
+
+impl TransactionBuilder {
+    /// Creates a new, empty transaction builder
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds outputs which may be spent by the transaction
+    ///
+    /// ### Arguments
+    ///
+    /// * `utxos`   - Spendable outputs and their `OutPoint`s
+    pub fn with_utxos(mut self, utxos: impl IntoIterator<Item = (OutPoint, TxOut)>) -> Self {
+        self.utxos.extend(utxos);
+        self
+    }
+
+    /// Adds an output to pay
+    ///
+    /// ### Arguments
+    ///
+    /// * `receiver`    - Address and asset to pay
+    /// * `locktime`    - Block height below which the payment is restricted. "0" means no locktime
+    pub fn pay_to(mut self, receiver: ReceiverInfo, locktime: u64) -> Self {
+        self.outputs.push(TxOut {
+            value: receiver.asset,
+            locktime,
+            script_public_key: Some(receiver.address),
+        });
+        self
+    }
+
+    /// Sets the fee rate to pay and the address the fee is paid to
+    ///
+    /// ### Arguments
+    ///
+    /// * `fee_rate`    - Fee rate to pay
+    /// * `fee_address` - Address to pay the fee to
+    pub fn with_fee(mut self, fee_rate: FeeRate, fee_address: String) -> Self {
+        self.fee_rate = fee_rate;
+        self.fee_address = fee_address;
+        self
+    }
+
+    /// Sets the address any excess is returned to
+    ///
+    /// ### Arguments
+    ///
+    /// * `change_address`  - Address to return change to
+    pub fn with_change_address(mut self, change_address: String) -> Self {
+        self.change_address = Some(change_address);
+        self
+    }
+
+    /// Sets the coin selection strategy
+    ///
+    /// ### Arguments
+    ///
+    /// * `strategy`    - Coin selection strategy to use
+    pub fn with_strategy(mut self, strategy: CoinSelectionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Excludes outputs which are still locked at the given block height
+    ///
+    /// ### Arguments
+    ///
+    /// * `current_block_number`    - Current block number
+    pub fn with_current_block_number(mut self, current_block_number: u64) -> Self {
+        self.current_block_number = Some(current_block_number);
+        self
+    }
+
+    /// Sets the first block number the transaction may be included in
+    ///
+    /// ### Arguments
+    ///
+    /// * `lock_height` - Block number the transaction is locked until. "0" means no lock
+    pub fn with_lock_height(mut self, lock_height: u64) -> Self {
+        self.lock_height = lock_height;
+        self
+    }
+
+    /// Sets a relative lock on every selected input
+    ///
+    /// ### Arguments
+    ///
+    /// * `sequence`    - Blocks that must pass after each spent output was included
+    pub fn with_relative_lock(mut self, sequence: u64) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// Sets the version of the transaction. Defaults to `NETWORK_VERSION`
+    ///
+    /// ### Arguments
+    ///
+    /// * `version` - Version of the transaction, selecting the rules it is validated under
+    pub fn with_version(mut self, version: usize) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Sets the network the transaction is signed for. Defaults to mainnet
+    ///
+    /// ### Arguments
+    ///
+    /// * `network` - Network the transaction is signed for
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Adds the key material used to sign the selected inputs
+    ///
+    /// ### Arguments
+    ///
+    /// * `key_material`    - Keys for the spendable outputs
+    pub fn with_key_material(
+        mut self,
+        key_material: BTreeMap<OutPoint, (PublicKey, SecretKey)>,
+    ) -> Self {
+        self.key_material.extend(key_material);
+        self
+    }
+
+    /// Selects inputs, adds fee and change, and signs the transaction
+    pub fn build(self) -> Result<Transaction, String> {
+        if self.outputs.is_empty() {
+            return Err("Transaction has no outputs to pay".to_string());
+        }
+        let change_address = self
+            .change_address
+            .clone()
+            .ok_or_else(|| "No change address provided".to_string())?;
+
+        let mut targets = AssetValues::default();
+        for tx_out in &self.outputs {
+            if let Asset::Item(item) = &tx_out.value {
+                if item.genesis_hash.is_none() {
+                    return Err("Item payments require a genesis hash".to_string());
+                }
+            }
+            targets.update_add(&tx_out.value);
+        }
+
+        let spendable = self.get_spendable_utxos();
+
+        // Items are selected first so that their inputs are included when sizing the fee
+        let mut selected = Vec::new();
+        for (genesis_hash, amount) in &targets.items {
+            let candidates: Vec<(OutPoint, u64)> = spendable
+                .iter()
+                .filter(|(_, asset)| asset.get_genesis_hash() == Some(genesis_hash))
+                .map(|(out_point, asset)| (out_point.clone(), asset.item_amount()))
+                .collect();
+
+            let items = select_largest_first(&candidates, *amount)
+                .ok_or_else(|| format!("Insufficient items for genesis hash {genesis_hash}"))?;
+            selected.extend(items);
+        }
+
+        // Item change is known once the items are selected, so it is sized into the fee
+        let mut items_spent = AssetValues::token_u64(targets.tokens.0);
+        for out_point in &selected {
+            if let Some((_, asset)) = spendable.iter().find(|(o, _)| o == out_point) {
+                items_spent.update_add(asset);
+            }
+        }
+        let item_change =
+            construct_change_tx_outs(&self.outputs, &items_spent, TokenAmount(0), &change_address)
+                .unwrap_or_default();
+
+        let (tokens, with_change) =
+            self.select_tokens(&spendable, &selected, &item_change, targets.tokens)?;
+        selected.extend(tokens);
+
+        let mut tx_ins_spent = AssetValues::default();
+        for out_point in &selected {
+            if let Some((_, asset)) = spendable.iter().find(|(o, _)| o == out_point) {
+                tx_ins_spent.update_add(asset);
+            }
+        }
+
+        let tx_ins: Vec<TxIn> = construct_unsigned_tx_ins(&selected)
+            .into_iter()
+            .map(|tx_in| tx_in.with_sequence(self.sequence))
+            .collect();
+        let (fee, change) = if with_change {
+            construct_fee_and_change(
+                &tx_ins,
+                &self.outputs,
+                &tx_ins_spent,
+                self.fee_rate,
+                &self.fee_address,
+                &change_address,
+            )
+        } else {
+            // A changeless selection gives up its excess `Token`s as fee
+            let fee = tx_ins_spent.tokens - targets.tokens;
+            let fee_info = (fee.0 > 0).then(|| ReceiverInfo {
+                address: self.fee_address.clone(),
+                asset: Asset::Token(fee),
+            });
+            construct_change_tx_outs(&self.outputs, &tx_ins_spent, fee, &change_address)
+                .map(|change| (fee_info, change))
+        }
+        .ok_or_else(|| "Insufficient funds to cover outputs and fee".to_string())?;
+
+        if fee.is_some() && self.fee_address.is_empty() {
+            return Err("No fee address provided".to_string());
+        }
+
+        if let Some(missing) = selected.iter().find(|o| !self.key_material.contains_key(o)) {
+            return Err(format!("No key material for input {missing}"));
+        }
+
+        let mut tx_outs = self.outputs.clone();
+        tx_outs.extend(change);
+        if tx_outs.len() > MAX_TX_OUTPUTS {
+            return Err("Transaction has too many outputs".to_string());
+        }
+
+        let mut tx = construct_tx_core(tx_ins, tx_outs, fee);
+        tx.lock_height = self.lock_height;
+        if let Some(version) = self.version {
+            tx.version = version;
+        }
+        tx.inputs = update_input_signatures_for_network(&tx, self.network, &self.key_material);
+        Ok(tx)
+    }
+
+    /// Gets the unlocked outputs, with `Item` genesis hashes resolved
+    fn get_spendable_utxos(&self) -> Vec<(OutPoint, Asset)> {
+        self.utxos
+            .iter()
+            .filter(|(_, tx_out)| match self.current_block_number {
+                Some(b_num) => tx_out.locktime <= b_num,
+                None => true,
+            })
+            .map(|(out_point, tx_out)| {
+                let asset = tx_out.value.clone().with_fixed_hash(out_point);
+                (out_point.clone(), asset)
+            })
+            .collect()
+    }
+
+    /// Selects the `Token` inputs to cover the payment and fee. Returns the selected
+    /// inputs and whether the transaction needs a `Token` change output.
+    ///
+    /// ### Arguments
+    ///
+    /// * `spendable`   - Spendable outputs
+    /// * `selected`    - Inputs already selected
+    /// * `item_change` - `Item` change outputs for the inputs already selected
+    /// * `target`      - `Token`s to pay, excluding the fee
+    fn select_tokens(
+        &self,
+        spendable: &[(OutPoint, Asset)],
+        selected: &[OutPoint],
+        item_change: &[TxOut],
+        target: TokenAmount,
+    ) -> Result<(Vec<OutPoint>, bool), String> {
+        let mut outputs = self.outputs.clone();
+        outputs.extend(item_change.iter().cloned());
+        let base_tx = construct_tx_core(
+            construct_unsigned_tx_ins(selected),
+            outputs,
+            Some(ReceiverInfo {
+                address: self.fee_address.clone(),
+                asset: Asset::Token(TokenAmount(0)),
+            }),
+        );
+        let base_size = estimate_signed_tx_size(&base_tx);
+        let change_fee = self.fee_rate.fee_for_size(get_tx_out_size());
+        let target = target + self.fee_rate.fee_for_size(base_size);
+
+        // Each candidate is valued net of the fee it adds when spent
+        let candidates: Vec<(OutPoint, u64)> = spendable
+            .iter()
+            .filter(|(out_point, asset)| asset.is_token() && !selected.contains(out_point))
+            .filter_map(|(out_point, asset)| {
+                let input_fee = self.fee_rate.fee_for_size(get_signed_tx_in_size(out_point));
+                (asset.token_amount() > input_fee)
+                    .then(|| (out_point.clone(), (asset.token_amount() - input_fee).0))
+            })
+            .collect();
+
+        if target.0 == 0 {
+            return Ok((Vec::new(), true));
+        }
+
+        if self.strategy == CoinSelectionStrategy::BranchAndBound {
+            if let Some(tokens) = select_branch_and_bound(&candidates, target.0, change_fee.0) {
+                debug!("Found changeless selection of {} inputs", tokens.len());
+                return Ok((tokens, false));
+            }
+        }
+
+        select_largest_first(&candidates, (target + change_fee).0)
+            .map(|tokens| (tokens, true))
+            .ok_or_else(|| "Insufficient tokens to cover outputs and fee".to_string())
+    }
+}
+
+/// Constructs unsigned inputs spending the given outputs
+///
+/// ### Arguments
+///
+/// * `out_points`  - Outputs to spend
+fn construct_unsigned_tx_ins(out_points: &[OutPoint]) -> Vec<TxIn> {
+    let tx_values = out_points
+        .iter()
+        .map(|out_point| TxConstructor {
+            previous_out: out_point.clone(),
+            signatures: Vec::new(),
+            pub_keys: Vec::new(),
+            address_version: None,
+        })
+        .collect();
+    construct_payment_tx_ins(tx_values)
+}
+
+/// Gets the number of bytes a signed P2PKH input adds to a transaction
+///
+/// ### Arguments
+///
+/// * `out_point`   - Output spent by the input
+fn get_signed_tx_in_size(out_point: &OutPoint) -> usize {
+    let tx_ins = construct_unsigned_tx_ins(std::slice::from_ref(out_point));
+    let tx = construct_tx_core(tx_ins, Vec::new(), None);
+    estimate_signed_tx_size(&tx) - Transaction::new().get_total_size()
+}
+
+/// Gets the number of bytes a `Token` change output adds to a transaction
+fn get_tx_out_size() -> usize {
+    let tx_out = TxOut::new_token_amount("0".repeat(STANDARD_ADDRESS_LENGTH), TokenAmount(0), None);
+    let tx = construct_tx_core(Vec::new(), vec![tx_out], None);
+    tx.get_total_size() - Transaction::new().get_total_size()
+}
+
+/*---- TESTS ----*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::sign_ed25519 as sign;
+    use crate::utils::fee_utils::{tx_meets_fee_policy, FeePolicy};
+    use crate::utils::script_utils::{
+        tx_is_valid, tx_is_valid_for_network, tx_is_valid_with_utxo_heights,
+    };
+    use crate::utils::transaction_utils::construct_address;
+
+    /// Creates spendable outputs of the given assets, owned by a single key
+    fn test_utxos(
+        assets: &[Asset],
+    ) -> (
+        BTreeMap<OutPoint, TxOut>,
+        BTreeMap<OutPoint, (PublicKey, SecretKey)>,
+    ) {
+        let (pk, sk) = sign::gen_keypair();
+        let address = construct_address(&pk);
+        let mut utxos = BTreeMap::new();
+        let mut key_material = BTreeMap::new();
+
+        for (n, asset) in assets.iter().enumerate() {
+            let out_point = OutPoint::new("tx_hash".to_owned(), n as i32);
+            utxos.insert(
+                out_point.clone(),
+                TxOut::new_asset(address.clone(), asset.clone(), None),
+            );
+            key_material.insert(out_point, (pk, sk.clone()));
+        }
+
+        (utxos, key_material)
+    }
+
+    fn test_builder(
+        utxos: &BTreeMap<OutPoint, TxOut>,
+        key_material: BTreeMap<OutPoint, (PublicKey, SecretKey)>,
+        fee_rate: FeeRate,
+    ) -> TransactionBuilder {
+        TransactionBuilder::new()
+            .with_utxos(utxos.clone())
+            .with_key_material(key_material)
+            .with_fee(fee_rate, hex::encode(vec![1; 32]))
+            .with_change_address(hex::encode(vec![2; 32]))
+    }
+
+    #[test]
+    /// Checks that a token payment is balanced with change, pays its fee and validates
+    fn test_build_token_payment_with_change() {
+        let (utxos, key_material) = test_utxos(&[
+            Asset::token_u64(1_000),
+            Asset::token_u64(50_000),
+            Asset::token_u64(20_000),
+        ]);
+        let fee_rate = FeeRate(5);
+
+        let tx = test_builder(&utxos, key_material, fee_rate)
+            .with_strategy(CoinSelectionStrategy::LargestFirst)
+            .pay_to(
+                ReceiverInfo {
+                    address: hex::encode(vec![0; 32]),
+                    asset: Asset::token_u64(30_000),
+                },
+                0,
+            )
+            .build()
+            .unwrap();
+
+        assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(tx.outputs.len(), 2);
+        assert!(tx_is_valid(&tx, 0, |v| utxos.get(v)).0);
+        assert!(tx_meets_fee_policy(&tx, &FeePolicy::new(fee_rate, TokenAmount(0))).0);
+    }
+
+    #[test]
+    /// Checks that a transaction signed for testnet is only valid on testnet
+    fn test_build_payment_for_network() {
+        let (utxos, key_material) = test_utxos(&[Asset::token_u64(50_000)]);
+
+        let tx = test_builder(&utxos, key_material, FeeRate(0))
+            .with_network(Network::Testnet)
+            .pay_to(
+                ReceiverInfo {
+                    address: hex::encode(vec![0; 32]),
+                    asset: Asset::token_u64(30_000),
+                },
+                0,
+            )
+            .build()
+            .unwrap();
+        let is_valid_on =
+            |network| tx_is_valid_for_network(&tx, network, 0, |v| utxos.get(v), |_| None).0;
+
+        assert!(is_valid_on(Network::Testnet));
+        assert!(!is_valid_on(Network::Mainnet));
+        assert!(!is_valid_on(Network::Devnet));
+        assert!(!tx_is_valid(&tx, 0, |v| utxos.get(v)).0);
+    }
+
+    #[test]
+    /// Checks that branch and bound avoids a change output when an exact match exists
+    fn test_build_token_payment_without_change() {
+        let (utxos, key_material) = test_utxos(&[
+            Asset::token_u64(50_000),
+            Asset::token_u64(30_000),
+            Asset::token_u64(20_000),
+        ]);
+
+        let tx = test_builder(&utxos, key_material, FeeRate(0))
+            .pay_to(
+                ReceiverInfo {
+                    address: hex::encode(vec![0; 32]),
+                    asset: Asset::token_u64(30_000),
+                },
+                0,
+            )
+            .build()
+            .unwrap();
+
+        assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(tx.outputs.len(), 1);
+        assert!(tx.fees.is_empty());
+        assert!(tx_is_valid(&tx, 0, |v| utxos.get(v)).0);
+    }
+
+    #[test]
+    /// Checks that item payments select matching items and return item change
+    fn test_build_item_payment() {
+        let genesis_hash = "genesis_hash".to_owned();
+        let (utxos, key_material) = test_utxos(&[
+            Asset::item(10, Some(genesis_hash.clone()), None),
+            Asset::item(10, Some("other_hash".to_owned()), None),
+            Asset::token_u64(10_000),
+        ]);
+
+        let tx = test_builder(&utxos, key_material, FeeRate(1))
+            .pay_to(
+                ReceiverInfo {
+                    address: hex::encode(vec![0; 32]),
+                    asset: Asset::item(4, Some(genesis_hash.clone()), None),
+                },
+                0,
+            )
+            .build()
+            .unwrap();
+
+        let item_change = tx
+            .outputs
+            .iter()
+            .find(|o| o.script_public_key == Some(hex::encode(vec![2; 32])) && o.value.is_item());
+
+        assert_eq!(tx.inputs.len(), 2);
+        assert_eq!(
+            item_change.map(|o| o.value.clone()),
+            Some(Asset::item(6, Some(genesis_hash), None))
+        );
+        assert!(tx_is_valid(&tx, 0, |v| utxos.get(v)).0);
+    }
+
+    #[test]
+    /// Checks that absolute and relative locks are signed and enforced on validation
+    fn test_build_locked_payment() {
+        let (utxos, key_material) = test_utxos(&[Asset::token_u64(10_000)]);
+        let utxo_height = |_: &OutPoint| Some(15);
+
+        let tx = test_builder(&utxos, key_material, FeeRate(0))
+            .with_lock_height(10)
+            .with_relative_lock(5)
+            .pay_to(
+                ReceiverInfo {
+                    address: hex::encode(vec![0; 32]),
+                    asset: Asset::token_u64(10_000),
+                },
+                0,
+            )
+            .build()
+            .unwrap();
+
+        let mut unlocked_tx = tx.clone();
+        unlocked_tx.lock_height = 0;
+
+        assert_eq!(tx.lock_height, 10);
+        assert_eq!(tx.inputs[0].sequence, 5);
+        assert!(!tx_is_valid_with_utxo_heights(&tx, 9, |v| utxos.get(v), utxo_height).0);
+        assert!(!tx_is_valid_with_utxo_heights(&tx, 19, |v| utxos.get(v), utxo_height).0);
+        assert!(tx_is_valid_with_utxo_heights(&tx, 20, |v| utxos.get(v), utxo_height).0);
+        assert!(!tx_is_valid(&tx, 20, |v| utxos.get(v)).0);
+        assert!(!tx_is_valid_with_utxo_heights(&unlocked_tx, 20, |v| utxos.get(v), utxo_height).0);
+    }
+
+    #[test]
+    /// Checks that payments exceeding the spendable outputs fail
+    fn test_build_insufficient_funds() {
+        let (utxos, key_material) = test_utxos(&[Asset::token_u64(1_000)]);
+
+        let result = test_builder(&utxos, key_material, FeeRate(1))
+            .pay_to(
+                ReceiverInfo {
+                    address: hex::encode(vec![0; 32]),
+                    asset: Asset::token_u64(1_000),
+                },
+                0,
+            )
+            .build();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    /// Checks that a changeless item payment still pays the fee for its item change
+    fn test_build_item_payment_without_change() {
+        let genesis_hash = "genesis_hash".to_owned();
+        let mut assets = vec![Asset::item(10, Some(genesis_hash.clone()), None)];
+        assets.extend((1..=60).map(|n| Asset::token_u64(n * 10)));
+        let (utxos, key_material) = test_utxos(&assets);
+        let fee_rate = FeeRate(1);
+
+        let tx = test_builder(&utxos, key_material, fee_rate)
+            .pay_to(
+                ReceiverInfo {
+                    address: hex::encode(vec![0; 32]),
+                    asset: Asset::item(4, Some(genesis_hash), None),
+                },
+                0,
+            )
+            .build()
+            .unwrap();
+
+        assert!(tx.outputs.iter().all(|o| !o.value.is_token()));
+        assert!(tx_meets_fee_policy(&tx, &FeePolicy::new(fee_rate, TokenAmount(0))).0);
+        assert!(tx_is_valid(&tx, 0, |v| utxos.get(v)).0);
+    }
+
+    #[test]
+    /// Checks that a fee cannot be paid without a fee address
+    fn test_build_fee_without_fee_address() {
+        let (utxos, key_material) = test_utxos(&[Asset::token_u64(10_000)]);
+
+        let result = TransactionBuilder::new()
+            .with_utxos(utxos)
+            .with_key_material(key_material)
+            .with_fee(FeeRate(1), String::new())
+            .with_change_address(hex::encode(vec![2; 32]))
+            .pay_to(
+                ReceiverInfo {
+                    address: hex::encode(vec![0; 32]),
+                    asset: Asset::token_u64(9_000),
+                },
+                0,
+            )
+            .build();
+
+        assert_eq!(result, Err("No fee address provided".to_string()));
+    }
+}
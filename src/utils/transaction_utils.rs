@@ -3,6 +3,7 @@ use crate::crypto::sha3_256;
 use crate::crypto::sign_ed25519::{self as sign, sign_detached, PublicKey, SecretKey};
 use crate::primitives::asset::Asset;
 use crate::primitives::druid::{DdeValues, DruidExpectation};
+use crate::primitives::network::Network;
 use crate::primitives::transaction::*;
 use crate::script::lang::Script;
 use crate::script::{OpCodes, StackEntry};
@@ -10,6 +11,7 @@ use bincode::serialize;
 use std::collections::BTreeMap;
 use tracing::debug;
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ReceiverInfo {
     pub address: String,
     pub asset: Asset,
@@ -166,32 +168,165 @@ pub fn get_stack_entry_signable_string(entry: &StackEntry) -> String {
     }
 }
 
-/// Constructs signable string from both TxIns and TxOuts
+/// Constructs the hash signed for a TxIn of a mainnet transaction
 ///
 /// ### Arguments
 ///
 /// * `tx_in`   - TxIn values
-/// * `tx_out`  - TxOut values
-pub fn construct_tx_in_out_signable_hash(tx_in: &TxIn, tx_out: &[TxOut]) -> String {
-    let mut signable_list = tx_out
-        .iter()
-        .map(|tx| {
-            debug!("txout: {:?}", tx);
-            serde_json::to_string(tx).unwrap_or("".to_string())
-        })
-        .collect::<Vec<String>>();
+/// * `tx`      - Transaction the TxIn belongs to
+pub fn construct_tx_in_out_signable_hash(tx_in: &TxIn, tx: &Transaction) -> String {
+    construct_tx_in_out_signable_hash_for_network(tx_in, tx, Network::Mainnet)
+}
+
+/// Constructs the hash signed for a TxIn on a given network, committing to every
+/// part of the transaction except the input scripts
+///
+/// ### Arguments
+///
+/// * `tx_in`       - TxIn values
+/// * `tx`          - Transaction the TxIn belongs to
+/// * `network`     - Network the transaction is signed for
+pub fn construct_tx_in_out_signable_hash_for_network(
+    tx_in: &TxIn,
+    tx: &Transaction,
+    network: Network,
+) -> String {
+    let preimage = construct_tx_in_out_signable_preimage(tx_in, tx, network);
+    debug!("Preimage for signing: {}", hex::encode(&preimage));
+
+    hex::encode(sha3_256::digest(&preimage))
+}
+
+/// Constructs the binary preimage hashed for signing a TxIn. All integers are
+/// little-endian and the layout is:
+///
+/// * `version`         - u8, `SIGNABLE_PREIMAGE_VERSION`
+/// * `network`         - u32, id of the network
+/// * `tx version`      - u64, version of the transaction
+/// * `lock_height`     - u64
+/// * `outputs`         - u32 count, then each TxOut
+/// * `fees`            - u32 count, then each fee TxOut
+/// * `druid_info`      - u8 presence flag, then the DRUID info if present
+/// * `previous_out`    - u8 presence flag, then `t_hash` as a string and `n` as i32
+/// * `sequence`        - u64
+///
+/// A TxOut is its asset, then locktime as u64, then address as an optional string. DRUID
+/// info is the DRUID as a string, participants as u64, a u32 count of expectations each
+/// with `from` and `to` as strings followed by its asset, then genesis hash as an optional
+/// string.
+///
+/// A token asset is the tag `0` followed by its amount as u64. An item asset is the tag
+/// `1` followed by its amount as u64, then genesis hash and metadata as optional strings.
+/// A string is its byte length as u32 followed by its UTF-8 bytes, and an optional string
+/// is a u8 presence flag followed by the string if present.
+///
+/// ### Arguments
+///
+/// * `tx_in`       - TxIn values
+/// * `tx`          - Transaction the TxIn belongs to
+/// * `network`     - Network the transaction is signed for
+pub fn construct_tx_in_out_signable_preimage(
+    tx_in: &TxIn,
+    tx: &Transaction,
+    network: Network,
+) -> Vec<u8> {
+    let mut preimage = vec![SIGNABLE_PREIMAGE_VERSION];
+    preimage.extend_from_slice(&network.id().to_le_bytes());
+    preimage.extend_from_slice(&(tx.version as u64).to_le_bytes());
+    preimage.extend_from_slice(&tx.lock_height.to_le_bytes());
+    push_preimage_tx_outs(&mut preimage, &tx.outputs);
+    push_preimage_tx_outs(&mut preimage, &tx.fees);
+
+    match &tx.druid_info {
+        Some(druid_info) => {
+            preimage.push(1);
+            push_preimage_string(&mut preimage, &druid_info.druid);
+            preimage.extend_from_slice(&(druid_info.participants as u64).to_le_bytes());
+            preimage.extend_from_slice(&(druid_info.expectations.len() as u32).to_le_bytes());
+            for expectation in &druid_info.expectations {
+                push_preimage_string(&mut preimage, &expectation.from);
+                push_preimage_string(&mut preimage, &expectation.to);
+                push_preimage_asset(&mut preimage, &expectation.asset);
+            }
+            push_optional_preimage_string(&mut preimage, druid_info.genesis_hash.as_deref());
+        }
+        None => preimage.push(0),
+    }
+
+    match &tx_in.previous_out {
+        Some(out_point) => {
+            preimage.push(1);
+            push_preimage_string(&mut preimage, &out_point.t_hash);
+            preimage.extend_from_slice(&out_point.n.to_le_bytes());
+        }
+        None => preimage.push(0),
+    }
+    preimage.extend_from_slice(&tx_in.sequence.to_le_bytes());
+
+    preimage
+}
 
-    let tx_in_value = serde_json::to_string(&tx_in.previous_out).unwrap_or("".to_string());
+/// Appends a count of TxOuts, then each TxOut, to a signable preimage
+///
+/// ### Arguments
+///
+/// * `preimage`    - Preimage to append to
+/// * `tx_outs`     - TxOuts to append
+fn push_preimage_tx_outs(preimage: &mut Vec<u8>, tx_outs: &[TxOut]) {
+    preimage.extend_from_slice(&(tx_outs.len() as u32).to_le_bytes());
+    for out in tx_outs {
+        push_preimage_asset(preimage, &out.value);
+        preimage.extend_from_slice(&out.locktime.to_le_bytes());
+        push_optional_preimage_string(preimage, out.script_public_key.as_deref());
+    }
+}
+
+/// Appends an asset to a signable preimage
+///
+/// ### Arguments
+///
+/// * `preimage`    - Preimage to append to
+/// * `asset`       - Asset to append
+fn push_preimage_asset(preimage: &mut Vec<u8>, asset: &Asset) {
+    match asset {
+        Asset::Token(tokens) => {
+            preimage.push(0);
+            preimage.extend_from_slice(&tokens.0.to_le_bytes());
+        }
+        Asset::Item(item) => {
+            preimage.push(1);
+            preimage.extend_from_slice(&item.amount.to_le_bytes());
+            push_optional_preimage_string(preimage, item.genesis_hash.as_deref());
+            push_optional_preimage_string(preimage, item.metadata.as_deref());
+        }
+    }
+}
 
-    signable_list.push(tx_in_value);
-    let signable = signable_list.join("");
-    debug!("Formatted string for signing: {signable}");
-    debug!(
-        "Hash: {:?}",
-        hex::encode(sha3_256::digest(signable.as_bytes()))
-    );
+/// Appends a length-prefixed string to a signable preimage
+///
+/// ### Arguments
+///
+/// * `preimage`    - Preimage to append to
+/// * `value`       - String to append
+fn push_preimage_string(preimage: &mut Vec<u8>, value: &str) {
+    preimage.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    preimage.extend_from_slice(value.as_bytes());
+}
 
-    hex::encode(sha3_256::digest(signable.as_bytes()))
+/// Appends a presence flag and, if present, a length-prefixed string to a signable preimage
+///
+/// ### Arguments
+///
+/// * `preimage`    - Preimage to append to
+/// * `value`       - Optional string to append
+fn push_optional_preimage_string(preimage: &mut Vec<u8>, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            preimage.push(1);
+            push_preimage_string(preimage, value);
+        }
+        None => preimage.push(0),
+    }
 }
 
 /// Constructs signable string for Script stack
@@ -338,12 +473,32 @@ pub fn update_utxo_set(current_utxo: &mut BTreeMap<OutPoint, Transaction>) {
     });
 }
 
-/// Constructs a search-valid hash for a transaction to be added to the blockchain
+/// Constructs a search-valid hash for a transaction to be added to the blockchain.
+/// This is the transaction id referenced by `OutPoint`s, and does not change if
+/// the input signatures are altered.
 ///
 /// ### Arguments
 ///
 /// * `tx`  - Transaction to hash
 pub fn construct_tx_hash(tx: &Transaction) -> String {
+    construct_tx_bytes_hash(&tx.without_witness())
+}
+
+/// Constructs a hash over the full transaction, including input signatures
+///
+/// ### Arguments
+///
+/// * `tx`  - Transaction to hash
+pub fn construct_tx_witness_hash(tx: &Transaction) -> String {
+    construct_tx_bytes_hash(tx)
+}
+
+/// Hashes the serialized transaction into a prefixed, truncated hash
+///
+/// ### Arguments
+///
+/// * `tx`  - Transaction to hash
+fn construct_tx_bytes_hash(tx: &Transaction) -> String {
     let bytes = match serialize(tx) {
         Ok(bytes) => bytes,
         Err(_) => vec![],
@@ -374,6 +529,7 @@ pub fn construct_create_tx_in(
     vec![TxIn {
         previous_out: None,
         script_signature: Script::new_create_asset(block_num, asset_hash, signature, public_key),
+        sequence: 0,
     }]
 }
 
@@ -434,9 +590,7 @@ pub fn construct_payment_tx(
         script_public_key: Some(receiver.address),
     };
     let tx_outs = vec![tx_out];
-    let final_tx_ins = update_input_signatures(&tx_ins, &tx_outs, key_material);
-
-    construct_tx_core(final_tx_ins, tx_outs, fee)
+    construct_signed_tx(tx_ins, tx_outs, fee, key_material)
 }
 
 /// Constructs a P2SH transaction to pay a receiver
@@ -464,9 +618,58 @@ pub fn construct_p2sh_tx(
         script_public_key: Some(script_hash),
     };
     let tx_outs = vec![tx_out];
-    let final_tx_ins = update_input_signatures(&tx_ins, &tx_outs, key_material);
+    construct_signed_tx(tx_ins, tx_outs, fee, key_material)
+}
 
-    construct_tx_core(final_tx_ins, tx_outs, fee)
+/// Constructs a transaction to pay multiple receivers in one batch. Each
+/// receiver may be paid a different asset with its own locktime.
+///
+/// ### Arguments
+///
+/// * `tx_ins`          - Input/s to pay from
+/// * `receivers`       - Receivers to pay, each with the block height below which its payment is restricted
+/// * `fee`             - Fee to pay
+/// * `key_material`    - Key material for signing
+pub fn construct_batch_payment_tx(
+    tx_ins: Vec<TxIn>,
+    receivers: Vec<(ReceiverInfo, u64)>,
+    fee: Option<ReceiverInfo>,
+    key_material: &BTreeMap<OutPoint, (PublicKey, SecretKey)>,
+) -> Transaction {
+    let tx_outs: Vec<TxOut> = receivers
+        .into_iter()
+        .map(|(receiver, locktime)| TxOut {
+            value: receiver.asset,
+            locktime,
+            script_public_key: Some(receiver.address),
+        })
+        .collect();
+    construct_signed_tx(tx_ins, tx_outs, fee, key_material)
+}
+
+/// Constructs a transaction to pay multiple P2SH scripts in one batch
+///
+/// ### Arguments
+///
+/// * `tx_ins`          - Input/s to pay from
+/// * `fee`             - Fee to pay
+/// * `payments`        - Scripts to pay, each with the asset to send and its locktime
+/// * `key_material`    - Key material for signing
+pub fn construct_batch_p2sh_tx(
+    tx_ins: Vec<TxIn>,
+    fee: Option<ReceiverInfo>,
+    payments: Vec<(Script, Asset, u64)>,
+    key_material: &BTreeMap<OutPoint, (PublicKey, SecretKey)>,
+) -> Transaction {
+    let receivers = payments
+        .into_iter()
+        .map(|(script, asset, locktime)| {
+            let address = construct_p2sh_address(&script);
+            (ReceiverInfo { address, asset }, locktime)
+        })
+        .collect();
+
+    construct_batch_payment_tx(tx_ins, receivers, fee, key_material)
 }
 
 /// Constructs a P2SH transaction to burn tokens
@@ -488,10 +691,7 @@ pub fn construct_burn_tx(
         ..Default::default()
     };
     let tx_outs = vec![tx_out];
-
-    let final_tx_ins = update_input_signatures(&tx_ins, &tx_outs, key_material);
-
-    construct_tx_core(final_tx_ins, tx_outs, fee)
+    construct_signed_tx(tx_ins, tx_outs, fee, key_material)
 }
 
 /// Constructs a transaction to pay a receiver
@@ -528,6 +728,25 @@ pub fn construct_tx_core(
     }
 }
 
+/// Constructs a transaction and signs its inputs, once the fee is in place
+///
+/// ### Arguments
+///
+/// * `tx_ins`          - Inputs to the transaction
+/// * `tx_outs`         - Outputs of the transaction
+/// * `fee`             - Fee to pay
+/// * `key_material`    - Key material for signing
+pub fn construct_signed_tx(
+    tx_ins: Vec<TxIn>,
+    tx_outs: Vec<TxOut>,
+    fee: Option<ReceiverInfo>,
+    key_material: &BTreeMap<OutPoint, (PublicKey, SecretKey)>,
+) -> Transaction {
+    let mut tx = construct_tx_core(tx_ins, tx_outs, fee);
+    tx.inputs = update_input_signatures(&tx, key_material);
+    tx
+}
+
 /// Constructs a core item-based payment transaction
 ///
 /// ### Arguments
@@ -547,9 +766,6 @@ pub fn construct_rb_tx_core(
     key_material: &BTreeMap<OutPoint, (PublicKey, SecretKey)>,
 ) -> Transaction {
     let mut tx = construct_tx_core(tx_ins, tx_outs, fee);
-
-    tx.inputs = update_input_signatures(&tx.inputs, &tx.outputs, key_material);
-
     tx.druid_info = Some(DdeValues {
         druid,
         participants: 2,
@@ -557,48 +773,55 @@ pub fn construct_rb_tx_core(
         genesis_hash: None,
     });
 
+    tx.inputs = update_input_signatures(&tx, key_material);
     tx
 }
 
-/// Updates the input signatures with output information
+/// Updates the input signatures of a mainnet transaction. Every other field of the
+/// transaction must be final, as it is committed to by the signatures.
 ///
 /// ### Arguments
 ///
-/// * `tx_ins`          - Inputs to the transaction
-/// * `tx_outs`         - Outputs of the transaction
+/// * `tx`              - Transaction to sign the inputs of
 /// * `key_material`    - Key material for signing
 pub fn update_input_signatures(
-    tx_ins: &[TxIn],
-    tx_outs: &[TxOut],
+    tx: &Transaction,
     key_material: &BTreeMap<OutPoint, (PublicKey, SecretKey)>,
 ) -> Vec<TxIn> {
-    let mut tx_ins = tx_ins.to_vec();
-
-    debug!("TxOuts: {:?}", tx_outs);
-    for tx_in in tx_ins.iter_mut() {
-        let signable_prev_out = TxIn {
-            previous_out: tx_in.previous_out.clone(),
-            script_signature: Script::new(),
-        };
+    update_input_signatures_for_network(tx, Network::Mainnet, key_material)
+}
 
-        debug!("Signable prev out: {:?}", signable_prev_out.previous_out);
-        let signable_hash = construct_tx_in_out_signable_hash(&signable_prev_out, tx_outs);
-        let previous_out = signable_prev_out.previous_out;
+/// Updates the input signatures of a transaction on a given network. Every other
+/// field of the transaction must be final, as it is committed to by the signatures.
+///
+/// ### Arguments
+///
+/// * `tx`              - Transaction to sign the inputs of
+/// * `network`         - Network the transaction is signed for
+/// * `key_material`    - Key material for signing
+pub fn update_input_signatures_for_network(
+    tx: &Transaction,
+    network: Network,
+    key_material: &BTreeMap<OutPoint, (PublicKey, SecretKey)>,
+) -> Vec<TxIn> {
+    let mut tx_ins = tx.inputs.clone();
 
+    debug!("TxOuts: {:?}", tx.outputs);
+    for tx_in in tx_ins.iter_mut() {
+        let signable_hash = construct_tx_in_out_signable_hash_for_network(tx_in, tx, network);
         debug!("Signable hash: {:?}", signable_hash);
 
-        if previous_out.is_some() && key_material.get(&previous_out.clone().unwrap()).is_some() {
-            let pk = key_material.get(&previous_out.clone().unwrap()).unwrap().0;
-            let sk = &key_material.get(&previous_out.unwrap()).unwrap().1;
-
-            let script_signature = Script::pay2pkh(
+        if let Some((pk, sk)) = tx_in
+            .previous_out
+            .as_ref()
+            .and_then(|out_point| key_material.get(out_point))
+        {
+            tx_in.script_signature = Script::pay2pkh(
                 signable_hash.clone(),
                 sign_detached(signable_hash.as_bytes(), sk),
-                pk,
+                *pk,
                 None,
             );
-
-            tx_in.script_signature = script_signature;
         }
     }
 
@@ -687,6 +910,7 @@ pub fn construct_payment_tx_ins(tx_values: Vec<TxConstructor>) -> Vec<TxIn> {
         let signable_prev_out = TxIn {
             previous_out: Some(entry.previous_out),
             script_signature: Script::new(),
+            sequence: 0,
         };
         let previous_out = signable_prev_out.previous_out;
         let script_signature = Script::new();
@@ -694,6 +918,7 @@ pub fn construct_payment_tx_ins(tx_values: Vec<TxConstructor>) -> Vec<TxIn> {
         tx_ins.push(TxIn {
             previous_out,
             script_signature,
+            sequence: 0,
         });
     }
 
@@ -714,6 +939,7 @@ pub fn construct_p2sh_redeem_tx_ins(tx_values: TxConstructor, script: Script) ->
     tx_ins.push(TxIn {
         previous_out,
         script_signature: script,
+        sequence: 0,
     });
 
     tx_ins
@@ -738,8 +964,8 @@ pub fn construct_dde_tx(
 ) -> Transaction {
     let mut tx = construct_tx_core(tx_ins, tx_outs, fee);
 
-    tx.inputs = update_input_signatures(&tx.inputs, &tx.outputs, key_material);
     tx.druid_info = Some(druid_info);
+    tx.inputs = update_input_signatures(&tx, key_material);
 
     tx
 }
@@ -752,7 +978,9 @@ mod tests {
     use crate::crypto::sign_ed25519::{self as sign, Signature};
     use crate::primitives::asset::{AssetValues, ItemAsset, TokenAmount};
     use crate::script::OpCodes;
-    use crate::utils::script_utils::{tx_has_valid_p2sh_script, tx_outs_are_valid};
+    use crate::utils::script_utils::{
+        tx_has_valid_p2sh_script, tx_is_valid, tx_is_valid_with_utxo_heights, tx_outs_are_valid,
+    };
 
     #[test]
     // Creates a valid payment transaction
@@ -1079,6 +1307,171 @@ mod tests {
         assert!(tx_outs_are_valid(&payment_tx_valid.outputs, &[], tx_ins_spent).0);
     }
 
+    #[test]
+    /// Creates a valid batch payment transaction paying mixed assets
+    fn test_construct_valid_batch_payment_tx() {
+        let (pk, sk) = sign::gen_keypair();
+        let genesis_hash = "item_tx_hash".to_string();
+        let token_prev_out = OutPoint::new(hex::encode(vec![0, 0, 0]), 0);
+        let item_prev_out = OutPoint::new(hex::encode(vec![0, 0, 0]), 1);
+        let mut key_material = BTreeMap::new();
+        key_material.insert(token_prev_out.clone(), (pk, sk.clone()));
+        key_material.insert(item_prev_out.clone(), (pk, sk));
+
+        let mut utxo_set = BTreeMap::new();
+        let address = construct_address(&pk);
+        utxo_set.insert(
+            token_prev_out.clone(),
+            TxOut::new_token_amount(address.clone(), TokenAmount(3000), None),
+        );
+        utxo_set.insert(
+            item_prev_out.clone(),
+            TxOut::new_item_amount(
+                address,
+                ItemAsset::new(10, Some(genesis_hash.clone()), None),
+                None,
+            ),
+        );
+
+        let tx_const = |previous_out| TxConstructor {
+            previous_out,
+            signatures: vec![],
+            pub_keys: vec![],
+            address_version: None,
+        };
+        let tx_ins =
+            construct_payment_tx_ins(vec![tx_const(token_prev_out), tx_const(item_prev_out)]);
+        let receivers = (0..3u8)
+            .map(|n| {
+                let receiver = ReceiverInfo {
+                    address: hex::encode(vec![n; 32]),
+                    asset: Asset::Token(TokenAmount(1000)),
+                };
+                (receiver, n as u64)
+            })
+            .chain(std::iter::once((
+                ReceiverInfo {
+                    address: hex::encode(vec![4; 32]),
+                    asset: Asset::item(10, Some(genesis_hash), None),
+                },
+                0,
+            )))
+            .collect();
+
+        let batch_tx = construct_batch_payment_tx(tx_ins, receivers, None, &key_material);
+
+        assert_eq!(batch_tx.outputs.len(), 4);
+        assert_eq!(batch_tx.outputs[2].locktime, 2);
+        assert!(tx_is_valid(&batch_tx, 2, |v| utxo_set.get(v)).0);
+    }
+
+    /// Creates a signed DDE transaction paying a fee, and the UTXO set it spends from
+    fn test_signed_dde_tx() -> (Transaction, BTreeMap<OutPoint, TxOut>) {
+        let (pk, sk) = sign::gen_keypair();
+        let prev_out = OutPoint::new(hex::encode(vec![0, 0, 0]), 0);
+        let key_material = BTreeMap::from([(prev_out.clone(), (pk, sk))]);
+        let utxo_set = BTreeMap::from([(
+            prev_out.clone(),
+            TxOut::new_token_amount(construct_address(&pk), TokenAmount(100), None),
+        )]);
+
+        let tx_ins = construct_payment_tx_ins(vec![TxConstructor {
+            previous_out: prev_out,
+            signatures: vec![],
+            pub_keys: vec![],
+            address_version: None,
+        }]);
+        let tx_outs = vec![TxOut::new_token_amount(
+            hex::encode([1; 32]),
+            TokenAmount(90),
+            None,
+        )];
+        let fee = ReceiverInfo {
+            address: hex::encode([2; 32]),
+            asset: Asset::token_u64(10),
+        };
+        let druid_info = DdeValues {
+            druid: "druid".to_owned(),
+            participants: 2,
+            ..Default::default()
+        };
+        let tx = construct_dde_tx(druid_info, tx_ins, tx_outs, Some(fee), &key_material);
+
+        (tx, utxo_set)
+    }
+
+    #[test]
+    /// Checks that altering the fees, version, DRUID info or lock height of a signed
+    /// transaction invalidates its signatures
+    fn test_signatures_commit_to_whole_tx() {
+        let (tx, utxo_set) = test_signed_dde_tx();
+        let is_valid = |tx: &Transaction| tx_is_valid(tx, 10, |v| utxo_set.get(v));
+
+        let mut fee_redirected = tx.clone();
+        fee_redirected.fees[0].script_public_key = Some(hex::encode([3; 32]));
+        let mut version_changed = tx.clone();
+        version_changed.version = 1;
+        let mut druid_changed = tx.clone();
+        druid_changed.druid_info = None;
+        let mut lock_changed = tx.clone();
+        lock_changed.lock_height = 1;
+
+        assert_eq!(is_valid(&tx), (true, "".to_string()));
+        for altered_tx in [fee_redirected, version_changed, druid_changed, lock_changed].iter() {
+            assert_eq!(
+                is_valid(altered_tx),
+                (false, "Invalid signature or script structure".to_string())
+            );
+        }
+    }
+
+    #[test]
+    /// Creates a valid batch P2SH transaction
+    fn test_construct_valid_batch_p2sh_tx() {
+        let (tx_ins, _drs_block_hash, key_material) = test_construct_valid_inputs(None);
+        let script = Script::new_for_coinbase(10);
+
+        let batch_tx = construct_batch_p2sh_tx(
+            tx_ins,
+            None,
+            vec![
+                (script.clone(), Asset::token_u64(10), 0),
+                (script.clone(), Asset::token_u64(20), 5),
+            ],
+            &key_material,
+        );
+
+        assert_eq!(batch_tx.outputs.len(), 2);
+        assert!(batch_tx
+            .outputs
+            .iter()
+            .all(|out| out.script_public_key == Some(construct_p2sh_address(&script))));
+    }
+
+    #[test]
+    /// Checks that transactions paying more than the maximum number of outputs are rejected
+    fn test_batch_payment_exceeds_max_outputs() {
+        let tx_outs: Vec<TxOut> = (0..=MAX_TX_OUTPUTS)
+            .map(|_| TxOut::new_token_amount(hex::encode(vec![0; 32]), TokenAmount(1), None))
+            .collect();
+        let tx_ins_spent = AssetValues::token_u64(tx_outs.len() as u64);
+
+        let result = tx_outs_are_valid(&tx_outs, &[], tx_ins_spent);
+
+        assert_eq!(
+            result,
+            (false, "Transaction has too many outputs".to_string())
+        );
+        assert!(
+            tx_outs_are_valid(
+                &tx_outs[1..],
+                &[],
+                AssetValues::token_u64(MAX_TX_OUTPUTS as u64)
+            )
+            .0
+        );
+    }
+
     #[test]
     // Creates a valid UTXO set
     fn test_construct_valid_utxo_set() {
@@ -1412,6 +1805,91 @@ mod tests {
         assert_eq!(actual_pub_addresses, expected_pub_addresses);
     }
 
+    #[test]
+    /// Checks that the transaction id is unaffected by changes to input signatures
+    fn test_construct_tx_hash_ignores_signatures() {
+        let (pk, sk) = sign::gen_keypair();
+        let tx_in = TxIn::new_from_input(
+            OutPoint::new("000000".to_owned(), 0),
+            Script::pay2pkh(
+                "000000".to_owned(),
+                sign::sign_detached(b"000000", &sk),
+                pk,
+                None,
+            ),
+        );
+        let tx_ins = vec![tx_in];
+        let tx_outs = vec![TxOut::new_token_amount(
+            construct_address(&pk),
+            TokenAmount(1),
+            None,
+        )];
+        let tx = construct_tx_core(tx_ins, tx_outs, None);
+
+        let mut malleated_tx = tx.clone();
+        malleated_tx.inputs[0].script_signature = Script::new();
+
+        assert_eq!(construct_tx_hash(&tx), construct_tx_hash(&malleated_tx));
+        assert_ne!(
+            construct_tx_witness_hash(&tx),
+            construct_tx_witness_hash(&malleated_tx)
+        );
+    }
+
+    #[test]
+    /// Checks that a third party cannot change the id of a valid transaction, as every
+    /// change to non-witness data also invalidates its signatures
+    fn test_construct_tx_hash_non_witness_malleability() {
+        let (tx, utxo_set) = test_signed_dde_tx();
+        let tx_hash = construct_tx_hash(&tx);
+
+        let mut output_changed = tx.clone();
+        output_changed.outputs[0].script_public_key = Some(hex::encode([3; 32]));
+        let mut fee_changed = tx.clone();
+        fee_changed.fees[0].script_public_key = Some(hex::encode([3; 32]));
+        let mut version_changed = tx.clone();
+        version_changed.version += 1;
+        let mut druid_changed = tx.clone();
+        druid_changed.druid_info.as_mut().unwrap().participants = 3;
+        let mut lock_changed = tx.clone();
+        lock_changed.lock_height = 1;
+        let mut sequence_changed = tx.clone();
+        sequence_changed.inputs[0].sequence = 1;
+
+        let altered_txs = [
+            output_changed,
+            fee_changed,
+            version_changed,
+            druid_changed,
+            lock_changed,
+            sequence_changed,
+        ];
+        for altered_tx in altered_txs.iter() {
+            assert_ne!(construct_tx_hash(altered_tx), tx_hash);
+            assert!(
+                !tx_is_valid_with_utxo_heights(altered_tx, 10, |v| utxo_set.get(v), |_| Some(0)).0
+            );
+        }
+    }
+
+    #[test]
+    /// Checks that coinbase transaction ids still commit to their block number
+    fn test_construct_tx_hash_keeps_coinbase_script() {
+        let coinbase = |block_number| {
+            let tx_in = TxIn {
+                previous_out: None,
+                script_signature: Script::new_for_coinbase(block_number),
+                sequence: 0,
+            };
+            construct_tx_core(vec![tx_in], vec![TxOut::new()], None)
+        };
+
+        assert_ne!(
+            construct_tx_hash(&coinbase(1)),
+            construct_tx_hash(&coinbase(2))
+        );
+    }
+
     #[test]
     // Test TxIn signable hash construction; should correlate with test on wallet
     fn test_construct_valid_tx_in_signable_hash() {
@@ -1444,6 +1922,137 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    /// Checks the signable preimage and hash against fixed vectors, which wallets in
+    /// other languages must reproduce
+    fn test_construct_valid_tx_in_out_signable_preimage() {
+        //
+        // Arrange
+        //
+        let tx_in = TxIn::new_from_input(OutPoint::new("000000".to_owned(), 1), Script::new())
+            .with_sequence(3);
+        let tx_outs = vec![
+            TxOut {
+                value: Asset::token_u64(10),
+                locktime: 0,
+                script_public_key: Some("13bd3351b78beb2d0dadf2058dcc926c".to_owned()),
+            },
+            TxOut {
+                value: Asset::item(2, Some("genesis".to_owned()), None),
+                locktime: 5,
+                script_public_key: None,
+            },
+        ];
+
+        let fees = vec![TxOut::new_token_amount(
+            "fee".to_owned(),
+            TokenAmount(1),
+            None,
+        )];
+        let druid_info = DdeValues {
+            druid: "druid".to_owned(),
+            participants: 2,
+            expectations: vec![DruidExpectation {
+                from: "a".to_owned(),
+                to: "b".to_owned(),
+                asset: Asset::token_u64(4),
+            }],
+            genesis_hash: None,
+        };
+        let tx = Transaction {
+            inputs: vec![tx_in.clone()],
+            outputs: tx_outs,
+            version: 6,
+            fees,
+            druid_info: Some(druid_info),
+            lock_height: 7,
+        };
+
+        //
+        // Act
+        //
+        let preimage = construct_tx_in_out_signable_preimage(&tx_in, &tx, Network::Mainnet);
+        let testnet_hash =
+            construct_tx_in_out_signable_hash_for_network(&tx_in, &tx, Network::Testnet);
+        let hash = construct_tx_in_out_signable_hash(&tx_in, &tx);
+
+        //
+        // Assert
+        //
+        let expected_preimage = concat!(
+            // Version
+            "01",
+            // Network
+            "00000000",
+            // Transaction version
+            "0600000000000000",
+            // Lock height
+            "0700000000000000",
+            // Output count
+            "02000000",
+            // Token asset and amount
+            "00",
+            "0a00000000000000",
+            // Locktime
+            "0000000000000000",
+            // Address
+            "01",
+            "20000000",
+            "3133626433333531623738626562326430646164663230353864636339323663",
+            // Item asset and amount
+            "01",
+            "0200000000000000",
+            // Genesis hash
+            "01",
+            "07000000",
+            "67656e65736973",
+            // No metadata
+            "00",
+            // Locktime
+            "0500000000000000",
+            // No address
+            "00",
+            // Fee count
+            "01000000",
+            // Fee asset, locktime and address
+            "00",
+            "0100000000000000",
+            "0000000000000000",
+            "01",
+            "03000000",
+            "666565",
+            // DRUID info
+            "01",
+            "05000000",
+            "6472756964",
+            // Participants
+            "0200000000000000",
+            // Expectation count, then from, to and asset
+            "01000000",
+            "01000000",
+            "61",
+            "01000000",
+            "62",
+            "00",
+            "0400000000000000",
+            // No genesis hash
+            "00",
+            // Previous out
+            "01",
+            "06000000",
+            "303030303030",
+            "01000000",
+            // Sequence
+            "0300000000000000",
+        );
+        assert_eq!(hex::encode(preimage), expected_preimage);
+        assert_eq!(
+            hash,
+            "a7eb5fb0cd643031c8c679402d9dfde6fc8f614a0f4f538e2de742ba9700ba4b"
+        );
+        assert_ne!(hash, testnet_hash);
+    }
+
     #[test]
     // Test TxIn signable asset hash construction; should correlate with test on wallet
     fn test_construct_valid_tx_in_signable_asset_hash() {